@@ -1,5 +1,20 @@
 // 排序
 // 这里实现了冒泡排序, 插入排序, 快速排序, 堆排序, 以及一个简化版的 TimSort
+// 小数组 (不超过 16 个元素) 统一交给排序网络处理
+// 以及几个非比较排序: 计数排序, 基数排序 (LSD/MSD) 和桶排序
+// 还有基于 std::thread::scope 的并行排序, 以及只排一部分的选择算法
+// 然后是统计模式, 记录比较排序的比较, 交换和移动次数, 以及每一步的数组状态
+// 然后是基准测试, 在各种输入分布 (包括专门针对快速排序构造的输入) 上检查并计时所有排序
+// 最后是外部排序, 处理内存放不下的数据
+
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 fn sort<T: Ord>(array: &mut [T]) {
     quick_sort(array)
@@ -10,14 +25,26 @@ fn sort<T: Ord>(array: &mut [T]) {
 }
 
 // 快速排序
-fn quick_sort<T: Ord>(array: &mut [T]) {
-    if array.len() <= 1 {
-        // 基本情况: 空数组或单元素数组已排序
-        return;
+// 用三数取中选基准, 有序和逆序的输入也能大致对半划分
+// 只对较短的一边递归, 较长的一边留在循环里继续划分, 所以递归深度不超过 log2(n)
+// 即使遇到 antiqsort 这样专门构造的输入, 也只是变慢, 不会栈溢出
+fn quick_sort<T: Ord>(mut array: &mut [T]) {
+    while array.len() > NETWORK_MAX {
+        let last = array.len() - 1;
+        array.swap(median_of_three(array), last); // partition 以最后一个元素为基准
+        let pivot = partition(array); // 获取基准位置
+        let (left, right) = std::mem::take(&mut array).split_at_mut(pivot);
+        let right = &mut right[1..];
+        if left.len() < right.len() {
+            quick_sort(left); // 递归排序较短的左半部分
+            array = right;
+        } else {
+            quick_sort(right); // 递归排序较短的右半部分
+            array = left;
+        }
     }
-    let pivot = partition(array); // 获取基准位置
-    quick_sort(&mut array[..pivot]); // 递归排序左半部分
-    quick_sort(&mut array[pivot + 1..]); // 递归排序右半部分
+    // 基本情况: 小数组用排序网络
+    network_sort_by(array, &T::cmp);
 }
 
 fn partition<T: Ord>(array: &mut [T]) -> usize {
@@ -36,7 +63,6 @@ fn partition<T: Ord>(array: &mut [T]) -> usize {
 }
 
 // 冒泡排序
-#[allow(dead_code)]
 fn bubble_sort<T: Ord>(array: &mut [T]) {
     for i in 0..array.len() {
         // 外层循环控制排序轮数
@@ -51,12 +77,16 @@ fn bubble_sort<T: Ord>(array: &mut [T]) {
 }
 
 // 插入排序
-#[allow(dead_code)]
 fn insertion_sort<T: Ord>(array: &mut [T]) {
+    insertion_sort_by(array, &T::cmp)
+}
+
+// 带比较函数的插入排序, 相等元素不会交换, 所以是稳定的
+fn insertion_sort_by<T, F: Fn(&T, &T) -> Ordering>(array: &mut [T], compare: &F) {
     for i in 1..array.len() {
         // 从第二个元素开始 (索引1)
         let mut j = i; // j 是当前要插入的元素位置
-        while j > 0 && array[j] < array[j - 1] {
+        while j > 0 && compare(&array[j], &array[j - 1]) == Ordering::Less {
             // 当前元素比前一个小
             array.swap(j, j - 1); // 交换它们
             j -= 1; // 继续向前比较
@@ -65,7 +95,6 @@ fn insertion_sort<T: Ord>(array: &mut [T]) {
 }
 
 // 堆排序
-#[allow(dead_code)]
 fn heap_sort<T: Ord>(array: &mut [T]) {
     if array.len() <= 1 {
         // 基本情况
@@ -112,22 +141,25 @@ fn heapify<T: Ord>(array: &mut [T], root: usize, end: usize) {
 }
 
 // TimSort 简化版
-#[allow(dead_code)]
 fn tim_sort<T: Ord + Clone>(array: &mut [T]) {
-    const MIN_RUN: usize = 32; // 最小 Run 长度
+    tim_sort_by(array, &T::cmp)
+}
 
+const MIN_RUN: usize = 32; // 最小 Run 长度
+
+// 带比较函数的 TimSort, 插入排序和合并都是稳定的, 所以整体也是稳定的
+fn tim_sort_by<T: Clone, F: Fn(&T, &T) -> Ordering>(array: &mut [T], compare: &F) {
     let len = array.len();
     if len <= MIN_RUN {
-        // 小数组直接插入排序
-        // 这个我们之前实现过
-        insertion_sort(array);
+        // 小数组直接排好
+        sort_run(array, compare);
         return;
     }
 
     // 将数组分成 MIN_RUN 大小的块并排序
     for i in (0..len).step_by(MIN_RUN) {
         let end = std::cmp::min(i + MIN_RUN, len);
-        insertion_sort(&mut array[i..end]);
+        sort_run(&mut array[i..end], compare);
     }
 
     // 合并已排序的 Run
@@ -145,7 +177,7 @@ fn tim_sort<T: Ord + Clone>(array: &mut [T]) {
             // 如果有两个块可以合并
             if mid < right {
                 // 合并它们
-                merge(array, left, mid, right);
+                merge_by(array, left, mid, right, compare);
             }
         }
         // 每次合并后对小块大小的要求大小翻倍
@@ -153,7 +185,23 @@ fn tim_sort<T: Ord + Clone>(array: &mut [T]) {
     }
 }
 
-fn merge<T: Ord + Clone>(array: &mut [T], left: usize, mid: usize, right: usize) {
+// 排好一个 Run, TimSort 是稳定的, 所以两种情况都要用稳定的排序
+// 不超过 NETWORK_MAX 个元素 (小数组, 或者末尾不足一个 Run 的部分) 用稳定的排序网络, 否则用插入排序
+fn sort_run<T, F: Fn(&T, &T) -> Ordering>(run: &mut [T], compare: &F) {
+    if run.len() <= NETWORK_MAX {
+        stable_network_sort_by(run, compare);
+    } else {
+        insertion_sort_by(run, compare);
+    }
+}
+
+fn merge_by<T: Clone, F: Fn(&T, &T) -> Ordering>(
+    array: &mut [T],
+    left: usize,
+    mid: usize,
+    right: usize,
+    compare: &F,
+) {
     let left_part = array[left..mid].to_vec(); // 复制左半部分
     let right_part = array[mid..right].to_vec(); // 复制右半部分
 
@@ -164,7 +212,8 @@ fn merge<T: Ord + Clone>(array: &mut [T], left: usize, mid: usize, right: usize)
     // 这和我们合并链表的方式相同
     // 合并两个已排序数组
     while i < left_part.len() && j < right_part.len() {
-        if left_part[i] <= right_part[j] {
+        // 只有右边严格更小时才先取右边, 相等时取左边以保持稳定
+        if compare(&right_part[j], &left_part[i]) != Ordering::Less {
             array[k] = left_part[i].clone();
             i += 1;
         } else {
@@ -188,26 +237,2259 @@ fn merge<T: Ord + Clone>(array: &mut [T], left: usize, mid: usize, right: usize)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// 排序网络
+// 排序网络是一串固定的 "比较-交换" 操作 (i, j): 如果 array[j] < array[i] 就交换它们
+// 操作序列只取决于长度, 与数据无关, 所以没有难以预测的分支, 对小数组比插入排序快
+// 下面是 2 到 16 个元素的已知最优网络 (比较器个数最少), 可以用 0-1 原理验证其正确性
+// 这些网络被用作快速排序, TimSort, 选择算法, MSD 基数排序和桶排序的基本情况
 
-    #[test]
-    fn test_sort_1() {
-        let mut vec = vec![37, 73, 57, 75, 91, 19, 46, 64];
-        sort(&mut vec);
-        assert_eq!(vec, vec![19, 37, 46, 57, 64, 73, 75, 91]);
+/// 排序网络能处理的最大长度
+const NETWORK_MAX: usize = 16;
+
+/// NETWORKS[n] 是长度为 n 的排序网络, 同一行的比较器互不相交, 可以并行执行
+#[rustfmt::skip]
+const NETWORKS: [&[(usize, usize)]; NETWORK_MAX + 1] = [
+    &[],
+    &[],
+    &[(0, 1)],
+    &[(0, 2), (0, 1), (1, 2)],
+    &[(0, 2), (1, 3), (0, 1), (2, 3), (1, 2)],
+    &[(0, 3), (1, 4), (0, 2), (1, 3), (0, 1), (2, 4), (1, 2), (3, 4), (2, 3)],
+    &[
+        (0, 5), (1, 3), (2, 4), (1, 2), (3, 4), (0, 3), (2, 5),
+        (0, 1), (2, 3), (4, 5), (1, 2), (3, 4),
+    ],
+    &[
+        (0, 6), (2, 3), (4, 5), (0, 2), (1, 4), (3, 6), (0, 1), (2, 5), (3, 4),
+        (1, 2), (4, 6), (2, 3), (4, 5), (1, 2), (3, 4), (5, 6),
+    ],
+    &[
+        (0, 2), (1, 3), (4, 6), (5, 7), (0, 4), (1, 5), (2, 6), (3, 7),
+        (0, 1), (2, 3), (4, 5), (6, 7), (2, 4), (3, 5), (1, 4), (3, 6),
+        (1, 2), (3, 4), (5, 6),
+    ],
+    &[
+        (0, 3), (1, 7), (2, 5), (4, 8), (0, 7), (2, 4), (3, 8), (5, 6),
+        (0, 2), (1, 3), (4, 5), (7, 8), (1, 4), (3, 6), (5, 7),
+        (0, 1), (2, 4), (3, 5), (6, 8), (2, 3), (4, 5), (6, 7),
+        (1, 2), (3, 4), (5, 6),
+    ],
+    &[
+        (0, 8), (1, 9), (2, 7), (3, 5), (4, 6), (0, 2), (1, 4), (5, 8), (7, 9),
+        (0, 3), (2, 4), (5, 7), (6, 9), (0, 1), (3, 6), (8, 9),
+        (1, 5), (2, 3), (4, 8), (6, 7), (1, 2), (3, 5), (4, 6), (7, 8),
+        (2, 3), (4, 5), (6, 7), (3, 4), (5, 6),
+    ],
+    &[
+        (0, 9), (1, 6), (2, 4), (3, 7), (5, 8), (0, 1), (3, 5), (4, 10), (6, 9), (7, 8),
+        (1, 3), (2, 5), (4, 7), (8, 10), (0, 4), (1, 2), (3, 7), (5, 9), (6, 8),
+        (0, 1), (2, 6), (4, 5), (7, 8), (9, 10), (2, 4), (3, 6), (5, 7), (8, 9),
+        (1, 2), (3, 4), (5, 6), (7, 8), (2, 3), (4, 5), (6, 7),
+    ],
+    &[
+        (0, 8), (1, 7), (2, 6), (3, 11), (4, 10), (5, 9),
+        (0, 1), (2, 5), (3, 4), (6, 9), (7, 8), (10, 11),
+        (0, 2), (1, 6), (5, 10), (9, 11), (0, 3), (1, 2), (4, 6), (5, 7), (8, 11), (9, 10),
+        (1, 4), (3, 5), (6, 8), (7, 10), (1, 3), (2, 5), (6, 9), (8, 10),
+        (2, 3), (4, 5), (6, 7), (8, 9), (4, 6), (5, 7), (3, 4), (5, 6), (7, 8),
+    ],
+    &[
+        (0, 12), (1, 10), (2, 9), (3, 7), (5, 11), (6, 8),
+        (1, 6), (2, 3), (4, 11), (7, 9), (8, 10),
+        (0, 4), (1, 2), (3, 6), (7, 8), (9, 10), (11, 12),
+        (4, 6), (5, 9), (8, 11), (10, 12), (0, 5), (3, 8), (4, 7), (6, 11), (9, 10),
+        (0, 1), (2, 5), (6, 9), (7, 8), (10, 11), (1, 3), (2, 4), (5, 6), (9, 10),
+        (1, 2), (3, 4), (5, 7), (6, 8), (2, 3), (4, 5), (6, 7), (8, 9), (3, 4), (5, 6),
+    ],
+    &[
+        (0, 1), (2, 3), (4, 5), (6, 7), (8, 9), (10, 11), (12, 13),
+        (0, 2), (1, 3), (4, 8), (5, 9), (10, 12), (11, 13),
+        (0, 4), (1, 2), (3, 7), (5, 8), (6, 10), (9, 13), (11, 12),
+        (0, 6), (1, 5), (3, 9), (4, 10), (7, 13), (8, 12),
+        (2, 10), (3, 11), (4, 6), (7, 9), (1, 3), (2, 8), (5, 11), (6, 7), (10, 12),
+        (1, 4), (2, 6), (3, 5), (7, 11), (8, 10), (9, 12),
+        (2, 4), (3, 6), (5, 8), (7, 10), (9, 11), (3, 4), (5, 6), (7, 8), (9, 10),
+        (6, 7),
+    ],
+    &[
+        (0, 13), (1, 12), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10),
+        (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (11, 12),
+        (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13),
+        (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14),
+        (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14),
+        (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14),
+        (2, 4), (3, 6), (9, 12), (11, 13), (3, 5), (6, 8), (7, 9), (10, 12),
+        (3, 4), (5, 6), (7, 8), (9, 10), (11, 12), (6, 7), (8, 9),
+    ],
+    &[
+        (0, 13), (1, 12), (2, 15), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10),
+        (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (10, 15), (11, 12),
+        (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13), (14, 15),
+        (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14), (13, 15),
+        (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14),
+        (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14),
+        (2, 4), (3, 6), (9, 12), (11, 13), (3, 5), (6, 8), (7, 9), (10, 12),
+        (3, 4), (5, 6), (7, 8), (9, 10), (11, 12), (6, 7), (8, 9),
+    ],
+];
+
+// 用排序网络排序不超过 NETWORK_MAX 个元素, 不稳定
+fn network_sort_by<T, F: Fn(&T, &T) -> Ordering>(array: &mut [T], compare: &F) {
+    for &(i, j) in NETWORKS[array.len()] {
+        if compare(&array[j], &array[i]) == Ordering::Less {
+            array.swap(i, j);
+        }
     }
-    #[test]
-    fn test_sort_2() {
-        let mut vec = vec![1];
-        sort(&mut vec);
-        assert_eq!(vec, vec![1]);
+}
+
+// 稳定的排序网络
+// 网络会跨越很远交换元素, 本身不稳定
+// 这里不直接移动元素, 而是对下标排列 perm 运行网络, 比较时以 (元素, 原始下标) 为键
+// 这样所有键都互不相同, 排序结果唯一, 相等元素自然保持原来的顺序
+// perm 只是 u8, 比较-交换可以写成无分支的选择, 最后再按 perm 一次性移动元素
+fn stable_network_sort_by<T, F: Fn(&T, &T) -> Ordering>(array: &mut [T], compare: &F) {
+    let len = array.len();
+    let mut perm = [0u8; NETWORK_MAX];
+    for (k, p) in perm[..len].iter_mut().enumerate() {
+        *p = k as u8;
     }
-    #[test]
-    fn test_sort_3() {
-        let mut vec = vec![99, 88, 77, 66, 55, 44, 33, 22, 11];
-        sort(&mut vec);
-        assert_eq!(vec, vec![11, 22, 33, 44, 55, 66, 77, 88, 99]);
+    for &(i, j) in NETWORKS[len] {
+        let (a, b) = (perm[i], perm[j]);
+        let less = compare(&array[b as usize], &array[a as usize]).then(b.cmp(&a));
+        let exchange = less == Ordering::Less;
+        perm[i] = if exchange { b } else { a };
+        perm[j] = if exchange { a } else { b };
+    }
+    // 位置 k 应该放原来的 array[perm[k]]
+    // 前面的位置已经换好了, 原来在 perm[k] 的元素可能已经被换走, 顺着 perm 找到它现在的位置
+    for k in 0..len {
+        let mut from = perm[k] as usize;
+        while from < k {
+            from = perm[from] as usize;
+        }
+        array.swap(k, from);
+    }
+}
+
+// 无分支的排序网络, 只适用于 Copy 的小元素 (比如整数和浮点数), 不稳定
+// 比较-交换写成 "按条件选择", 编译器会生成 cmov 之类的条件传送指令, 没有分支预测失败
+fn branchless_sort_by<T: Copy, F: Fn(&T, &T) -> bool>(array: &mut [T], is_less: F) {
+    for &(i, j) in NETWORKS[array.len()] {
+        let (a, b) = (array[i], array[j]);
+        let exchange = is_less(&b, &a);
+        array[i] = if exchange { b } else { a };
+        array[j] = if exchange { a } else { b };
+    }
+}
+
+// 非比较排序
+// 上面的算法都只依赖 Ord, 所以下界是 O(n log n) 次比较
+// 如果键本身就是定长整数或字节串, 我们可以直接按键的 "数位" 分配位置, 完全不做比较
+
+/// 可以用于基数排序的键
+/// 把键映射成一个无符号整数, 并保证映射后的大小顺序与原值一致
+trait RadixKey: Copy {
+    /// 键的有效字节数, 决定 LSD 基数排序需要几轮
+    const BYTES: usize;
+
+    fn radix_key(&self) -> u64;
+}
+
+impl RadixKey for u32 {
+    const BYTES: usize = 4;
+
+    fn radix_key(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl RadixKey for u64 {
+    const BYTES: usize = 8;
+
+    fn radix_key(&self) -> u64 {
+        *self
+    }
+}
+
+impl RadixKey for i64 {
+    const BYTES: usize = 8;
+
+    fn radix_key(&self) -> u64 {
+        // 补码中负数的最高位是 1, 直接当作无符号数会排在正数后面
+        // 翻转符号位之后, i64::MIN 变成 0, i64::MAX 变成 u64::MAX, 顺序就对了
+        (*self as u64) ^ (1 << 63)
+    }
+}
+
+// 计数表最多这么多个桶, 超过时改用 LSD 基数排序, 避免按键的跨度分配一张巨大的表
+const COUNTING_SORT_MAX_RANGE: u64 = 1 << 20;
+
+// 计数排序
+// 适用于键的取值范围 (max - min) 很小的情况, 需要 O(n + range) 的时间和额外空间
+// 范围超过 COUNTING_SORT_MAX_RANGE 时退化为 radix_sort_lsd, 结果同样稳定
+fn counting_sort<T: RadixKey>(array: &mut [T]) {
+    if array.len() <= 1 {
+        return;
+    }
+    // 先找出键的范围, 这样负数和很大的起始值都不需要特殊处理
+    let min = array.iter().map(|x| x.radix_key()).min().unwrap();
+    let max = array.iter().map(|x| x.radix_key()).max().unwrap();
+    // max - min 本身不会溢出, 但例如 [i64::MIN, i64::MAX] 的跨度是 u64::MAX, 再加一就溢出了
+    let span = max - min;
+    if span >= COUNTING_SORT_MAX_RANGE {
+        radix_sort_lsd(array);
+        return;
+    }
+    let range = span as usize + 1;
+    counting_pass(array, range, |x| (x.radix_key() - min) as usize);
+}
+
+// 稳定的一轮计数分配, 计数排序和 LSD 基数排序共用
+// bucket 返回元素所在的桶, 必须小于 buckets
+fn counting_pass<T: Copy, F: Fn(&T) -> usize>(array: &mut [T], buckets: usize, bucket: F) {
+    // 统计每个桶里有多少元素
+    let mut count = vec![0usize; buckets];
+    for x in array.iter() {
+        count[bucket(x)] += 1;
+    }
+    // 前缀和: count[b] 变成桶 b 在输出中的起始位置
+    let mut start = 0;
+    for c in count.iter_mut() {
+        let n = *c;
+        *c = start;
+        start += n;
+    }
+    // 按原顺序依次放入, 相同键的元素相对顺序不变, 所以是稳定的
+    let buffer = array.to_vec();
+    for x in buffer {
+        let b = bucket(&x);
+        array[count[b]] = x;
+        count[b] += 1;
+    }
+}
+
+// LSD 基数排序
+// 从最低字节到最高字节, 每轮做一次稳定的计数分配
+// 因为每轮都是稳定的, 所以高位相同的元素会保持低位已经排好的顺序
+fn radix_sort_lsd<T: RadixKey>(array: &mut [T]) {
+    if array.len() <= 1 {
+        return;
+    }
+    for byte in 0..T::BYTES {
+        let shift = byte * 8;
+        let digit = |x: &T| ((x.radix_key() >> shift) & 0xFF) as usize;
+        // 如果这一位上所有元素都相同, 这一轮分配不会改变任何东西, 直接跳过
+        let first = digit(&array[0]);
+        if array.iter().all(|x| digit(x) == first) {
+            continue;
+        }
+        counting_pass(array, 256, digit);
+    }
+}
+
+// MSD 基数排序 (字节串)
+// 从第一个字节开始按字节分桶, 再对每个桶递归处理下一个字节
+// 这里用 American flag sort 的方式原地交换, 所以不要求 T: Clone
+fn radix_sort_msd<T: AsRef<[u8]>>(array: &mut [T]) {
+    msd_sort(array, 0);
+}
+
+fn msd_sort<T: AsRef<[u8]>>(array: &mut [T], depth: usize) {
+    // 小桶里分配的开销比比较还大, 改用排序网络
+    // 所有元素的前 depth 个字节都相同, 只需要比较剩下的部分
+    if array.len() <= NETWORK_MAX {
+        network_sort_by(array, &|a: &T, b: &T| {
+            suffix(a, depth).cmp(suffix(b, depth))
+        });
+        return;
+    }
+
+    // 桶 0 留给在 depth 处已经结束的字节串, 它们比任何更长的串都小
+    // 字节 b 放在桶 b + 1
+    let bucket = |x: &T| x.as_ref().get(depth).map_or(0, |&b| b as usize + 1);
+
+    let mut count = [0usize; 257];
+    for x in array.iter() {
+        count[bucket(x)] += 1;
+    }
+    // 计算每个桶的起止位置
+    let mut start = [0usize; 257];
+    let mut end = [0usize; 257];
+    let mut sum = 0;
+    for b in 0..257 {
+        start[b] = sum;
+        sum += count[b];
+        end[b] = sum;
+    }
+
+    // 原地分配: next[b] 是桶 b 中下一个待确认的位置
+    // 不断把 next[b] 上的元素交换到它真正所属的桶, 直到换来的元素属于桶 b
+    let mut next = start;
+    for b in 0..257 {
+        while next[b] < end[b] {
+            let target = bucket(&array[next[b]]);
+            if target == b {
+                next[b] += 1;
+            } else {
+                array.swap(next[b], next[target]);
+                next[target] += 1;
+            }
+        }
+    }
+
+    // 桶 0 中的字节串已经完全相同, 不需要再排序
+    for b in 1..257 {
+        if end[b] - start[b] > 1 {
+            msd_sort(&mut array[start[b]..end[b]], depth + 1);
+        }
+    }
+}
+
+fn suffix<T: AsRef<[u8]>>(x: &T, depth: usize) -> &[u8] {
+    x.as_ref().get(depth..).unwrap_or(&[])
+}
+
+// 桶排序
+// 假设数据在 [min, max] 上大致均匀分布, 那么每个桶里的元素都很少, 期望时间为 O(n)
+// 浮点数不满足 Ord, 这里和 f64::total_cmp 的顺序保持一致, 包括 NaN
+fn bucket_sort(array: &mut [f64]) {
+    // NaN 不在任何区间里, 先按 total_cmp 的顺序把它们挪到两端:
+    // 符号位为负的 NaN 排在最前面, 其余的 NaN 排在最后面
+    let mut front = 0;
+    for i in 0..array.len() {
+        if array[i].is_nan() && array[i].is_sign_negative() {
+            array.swap(front, i);
+            front += 1;
+        }
+    }
+    let mut back = array.len();
+    for i in (front..array.len()).rev() {
+        if array[i].is_nan() {
+            back -= 1;
+            array.swap(back, i);
+        }
+    }
+    array[..front].sort_by(f64::total_cmp);
+    array[back..].sort_by(f64::total_cmp);
+
+    let array = &mut array[front..back];
+    let len = array.len();
+    if len <= 1 {
+        return;
+    }
+
+    let min = array.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = array.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let width = max - min;
+    if width == 0.0 || !width.is_finite() {
+        // 所有元素相等, 或者范围无法均分 (包含无穷大), 直接退化为普通排序
+        array.sort_by(f64::total_cmp);
+        return;
+    }
+
+    // 桶的个数与元素个数相同, 第 i 个桶负责 [min + i * width / n, min + (i + 1) * width / n)
+    let mut buckets: Vec<Vec<f64>> = vec![Vec::new(); len];
+    for &x in array.iter() {
+        // max 本身会算出 len, 把它放进最后一个桶
+        let index = (((x - min) / width) * len as f64) as usize;
+        buckets[index.min(len - 1)].push(x);
+    }
+
+    // 每个桶内排序, 然后按顺序拼接
+    // 桶通常很小, 用无分支的排序网络, 偶尔遇到大桶再用插入排序
+    let mut k = 0;
+    for mut bucket in buckets {
+        if bucket.len() <= NETWORK_MAX {
+            branchless_sort_by(&mut bucket, |a, b| a.total_cmp(b).is_lt());
+        } else {
+            for i in 1..bucket.len() {
+                let mut j = i;
+                while j > 0 && bucket[j].total_cmp(&bucket[j - 1]).is_lt() {
+                    bucket.swap(j, j - 1);
+                    j -= 1;
+                }
+            }
+        }
+        for x in bucket {
+            array[k] = x;
+            k += 1;
+        }
+    }
+}
+
+// 并行排序
+// 把数组切成 threads 块, 每块在自己的线程里用 TimSort 排序
+// 然后像 TimSort 合并 Run 一样, 每一轮并行地把相邻两块合并, 直到只剩一块
+// 切分位置只取决于长度和线程数, 而且排序和合并都是稳定的
+// 所以结果与顺序执行的稳定排序完全相同, 不受线程调度影响
+
+/// 获取默认的线程数, 取不到时退化为单线程
+fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn par_sort<T: Ord + Clone + Send>(array: &mut [T], threads: usize) {
+    par_sort_by(array, threads, T::cmp)
+}
+
+fn par_sort_by<T, F>(array: &mut [T], threads: usize, compare: F)
+where
+    T: Clone + Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    // 每块至少这么多元素, 再小的话创建线程的开销比排序本身还大
+    const MIN_CHUNK: usize = 4096;
+
+    let len = array.len();
+    let threads = threads.max(1).min(len.div_ceil(MIN_CHUNK));
+    if threads <= 1 {
+        tim_sort_by(array, &compare);
+        return;
+    }
+
+    // 闭包只借用一次, 各个线程共享这个引用
+    let compare = &compare;
+    let chunk = len.div_ceil(threads);
+
+    // 第一阶段: 各块独立排序
+    // chunks_mut 给出互不重叠的可变切片, 所以可以安全地交给不同线程
+    std::thread::scope(|s| {
+        for part in array.chunks_mut(chunk) {
+            s.spawn(move || tim_sort_by(part, compare));
+        }
+    });
+
+    // 第二阶段: 逐轮合并相邻的两块
+    let mut size = chunk;
+    while size < len {
+        std::thread::scope(|s| {
+            for pair in array.chunks_mut(2 * size) {
+                // 最后一块可能没有可以合并的对象
+                if pair.len() > size {
+                    s.spawn(move || {
+                        let right = pair.len();
+                        merge_by(pair, 0, size, right, compare)
+                    });
+                }
+            }
+        });
+        size *= 2;
+    }
+}
+
+// 选择算法
+// 很多时候我们只需要第 k 小的元素或者最小的 k 个元素, 没必要把整个数组排好
+
+// 快速选择
+// 和快速排序一样做划分, 但每次只进入 k 所在的那一边, 期望 O(n)
+// 如果划分总是很不均匀 (比如针对三数取中构造的输入), 超过 2 * log2(n) 轮之后
+// 改用中位数的中位数作为基准, 保证最坏情况也是 O(n), 这就是 introselect
+// 返回后 array[k] 就是排好序时应该在 k 位置的元素, 左边都不大于它, 右边都不小于它
+fn select_nth<T: Ord>(array: &mut [T], k: usize) -> &T {
+    assert!(k < array.len(), "k 超出数组范围");
+    let mut budget = 2 * array.len().ilog2();
+    let mut left = 0;
+    let mut right = array.len();
+    // 不变式: 第 k 小的元素在 array[left..right] 中
+    while right - left > 1 {
+        let part = &mut array[left..right];
+        if part.len() <= NETWORK_MAX {
+            // 范围足够小, 直接排好
+            network_sort_by(part, &T::cmp);
+            break;
+        }
+        let pivot = if budget > 0 {
+            budget -= 1;
+            median_of_three(part)
+        } else {
+            median_of_medians(part)
+        };
+        let (equal, pivot) = partition_at(part, pivot);
+        if k < left + equal {
+            right = left + equal;
+        } else if k > left + pivot {
+            left += pivot + 1;
+        } else {
+            // k 落在等于基准的区间里, 已经就位
+            break;
+        }
+    }
+    &array[k]
+}
+
+// 以 array[pivot] 为基准划分, 复用快速排序的 partition
+// 返回等于基准的区间 [equal, pivot], 这样大量重复元素时也能一次排除掉
+fn partition_at<T: Ord>(array: &mut [T], pivot: usize) -> (usize, usize) {
+    let last = array.len() - 1;
+    array.swap(pivot, last); // partition 总是以最后一个元素为基准
+    let pivot = partition(array);
+    // partition 之后左边都小于等于基准, 把其中等于基准的移到紧挨着基准的位置
+    let mut equal = pivot;
+    let mut i = 0;
+    while i < equal {
+        if array[i] == array[pivot] {
+            equal -= 1;
+            array.swap(i, equal); // 换过来的元素还没检查, 所以 i 不动
+        } else {
+            i += 1;
+        }
+    }
+    (equal, pivot)
+}
+
+// 三数取中: 返回首, 中, 尾三个元素中中间那个的下标
+fn median_of_three<T: Ord>(array: &[T]) -> usize {
+    let (a, b, c) = (0, array.len() / 2, array.len() - 1);
+    if (array[a] <= array[b]) == (array[b] <= array[c]) {
+        b
+    } else if (array[b] <= array[a]) == (array[a] <= array[c]) {
+        a
+    } else {
+        c
+    }
+}
+
+// 中位数的中位数 (BFPRT)
+// 每 5 个元素一组, 取出每组的中位数放到数组前面, 再递归地求这些中位数的中位数
+// 返回的基准至少大于等于约 30% 的元素, 也至少小于等于约 30% 的元素
+// 整个过程是确定性的, 不依赖输入的分布, 会重排数组, 返回基准所在的下标
+fn median_of_medians<T: Ord>(array: &mut [T]) -> usize {
+    let len = array.len();
+    if len <= 5 {
+        network_sort_by(array, &T::cmp);
+        return len / 2;
+    }
+    // 第 i 组的中位数交换到位置 i
+    let groups = len.div_ceil(5);
+    for i in 0..groups {
+        let start = i * 5;
+        let end = std::cmp::min(start + 5, len);
+        network_sort_by(&mut array[start..end], &T::cmp);
+        array.swap(i, start + (end - start) / 2);
+    }
+    // 在这些中位数里确定性地选出中位数
+    let mid = groups / 2;
+    median_select(&mut array[..groups], mid);
+    mid
+}
+
+// 只用中位数的中位数做基准的选择, 最坏情况 O(n)
+fn median_select<T: Ord>(array: &mut [T], k: usize) {
+    let mut left = 0;
+    let mut right = array.len();
+    while right - left > 1 {
+        let part = &mut array[left..right];
+        let pivot = median_of_medians(part);
+        let (equal, pivot) = partition_at(part, pivot);
+        if k < left + equal {
+            right = left + equal;
+        } else if k > left + pivot {
+            left += pivot + 1;
+        } else {
+            break;
+        }
+    }
+}
+
+// 部分排序
+// 让最小的 k 个元素按顺序排在数组前面, 其余元素的顺序不做保证
+// 在前 k 个元素上维护一个最大堆, 堆顶就是目前找到的第 k 小
+// 后面的元素只要比堆顶小, 就和堆顶交换并重新堆化, 最后对堆做一次堆排序
+// 总共 O(n log k)
+fn partial_sort<T: Ord>(array: &mut [T], k: usize) {
+    let k = std::cmp::min(k, array.len());
+    if k == 0 {
+        return;
+    }
+    // 和堆排序一样, 先在前 k 个元素上建立最大堆
+    for i in (0..k / 2).rev() {
+        heapify(array, i, k);
+    }
+    for i in k..array.len() {
+        if array[i] < array[0] {
+            array.swap(0, i);
+            heapify(array, 0, k);
+        }
+    }
+    // 堆排序的提取阶段, 只作用在前 k 个元素上
+    for i in (1..k).rev() {
+        array.swap(0, i);
+        heapify(array, 0, i);
+    }
+}
+
+// 统计模式
+// 基于比较的排序对数组只做三种操作: 比较两个元素, 交换两个位置, 把一个元素的副本写到某个位置
+// 上面的排序直接操作切片, 不带任何统计的开销
+// 这里为每个比较排序写了一份统计版本, 和原来的算法一步一步对应, 只是所有操作都经过 Probe
+// 由 Probe 计数, 并在每次交换或写入之后记录数组状态
+// 子数组用整个数组上的下标区间 [lo, hi) 表示, 这样每一步都知道改的是整个数组的哪个位置
+
+/// 一次排序的统计结果
+#[derive(Debug, PartialEq)]
+struct SortStats<T> {
+    comparisons: usize, // 比较次数
+    swaps: usize,       // 交换次数
+    moves: usize,       // 元素复制次数, 包括复制到辅助数组和写回
+    trace: Vec<Vec<T>>, // 开启追踪时, 初始状态以及每次交换或写入之后的数组状态
+}
+
+/// 统计版本的排序操作的数组
+struct Probe<'a, T> {
+    array: &'a mut [T],
+    stats: SortStats<T>,
+    trace: bool,
+}
+
+impl<'a, T: Ord + Clone> Probe<'a, T> {
+    fn new(array: &'a mut [T], trace: bool) -> Self {
+        let stats = SortStats {
+            comparisons: 0,
+            swaps: 0,
+            moves: 0,
+            trace: Vec::new(),
+        };
+        let mut probe = Probe {
+            array,
+            stats,
+            trace,
+        };
+        probe.snapshot();
+        probe
+    }
+
+    fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    /// 比较 array[i] 和 array[j]
+    fn cmp(&mut self, i: usize, j: usize) -> Ordering {
+        self.stats.comparisons += 1;
+        self.array[i].cmp(&self.array[j])
+    }
+
+    /// 比较两个不在数组里的元素, 比如合并时辅助数组中的元素
+    fn compare(&mut self, a: &T, b: &T) -> Ordering {
+        self.stats.comparisons += 1;
+        a.cmp(b)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.array.swap(i, j);
+        self.stats.swaps += 1;
+        self.snapshot();
+    }
+
+    /// 把 array[i] 复制到辅助数组
+    fn copy(&mut self, i: usize) -> T {
+        self.stats.moves += 1;
+        self.array[i].clone()
+    }
+
+    /// 把辅助数组中的元素写回 k 位置
+    fn set(&mut self, k: usize, value: &T) {
+        self.array[k] = value.clone();
+        self.stats.moves += 1;
+        self.snapshot();
+    }
+
+    fn snapshot(&mut self) {
+        if self.trace {
+            self.stats.trace.push(self.array.to_vec());
+        }
+    }
+}
+
+// 下面是各个比较排序的统计版本, 注释只说明和原来的对应关系
+
+// 对应 quick_sort
+fn probe_quick_sort<T: Ord + Clone>(probe: &mut Probe<T>) {
+    probe_quick_range(probe, 0, probe.len());
+}
+
+fn probe_quick_range<T: Ord + Clone>(probe: &mut Probe<T>, mut lo: usize, mut hi: usize) {
+    while hi - lo > NETWORK_MAX {
+        let median = probe_median_of_three(probe, lo, hi);
+        probe.swap(median, hi - 1);
+        let pivot = probe_partition(probe, lo, hi);
+        if pivot - lo < hi - pivot - 1 {
+            probe_quick_range(probe, lo, pivot);
+            lo = pivot + 1;
+        } else {
+            probe_quick_range(probe, pivot + 1, hi);
+            hi = pivot;
+        }
+    }
+    probe_network_sort(probe, lo, hi);
+}
+
+// 对应 partition, 以 array[hi - 1] 为基准
+fn probe_partition<T: Ord + Clone>(probe: &mut Probe<T>, lo: usize, hi: usize) -> usize {
+    let pivot = hi - 1;
+    let mut i = lo;
+    for j in lo..pivot {
+        if probe.cmp(j, pivot) != Ordering::Greater {
+            probe.swap(i, j);
+            i += 1;
+        }
+    }
+    probe.swap(i, pivot);
+    i
+}
+
+// 对应 median_of_three, 比较的顺序和次数都相同
+fn probe_median_of_three<T: Ord + Clone>(probe: &mut Probe<T>, lo: usize, hi: usize) -> usize {
+    let (a, b, c) = (lo, lo + (hi - lo) / 2, hi - 1);
+    let le = |probe: &mut Probe<T>, x, y| probe.cmp(x, y) != Ordering::Greater;
+    if le(probe, a, b) == le(probe, b, c) {
+        b
+    } else if le(probe, b, a) == le(probe, a, c) {
+        a
+    } else {
+        c
+    }
+}
+
+// 对应 network_sort_by
+fn probe_network_sort<T: Ord + Clone>(probe: &mut Probe<T>, lo: usize, hi: usize) {
+    for &(i, j) in NETWORKS[hi - lo] {
+        if probe.cmp(lo + j, lo + i) == Ordering::Less {
+            probe.swap(lo + i, lo + j);
+        }
+    }
+}
+
+// 对应 bubble_sort
+fn probe_bubble_sort<T: Ord + Clone>(probe: &mut Probe<T>) {
+    let len = probe.len();
+    for i in 0..len {
+        for j in 0..len - i - 1 {
+            if probe.cmp(j, j + 1) == Ordering::Greater {
+                probe.swap(j, j + 1);
+            }
+        }
+    }
+}
+
+// 对应 insertion_sort
+fn probe_insertion_sort<T: Ord + Clone>(probe: &mut Probe<T>) {
+    probe_insertion_range(probe, 0, probe.len());
+}
+
+fn probe_insertion_range<T: Ord + Clone>(probe: &mut Probe<T>, lo: usize, hi: usize) {
+    for i in lo + 1..hi {
+        let mut j = i;
+        while j > lo && probe.cmp(j, j - 1) == Ordering::Less {
+            probe.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+// 对应 heap_sort
+fn probe_heap_sort<T: Ord + Clone>(probe: &mut Probe<T>) {
+    let len = probe.len();
+    if len <= 1 {
+        return;
+    }
+    for i in (0..len / 2).rev() {
+        probe_heapify(probe, i, len);
+    }
+    for i in (1..len).rev() {
+        probe.swap(0, i);
+        probe_heapify(probe, 0, i);
+    }
+}
+
+// 对应 heapify
+fn probe_heapify<T: Ord + Clone>(probe: &mut Probe<T>, root: usize, end: usize) {
+    let mut largest = root;
+    let left = 2 * root + 1;
+    let right = 2 * root + 2;
+    if left < end && probe.cmp(left, largest) == Ordering::Greater {
+        largest = left;
+    }
+    if right < end && probe.cmp(right, largest) == Ordering::Greater {
+        largest = right;
+    }
+    if largest != root {
+        probe.swap(root, largest);
+        probe_heapify(probe, largest, end);
+    }
+}
+
+// 对应 tim_sort
+fn probe_tim_sort<T: Ord + Clone>(probe: &mut Probe<T>) {
+    let len = probe.len();
+    if len <= MIN_RUN {
+        probe_sort_run(probe, 0, len);
+        return;
+    }
+    for i in (0..len).step_by(MIN_RUN) {
+        probe_sort_run(probe, i, std::cmp::min(i + MIN_RUN, len));
+    }
+    let mut size = MIN_RUN;
+    while size < len {
+        for left in (0..len).step_by(2 * size) {
+            let mid = std::cmp::min(left + size, len);
+            let right = std::cmp::min(left + 2 * size, len);
+            if mid < right {
+                probe_merge(probe, left, mid, right);
+            }
+        }
+        size *= 2;
+    }
+}
+
+// 对应 sort_run
+fn probe_sort_run<T: Ord + Clone>(probe: &mut Probe<T>, lo: usize, hi: usize) {
+    if hi - lo <= NETWORK_MAX {
+        probe_stable_network_sort(probe, lo, hi);
+    } else {
+        probe_insertion_range(probe, lo, hi);
+    }
+}
+
+// 对应 stable_network_sort_by, 对下标排列运行网络时比较的仍然是数组里的元素
+fn probe_stable_network_sort<T: Ord + Clone>(probe: &mut Probe<T>, lo: usize, hi: usize) {
+    let len = hi - lo;
+    let mut perm = [0u8; NETWORK_MAX];
+    for (k, p) in perm[..len].iter_mut().enumerate() {
+        *p = k as u8;
+    }
+    for &(i, j) in NETWORKS[len] {
+        let (a, b) = (perm[i], perm[j]);
+        let less = probe.cmp(lo + b as usize, lo + a as usize).then(b.cmp(&a));
+        if less == Ordering::Less {
+            perm[i] = b;
+            perm[j] = a;
+        }
+    }
+    for k in 0..len {
+        let mut from = perm[k] as usize;
+        while from < k {
+            from = perm[from] as usize;
+        }
+        probe.swap(lo + k, lo + from);
+    }
+}
+
+// 对应 merge_by
+fn probe_merge<T: Ord + Clone>(probe: &mut Probe<T>, left: usize, mid: usize, right: usize) {
+    let left_part: Vec<T> = (left..mid).map(|i| probe.copy(i)).collect();
+    let right_part: Vec<T> = (mid..right).map(|i| probe.copy(i)).collect();
+    let mut i = 0;
+    let mut j = 0;
+    let mut k = left;
+    while i < left_part.len() && j < right_part.len() {
+        if probe.compare(&right_part[j], &left_part[i]) != Ordering::Less {
+            probe.set(k, &left_part[i]);
+            i += 1;
+        } else {
+            probe.set(k, &right_part[j]);
+            j += 1;
+        }
+        k += 1;
+    }
+    for value in left_part[i..].iter().chain(&right_part[j..]) {
+        probe.set(k, value);
+        k += 1;
+    }
+}
+
+type Sorter<T> = fn(&mut [T]);
+
+type ProbeSorter<T> = fn(&mut Probe<T>);
+
+/// 所有基于比较的排序以及各自的统计版本, 方便在同一个输入上逐一运行并对比
+fn comparison_sorts<T: Ord + Clone>() -> Vec<(&'static str, Sorter<T>, ProbeSorter<T>)> {
+    vec![
+        ("quick_sort", quick_sort, probe_quick_sort),
+        ("bubble_sort", bubble_sort, probe_bubble_sort),
+        ("insertion_sort", insertion_sort, probe_insertion_sort),
+        ("heap_sort", heap_sort, probe_heap_sort),
+        ("tim_sort", tim_sort, probe_tim_sort),
+    ]
+}
+
+/// 用 sorter 的统计版本对数组排序, 同时返回统计结果
+/// trace 为 true 时会记录每一步的数组状态, 大数组上会很占内存
+fn sort_with_stats<T: Ord + Clone>(
+    array: &mut [T],
+    sorter: ProbeSorter<T>,
+    trace: bool,
+) -> SortStats<T> {
+    let mut probe = Probe::new(array, trace);
+    sorter(&mut probe);
+    probe.stats
+}
+
+/// 在同一个输入上运行所有比较排序, 返回各自的统计结果 (不记录追踪)
+fn compare_sorts<T: Ord + Clone>(input: &[T]) -> Vec<(&'static str, SortStats<T>)> {
+    comparison_sorts::<T>()
+        .into_iter()
+        .map(|(name, _, probe_sorter)| {
+            let mut array = input.to_vec();
+            (name, sort_with_stats(&mut array, probe_sorter, false))
+        })
+        .collect()
+}
+
+// 基准测试
+// 不同的排序在不同的输入分布上表现差别很大, 比如快速排序在有序输入上退化到 O(n^2)
+// 这里为每种分布生成输入, 运行每个排序, 检查结果并记录耗时
+
+// 简单的线性同余生成器, 用来构造可复现的 "随机" 数据
+fn lcg(seed: &mut u64) -> u64 {
+    *seed = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    *seed >> 11
+}
+
+/// 输入分布
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pattern {
+    Random,    // 0..n 上均匀随机
+    Sorted,    // 已经升序
+    Reversed,  // 降序
+    OrganPipe, // 先升后降, 像管风琴的音管
+    FewUnique, // 只有 4 种不同的值, 大量重复
+    Sawtooth,  // 若干段升序拼接
+    AntiQsort, // McIlroy 的 antiqsort, 专门让 quick_sort 退化
+}
+
+impl Pattern {
+    const ALL: [Pattern; 7] = [
+        Pattern::Random,
+        Pattern::Sorted,
+        Pattern::Reversed,
+        Pattern::OrganPipe,
+        Pattern::FewUnique,
+        Pattern::Sawtooth,
+        Pattern::AntiQsort,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Pattern::Random => "random",
+            Pattern::Sorted => "sorted",
+            Pattern::Reversed => "reversed",
+            Pattern::OrganPipe => "organ_pipe",
+            Pattern::FewUnique => "few_unique",
+            Pattern::Sawtooth => "sawtooth",
+            Pattern::AntiQsort => "antiqsort",
+        }
+    }
+
+    /// 生成长度为 n 的输入, 所有值都在 0..n 之内
+    fn generate(self, n: usize, seed: u64) -> Vec<u64> {
+        let mut seed = seed;
+        let n64 = n as u64;
+        match self {
+            Pattern::Random => (0..n).map(|_| lcg(&mut seed) % n64).collect(),
+            Pattern::Sorted => (0..n64).collect(),
+            Pattern::Reversed => (0..n64).rev().collect(),
+            Pattern::OrganPipe => (0..n).map(|i| i.min(n - 1 - i) as u64).collect(),
+            Pattern::FewUnique => (0..n).map(|_| lcg(&mut seed) % 4).collect(),
+            Pattern::Sawtooth => {
+                let period = (n / 8).max(1);
+                (0..n).map(|i| (i % period) as u64).collect()
+            }
+            Pattern::AntiQsort => antiqsort(n, quick_sort),
+        }
+    }
+}
+
+// McIlroy 的 antiqsort ("A Killer Adversary for Quicksort")
+// 一开始所有元素都是 "气体" (gas), 值未定且比任何已确定的值都大
+// 排序比较两个气体时, 把其中一个 "冻结" 成下一个最小的确定值
+// candidate 是最近一次和确定值比较过的气体, 它很可能就是基准
+// 论文中两个气体比较时, 如果其中一个是 candidate 就冻结它, 否则冻结另一个
+// 之后仍是气体的一方成为新的 candidate, 于是基准很快被冻结成很小的值, 每次划分都极不均匀
+// 排序结束后, 按冻结出的值还原出原始输入, 同一个排序再处理这个输入就会走完全相同的比较路径
+fn antiqsort(n: usize, sorter: Sorter<Gas>) -> Vec<u64> {
+    let adversary = Rc::new(RefCell::new(Adversary {
+        values: vec![None; n],
+        solid: 0,
+        candidate: 0,
+    }));
+    let mut items: Vec<Gas> = (0..n)
+        .map(|id| Gas {
+            id,
+            adversary: Rc::clone(&adversary),
+        })
+        .collect();
+    sorter(&mut items);
+
+    // 从未与其他气体比较过的元素, 给它们比所有确定值都大的值即可
+    let adversary = adversary.borrow_mut();
+    let mut next = adversary.solid;
+    adversary
+        .values
+        .iter()
+        .map(|v| {
+            v.unwrap_or_else(|| {
+                next += 1;
+                next - 1
+            }) as u64
+        })
+        .collect()
+}
+
+struct Adversary {
+    values: Vec<Option<usize>>, // None 表示还是气体
+    solid: usize,               // 下一个要分配的确定值
+    candidate: usize,           // 最近一次和确定值比较的气体, 很可能是基准
+}
+
+impl Adversary {
+    fn freeze(&mut self, id: usize) {
+        self.values[id] = Some(self.solid);
+        self.solid += 1;
+    }
+}
+
+// antiqsort 中的元素, 比较时由 Adversary 决定结果
+struct Gas {
+    id: usize,
+    adversary: Rc<RefCell<Adversary>>,
+}
+
+impl Ord for Gas {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut a = self.adversary.borrow_mut();
+        let (x, y) = (self.id, other.id);
+        if a.values[x].is_none() && a.values[y].is_none() {
+            // 两个都是气体, 和论文一样: x 是 candidate 时冻结 x, 否则冻结 y
+            if x == a.candidate {
+                a.freeze(x);
+            } else {
+                a.freeze(y);
+            }
+        }
+        if a.values[x].is_none() {
+            a.candidate = x;
+        } else if a.values[y].is_none() {
+            a.candidate = y;
+        }
+        // 气体比任何确定值都大, None 在 Option 的顺序里最小, 所以这里要反过来比较
+        match (a.values[x], a.values[y]) {
+            (Some(vx), Some(vy)) => vx.cmp(&vy),
+            (None, _) => Ordering::Greater,
+            (_, None) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Gas {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Gas {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Gas {}
+
+/// 基准测试里排序的元素: 只按 key 比较, index 是它在输入中的位置, 用来检查稳定性
+#[derive(Debug, Clone, Copy)]
+struct Keyed {
+    key: u64,
+    index: usize,
+}
+
+impl Ord for Keyed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl PartialOrd for Keyed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Keyed {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Keyed {}
+
+impl RadixKey for Keyed {
+    const BYTES: usize = 8;
+
+    fn radix_key(&self) -> u64 {
+        self.key
+    }
+}
+
+/// 参与基准测试的排序, stable 表示它承诺是稳定的
+struct Algorithm {
+    name: &'static str,
+    sort: Sorter<Keyed>,
+    stable: bool,
+}
+
+fn algorithms() -> Vec<Algorithm> {
+    let mut list: Vec<Algorithm> = comparison_sorts::<Keyed>()
+        .into_iter()
+        .map(|(name, sort, _)| Algorithm {
+            name,
+            sort,
+            stable: matches!(name, "bubble_sort" | "insertion_sort" | "tim_sort"),
+        })
+        .collect();
+    list.extend([
+        Algorithm {
+            name: "par_sort",
+            sort: |array| par_sort(array, 4),
+            stable: true,
+        },
+        Algorithm {
+            name: "partial_sort",
+            sort: |array| partial_sort(array, array.len()),
+            stable: false,
+        },
+        Algorithm {
+            name: "counting_sort",
+            sort: counting_sort,
+            stable: true,
+        },
+        Algorithm {
+            name: "radix_sort_lsd",
+            sort: radix_sort_lsd,
+            stable: true,
+        },
+        Algorithm {
+            name: "radix_sort_msd",
+            sort: msd_sort_keyed,
+            stable: false,
+        },
+        Algorithm {
+            name: "bucket_sort",
+            sort: bucket_sort_keyed,
+            stable: false,
+        },
+        Algorithm {
+            name: "select_sort",
+            sort: select_sort,
+            stable: false,
+        },
+    ]);
+    list
+}
+
+// MSD 基数排序按字节串排序, 这里把 key 转成大端序字节, 字节序和数值大小一致
+fn msd_sort_keyed(array: &mut [Keyed]) {
+    struct KeyBytes([u8; 8], Keyed);
+
+    impl AsRef<[u8]> for KeyBytes {
+        fn as_ref(&self) -> &[u8] {
+            &self.0
+        }
+    }
+
+    let mut bytes: Vec<KeyBytes> = array
+        .iter()
+        .map(|&item| KeyBytes(item.key.to_be_bytes(), item))
+        .collect();
+    radix_sort_msd(&mut bytes);
+    for (slot, KeyBytes(_, item)) in array.iter_mut().zip(bytes) {
+        *slot = item;
+    }
+}
+
+// 桶排序只能排 f64, 把 (key, index) 编码成 key * n + index 再还原
+// 基准测试的 key 都小于 n, 编码后不超过 n^2, 在 f64 能精确表示的范围内
+fn bucket_sort_keyed(array: &mut [Keyed]) {
+    let n = array.len() as u64;
+    let mut values: Vec<f64> = array
+        .iter()
+        .map(|item| (item.key * n + item.index as u64) as f64)
+        .collect();
+    bucket_sort(&mut values);
+    for (slot, value) in array.iter_mut().zip(values) {
+        let value = value as u64;
+        *slot = Keyed {
+            key: value / n,
+            index: (value % n) as usize,
+        };
+    }
+}
+
+// 用选择算法排序: 每次用 select_nth 把中位数放到中间, 再分别处理两半
+// 基准总是真正的中位数, 递归深度只有 log2(n)
+fn select_sort<T: Ord>(array: &mut [T]) {
+    if array.len() <= NETWORK_MAX {
+        network_sort_by(array, &T::cmp);
+        return;
+    }
+    let mid = array.len() / 2;
+    select_nth(array, mid);
+    let (left, right) = array.split_at_mut(mid);
+    select_sort(left);
+    select_sort(&mut right[1..]);
+}
+
+/// 检查排序结果: 有序, 是输入的一个排列, 如果承诺稳定还要检查相同 key 的相对顺序
+fn check_sorted(input: &[Keyed], output: &[Keyed], stable: bool) -> Result<(), String> {
+    if let Some(i) = (1..output.len()).find(|&i| output[i - 1].key > output[i].key) {
+        return Err(format!("位置 {} 处没有排好序", i));
+    }
+    // 输入中每个 index 都恰好出现一次, 并且对应的 key 不变
+    let mut seen = vec![false; input.len()];
+    if output.len() != input.len() {
+        return Err("长度改变了".to_string());
+    }
+    for item in output {
+        if seen[item.index] || input[item.index].key != item.key {
+            return Err(format!("元素 {:?} 重复或被修改", item));
+        }
+        seen[item.index] = true;
+    }
+    if stable
+        && let Some(w) = output
+            .windows(2)
+            .find(|w| w[0].key == w[1].key && w[0].index > w[1].index)
+    {
+        return Err(format!("{:?} 和 {:?} 的相对顺序改变了", w[0], w[1]));
+    }
+    Ok(())
+}
+
+/// 一次计时结果
+#[derive(Debug)]
+struct BenchResult {
+    algorithm: &'static str,
+    pattern: Pattern,
+    n: usize,
+    elapsed: Duration,
+}
+
+/// 对每种分布的长度为 n 的输入运行每个排序, 任何一个结果不正确就返回错误
+fn run_benchmark(n: usize, seed: u64) -> Result<Vec<BenchResult>, String> {
+    let mut results = Vec::new();
+    for pattern in Pattern::ALL {
+        let input: Vec<Keyed> = pattern
+            .generate(n, seed)
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| Keyed { key, index })
+            .collect();
+        for algorithm in algorithms() {
+            let mut array = input.clone();
+            let start = Instant::now();
+            (algorithm.sort)(&mut array);
+            let elapsed = start.elapsed();
+            check_sorted(&input, &array, algorithm.stable)
+                .map_err(|e| format!("{} 在 {} 输入上: {}", algorithm.name, pattern.name(), e))?;
+            results.push(BenchResult {
+                algorithm: algorithm.name,
+                pattern,
+                n,
+                elapsed,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// 以表格形式输出, 每行一个排序, 每列一种分布, 单位毫秒
+fn results_table(results: &[BenchResult]) -> String {
+    let mut table = format!("{:<16}", "algorithm");
+    for pattern in Pattern::ALL {
+        table += &format!("{:>12}", pattern.name());
+    }
+    table.push('\n');
+    for algorithm in algorithms() {
+        table += &format!("{:<16}", algorithm.name);
+        for pattern in Pattern::ALL {
+            let cell = results
+                .iter()
+                .find(|r| r.algorithm == algorithm.name && r.pattern == pattern)
+                .map_or("-".to_string(), |r| {
+                    format!("{:.3}", r.elapsed.as_secs_f64() * 1000.0)
+                });
+            table += &format!("{:>12}", cell);
+        }
+        table.push('\n');
+    }
+    table
+}
+
+/// 以 CSV 形式输出, 方便导入其他工具
+fn results_csv(results: &[BenchResult]) -> String {
+    let mut csv = "algorithm,pattern,n,nanos\n".to_string();
+    for r in results {
+        csv += &format!(
+            "{},{},{},{}\n",
+            r.algorithm,
+            r.pattern.name(),
+            r.n,
+            r.elapsed.as_nanos()
+        );
+    }
+    csv
+}
+
+// 外部排序
+// 数据比内存大时, 先按内存预算把输入切成若干块, 每块在内存中排好后写到临时文件 (一个 Run)
+// 块内直接用上面的 sort
+// 然后像 merge 合并两个有序数组那样合并所有 Run, 规则也相同: 每次取出最小的记录, 相等时先取前面的 Run
+// 但 merge_by 要求两段数据都在同一个切片里, 而 Run 在文件中, 只能一条一条地读, 并且一次要合并 k 个
+// 所以这里不复用 merge_by, 每个 Run 只读出当前最小的那条记录, 用一个小顶堆每次 O(log k) 找到全局最小
+
+/// 记录的编解码方式, 决定如何从字节流中切分出一条条记录, 以及如何写回
+/// 记录按字节序比较
+trait RecordCodec {
+    /// 读取下一条记录, 输入结束时返回 None
+    fn read_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>>;
+
+    fn write_record(&self, writer: &mut dyn Write, record: &[u8]) -> io::Result<()>;
+}
+
+/// 以换行符分隔的文本行, 记录本身不包含换行符
+struct LineCodec;
+
+impl RecordCodec for LineCodec {
+    fn read_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        // 最后一行可能没有换行符
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    fn write_record(&self, writer: &mut dyn Write, record: &[u8]) -> io::Result<()> {
+        writer.write_all(record)?;
+        writer.write_all(b"\n")
+    }
+}
+
+/// 定长记录, 每条恰好 width 字节
+struct FixedWidthCodec {
+    width: usize,
+}
+
+impl RecordCodec for FixedWidthCodec {
+    fn read_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+        // 宽度为 0 时 read_full 每次都能 "读满" 一条空记录, read_chunk 永远不会结束
+        if self.width == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "记录宽度不能为 0",
+            ));
+        }
+        let mut record = vec![0; self.width];
+        Ok(read_full(reader, &mut record)?.then_some(record))
+    }
+
+    fn write_record(&self, writer: &mut dyn Write, record: &[u8]) -> io::Result<()> {
+        writer.write_all(record)
+    }
+}
+
+/// 长度前缀记录: 4 字节小端序长度, 后面跟着记录内容
+struct LengthPrefixedCodec;
+
+impl RecordCodec for LengthPrefixedCodec {
+    fn read_record(&self, reader: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+        let mut len = [0; 4];
+        if !read_full(reader, &mut len)? {
+            return Ok(None);
+        }
+        // 长度来自输入, 不可信, 不能照着它一次分配好缓冲区
+        // 用 take 边读边扩容, 损坏的长度最多只会让我们读完剩下的数据, 而不是先分配 4GiB
+        let len = u32::from_le_bytes(len) as u64;
+        let mut record = Vec::new();
+        reader.take(len).read_to_end(&mut record)?;
+        if (record.len() as u64) < len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("长度前缀为 {len}, 但只剩下 {} 字节", record.len()),
+            ));
+        }
+        Ok(Some(record))
+    }
+
+    fn write_record(&self, writer: &mut dyn Write, record: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(record.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "记录超过 4GiB"))?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(record)
+    }
+}
+
+// 填满 buf, 一个字节都没读到说明输入正常结束, 返回 false
+// 读到一半就结束说明最后一条记录不完整, 返回错误
+fn read_full(reader: &mut dyn BufRead, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    match filled {
+        0 if !buf.is_empty() => Ok(false),
+        n if n == buf.len() => Ok(true),
+        _ => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+// 临时文件, 离开作用域时自动删除, 出错提前返回时也不会留下垃圾
+struct RunFile {
+    path: PathBuf,
+}
+
+impl Drop for RunFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// 合并时每个 Run 的读缓冲区希望至少有这么大, 预算不够时减少同时合并的 Run 个数
+const MIN_READ_BUFFER: usize = 4096;
+
+/// 外部排序器
+struct ExternalSorter<C: RecordCodec> {
+    codec: C,
+    memory_budget: usize, // 每块数据在内存中最多占用的字节数, 合并时所有读缓冲区加起来也不超过它
+    temp_dir: PathBuf,    // 临时文件所在的目录
+    max_fan_in: usize,    // 一次最多同时打开并合并几个 Run
+}
+
+impl<C: RecordCodec> ExternalSorter<C> {
+    fn new(codec: C, memory_budget: usize) -> Self {
+        ExternalSorter {
+            codec,
+            memory_budget,
+            temp_dir: std::env::temp_dir(),
+            max_fan_in: 64,
+        }
+    }
+
+    fn temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = dir.into();
+        self
+    }
+
+    /// Run 太多时分多轮合并, 每轮最多合并 fan_in 个, 避免同时打开的文件超过系统限制
+    fn max_fan_in(mut self, fan_in: usize) -> Self {
+        assert!(fan_in >= 2, "至少要两路合并");
+        self.max_fan_in = fan_in;
+        self
+    }
+
+    /// 从 input 读取所有记录, 排序后写入 output, 返回写到临时文件的 Run 个数
+    /// 数据能一次放进内存时不会创建临时文件, 返回 0
+    fn sort<R: Read, W: Write>(&self, input: R, output: W) -> io::Result<usize> {
+        let mut reader = BufReader::new(input);
+        let mut output = BufWriter::new(output);
+        let mut runs = Vec::new();
+
+        loop {
+            let (mut chunk, done) = self.read_chunk(&mut reader)?;
+            sort(&mut chunk);
+            if done && runs.is_empty() {
+                // 全部数据都在这一块里, 直接输出
+                for record in &chunk {
+                    self.codec.write_record(&mut output, record)?;
+                }
+                output.flush()?;
+                return Ok(0);
+            }
+            if !chunk.is_empty() {
+                runs.push(self.spill(&chunk)?);
+            }
+            if done {
+                break;
+            }
+        }
+
+        let count = runs.len();
+        self.merge_runs(runs, &mut output)?;
+        output.flush()?;
+        Ok(count)
+    }
+
+    // 读取记录直到超出内存预算, 第二个返回值表示输入是否已经读完
+    fn read_chunk(&self, reader: &mut dyn BufRead) -> io::Result<(Vec<Vec<u8>>, bool)> {
+        let mut chunk = Vec::new();
+        let mut used = 0;
+        // 每块至少一条记录, 否则预算比单条记录还小时会死循环
+        while chunk.is_empty() || used < self.memory_budget {
+            match self.codec.read_record(reader)? {
+                Some(record) => {
+                    // Vec 本身的三个字段也要算进去
+                    used += record.len() + std::mem::size_of::<Vec<u8>>();
+                    chunk.push(record);
+                }
+                None => return Ok((chunk, true)),
+            }
+        }
+        Ok((chunk, false))
+    }
+
+    // 把排好序的一块写到新的临时文件
+    fn spill(&self, chunk: &[Vec<u8>]) -> io::Result<RunFile> {
+        let (run, mut writer) = self.create_run()?;
+        for record in chunk {
+            self.codec.write_record(&mut writer, record)?;
+        }
+        writer.flush()?;
+        Ok(run)
+    }
+
+    // 新建一个空的临时文件
+    fn create_run(&self) -> io::Result<(RunFile, BufWriter<File>)> {
+        // 进程号加上全局计数器, 同一进程里同时运行的多个排序也不会冲突
+        static NEXT_RUN: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let id = NEXT_RUN.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let run = RunFile {
+            path: self
+                .temp_dir
+                .join(format!("external-sort-{}-{}.run", std::process::id(), id)),
+        };
+        let writer = BufWriter::new(File::create(&run.path)?);
+        Ok((run, writer))
+    }
+
+    // 每轮同时合并的 Run 个数: 不超过 max_fan_in, 并且让每个读缓冲区尽量不小于 MIN_READ_BUFFER
+    fn fan_in(&self) -> usize {
+        (self.memory_budget / MIN_READ_BUFFER).clamp(2, self.max_fan_in)
+    }
+
+    // 多轮归并
+    // Run 的个数超过 fan_in 时, 每次取出最前面的 fan_in 个合并成一个新的 Run 放到最后,
+    // 直到剩下的不超过 fan_in 个, 最后一轮直接写到输出
+    // 参与合并的 Run 在这一轮结束时被丢弃, 临时文件随之删除, 同时存在的文件也不会越来越多
+    fn merge_runs(&self, runs: Vec<RunFile>, output: &mut dyn Write) -> io::Result<()> {
+        let fan_in = self.fan_in();
+        let mut runs = VecDeque::from(runs);
+        while runs.len() > fan_in {
+            let group: Vec<RunFile> = runs.drain(..fan_in).collect();
+            let (merged, mut writer) = self.create_run()?;
+            self.merge_group(&group, fan_in, &mut writer)?;
+            writer.flush()?;
+            runs.push_back(merged);
+        }
+        self.merge_group(runs.make_contiguous(), fan_in, output)
+    }
+
+    // k 路归并, k 不超过 fan_in
+    fn merge_group(
+        &self,
+        runs: &[RunFile],
+        fan_in: usize,
+        output: &mut dyn Write,
+    ) -> io::Result<()> {
+        // 内存预算平分给 fan_in 个读缓冲区
+        // 预算很小时缓冲区也跟着变小, 但至少要有一个字节, 容量为 0 的 BufReader 会被当作读到了结尾
+        let capacity = (self.memory_budget / fan_in).max(1);
+        let mut readers = Vec::with_capacity(runs.len());
+        for run in runs {
+            readers.push(BufReader::with_capacity(capacity, File::open(&run.path)?));
+        }
+
+        // 堆里放 (记录, Run 编号), BinaryHeap 是大顶堆, 用 Reverse 变成小顶堆
+        // 记录相同时编号小的先出来, 和 merge 里相等时先取左边一样
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(record) = self.codec.read_record(reader)? {
+                heap.push(Reverse((record, i)));
+            }
+        }
+        while let Some(Reverse((record, i))) = heap.pop() {
+            self.codec.write_record(output, &record)?;
+            // 从同一个 Run 补充下一条记录
+            if let Some(next) = self.codec.read_record(&mut readers[i])? {
+                heap.push(Reverse((next, i)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_1() {
+        let mut vec = vec![37, 73, 57, 75, 91, 19, 46, 64];
+        sort(&mut vec);
+        assert_eq!(vec, vec![19, 37, 46, 57, 64, 73, 75, 91]);
+    }
+    #[test]
+    fn test_sort_2() {
+        let mut vec = vec![1];
+        sort(&mut vec);
+        assert_eq!(vec, vec![1]);
+    }
+    #[test]
+    fn test_sort_3() {
+        let mut vec = vec![99, 88, 77, 66, 55, 44, 33, 22, 11];
+        sort(&mut vec);
+        assert_eq!(vec, vec![11, 22, 33, 44, 55, 66, 77, 88, 99]);
+    }
+
+    #[test]
+    fn test_sort_large_sorted() {
+        // 有序和逆序的大数组, 在默认大小 (2MB) 的线程栈上也不能栈溢出
+        let n = 1_000_000;
+        let handle = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(move || {
+                let mut sorted: Vec<u32> = (0..n).collect();
+                sort(&mut sorted);
+                assert!(sorted.iter().copied().eq(0..n));
+                let mut reversed: Vec<u32> = (0..n).rev().collect();
+                sort(&mut reversed);
+                assert!(reversed.iter().copied().eq(0..n));
+            });
+        handle.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_counting_sort() {
+        let mut vec: Vec<i64> = vec![3, -1, 4, -1, 5, 9, -2, 6, 5, 3, 5];
+        counting_sort(&mut vec);
+        assert_eq!(vec, vec![-2, -1, -1, 3, 3, 4, 5, 5, 5, 6, 9]);
+
+        let mut vec: Vec<u32> = vec![1_000_007, 1_000_003, 1_000_005, 1_000_003];
+        counting_sort(&mut vec);
+        assert_eq!(vec, vec![1_000_003, 1_000_003, 1_000_005, 1_000_007]);
+
+        // 跨度太大时改用基数排序, 不会溢出也不会分配巨大的计数表
+        let mut vec: Vec<i64> = vec![i64::MAX, 0, i64::MIN, -1, i64::MAX, 1];
+        counting_sort(&mut vec);
+        assert_eq!(vec, vec![i64::MIN, -1, 0, 1, i64::MAX, i64::MAX]);
+
+        let mut vec: Vec<u64> = vec![u64::MAX, 3, 0, 1 << 40];
+        counting_sort(&mut vec);
+        assert_eq!(vec, vec![0, 3, 1 << 40, u64::MAX]);
+    }
+
+    #[test]
+    fn test_radix_sort_lsd() {
+        let mut seed = 42;
+        let mut unsigned: Vec<u64> = (0..1000).map(|_| lcg(&mut seed)).collect();
+        let mut signed: Vec<i64> = (0..1000)
+            .map(|_| lcg(&mut seed) as i64 - (1 << 52))
+            .collect();
+        signed.extend([i64::MIN, i64::MAX, 0, -1]);
+        let mut small: Vec<u32> = (0..1000).map(|_| lcg(&mut seed) as u32).collect();
+
+        let mut expected_unsigned = unsigned.clone();
+        let mut expected_signed = signed.clone();
+        let mut expected_small = small.clone();
+        expected_unsigned.sort();
+        expected_signed.sort();
+        expected_small.sort();
+
+        radix_sort_lsd(&mut unsigned);
+        radix_sort_lsd(&mut signed);
+        radix_sort_lsd(&mut small);
+        assert_eq!(unsigned, expected_unsigned);
+        assert_eq!(signed, expected_signed);
+        assert_eq!(small, expected_small);
+    }
+
+    #[test]
+    fn test_radix_sort_msd() {
+        let mut vec = vec![
+            "she",
+            "sells",
+            "seashells",
+            "by",
+            "the",
+            "sea",
+            "shore",
+            "",
+            "s",
+            "sea",
+        ];
+        radix_sort_msd(&mut vec);
+        assert_eq!(
+            vec,
+            vec![
+                "",
+                "by",
+                "s",
+                "sea",
+                "sea",
+                "seashells",
+                "sells",
+                "she",
+                "shore",
+                "the"
+            ]
+        );
+
+        // 足够多的元素才会走到按字节分桶的路径
+        let mut seed = 7;
+        let mut keys: Vec<Vec<u8>> = (0..500)
+            .map(|_| {
+                let len = (lcg(&mut seed) % 6) as usize;
+                (0..len)
+                    .map(|_| b'a' + (lcg(&mut seed) % 3) as u8)
+                    .collect()
+            })
+            .collect();
+        let mut expected = keys.clone();
+        expected.sort();
+        radix_sort_msd(&mut keys);
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_bucket_sort() {
+        let mut vec = vec![0.42, 0.32, 0.23, 0.52, 0.25, 0.47, 0.51, -0.5, 1.0];
+        bucket_sort(&mut vec);
+        assert_eq!(
+            vec,
+            vec![-0.5, 0.23, 0.25, 0.32, 0.42, 0.47, 0.51, 0.52, 1.0]
+        );
+
+        let mut same = vec![2.5; 4];
+        bucket_sort(&mut same);
+        assert_eq!(same, vec![2.5; 4]);
+
+        // NaN 和 total_cmp 排在同样的位置
+        let mut nan = vec![
+            1.0,
+            f64::NAN,
+            -0.5,
+            -f64::NAN,
+            f64::INFINITY,
+            0.25,
+            f64::NAN,
+        ];
+        let mut expected = nan.clone();
+        expected.sort_by(f64::total_cmp);
+        bucket_sort(&mut nan);
+        let bits = |v: &[f64]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&nan), bits(&expected));
+    }
+
+    #[test]
+    fn test_par_sort() {
+        let mut seed = 1;
+        let mut vec: Vec<u64> = (0..100_000).map(|_| lcg(&mut seed) % 1000).collect();
+        let mut expected = vec.clone();
+        sort(&mut expected);
+
+        // 线程数不能整除长度, 最后一块更短
+        par_sort(&mut vec, 7);
+        assert_eq!(vec, expected);
+
+        // 单线程和元素太少时退化为顺序排序
+        let mut small = vec![3, 1, 2];
+        par_sort(&mut small, default_threads());
+        assert_eq!(small, vec![1, 2, 3]);
+        let mut empty: Vec<i32> = vec![];
+        par_sort(&mut empty, 0);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_par_sort_by_is_stable() {
+        let mut seed = 2;
+        // (键, 原始位置), 只按键比较, 然后检查相同键的原始位置仍然递增
+        let pairs: Vec<(u64, usize)> = (0..50_000).map(|i| (lcg(&mut seed) % 100, i)).collect();
+
+        let mut sequential = pairs.clone();
+        tim_sort_by(&mut sequential, &|a: &(u64, usize), b: &(u64, usize)| {
+            a.0.cmp(&b.0)
+        });
+        for threads in [2, 3, 8] {
+            let mut parallel = pairs.clone();
+            par_sort_by(&mut parallel, threads, |a, b| a.0.cmp(&b.0));
+            assert_eq!(parallel, sequential);
+        }
+        assert!(
+            sequential
+                .windows(2)
+                .all(|w| w[0].0 < w[1].0 || w[0].1 < w[1].1)
+        );
+    }
+
+    #[test]
+    fn test_select_nth() {
+        let mut seed = 3;
+        let random: Vec<u64> = (0..2000).map(|_| lcg(&mut seed) % 50).collect();
+        let mut sorted = random.clone();
+        sort(&mut sorted);
+        for k in [0, 1, 999, 1000, 1998, 1999] {
+            let mut vec = random.clone();
+            assert_eq!(*select_nth(&mut vec, k), sorted[k]);
+            assert!(vec[..k].iter().all(|x| *x <= vec[k]));
+            assert!(vec[k + 1..].iter().all(|x| *x >= vec[k]));
+        }
+
+        // 全部相同和有序的输入都不应该退化
+        let mut same = vec![7; 100_000];
+        assert_eq!(*select_nth(&mut same, 12_345), 7);
+        let mut ascending: Vec<i32> = (0..100_000).collect();
+        assert_eq!(*select_nth(&mut ascending, 99_999), 99_999);
+    }
+
+    #[test]
+    fn test_median_of_medians() {
+        let mut seed = 4;
+        let mut vec: Vec<u64> = (0..1000).map(|_| lcg(&mut seed)).collect();
+        let pivot = median_of_medians(&mut vec);
+        let value = vec[pivot];
+        let less = vec.iter().filter(|x| **x < value).count();
+        let greater = vec.iter().filter(|x| **x > value).count();
+        // 保证两边都至少有约 30% 的元素
+        assert!(less >= 250 && greater >= 250);
+
+        let mut small = vec![5, 1, 4];
+        let pivot = median_of_medians(&mut small);
+        assert_eq!(small[pivot], 4);
+    }
+
+    #[test]
+    fn test_partial_sort() {
+        let mut vec = vec![37, 73, 57, 75, 91, 19, 46, 64];
+        partial_sort(&mut vec, 3);
+        assert_eq!(vec[..3], [19, 37, 46]);
+
+        let mut seed = 5;
+        let mut vec: Vec<u64> = (0..1000).map(|_| lcg(&mut seed) % 100).collect();
+        let mut sorted = vec.clone();
+        sort(&mut sorted);
+        partial_sort(&mut vec, 100);
+        assert_eq!(vec[..100], sorted[..100]);
+
+        // k 超过长度时等价于完整排序
+        let mut all = vec![3, 2, 1];
+        partial_sort(&mut all, 10);
+        assert_eq!(all, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sort_with_stats() {
+        let mut vec = vec![3, 2, 1];
+        let stats = sort_with_stats(&mut vec, probe_insertion_sort, true);
+        assert_eq!(vec, vec![1, 2, 3]);
+        assert_eq!(stats.comparisons, 3);
+        assert_eq!(stats.swaps, 3);
+        assert_eq!(stats.moves, 0);
+        assert_eq!(
+            stats.trace,
+            vec![vec![3, 2, 1], vec![2, 3, 1], vec![2, 1, 3], vec![1, 2, 3]]
+        );
+
+        // 有序输入上冒泡排序只比较不交换
+        let mut sorted: Vec<i32> = (0..10).collect();
+        let stats = sort_with_stats(&mut sorted, probe_bubble_sort, false);
+        assert_eq!(stats.comparisons, 45);
+        assert_eq!(stats.swaps, 0);
+        assert!(stats.trace.is_empty());
+    }
+
+    #[test]
+    fn test_sort_with_stats_merge_trace() {
+        // 超过 MIN_RUN 才会合并, 检查合并时的写入也能正确还原
+        let mut vec: Vec<i32> = (0..40).rev().collect();
+        let stats = sort_with_stats(&mut vec, probe_tim_sort, true);
+        assert_eq!(vec, (0..40).collect::<Vec<_>>());
+        assert!(stats.moves > 0);
+        assert_eq!(stats.trace[0], (0..40).rev().collect::<Vec<_>>());
+        assert_eq!(stats.trace.last().unwrap(), &vec);
+        // 合并前两个 Run 已经各自有序, 合并写回的第一个元素是第二个 Run 的最小值 0
+        // 第二个 Run 只有 8 个元素, 是用排序网络排好的
+        let merged = stats.trace.iter().position(|s| s[0] == 0).unwrap();
+        assert_eq!(stats.trace[merged - 1][..32], (8..40).collect::<Vec<_>>());
+        assert_eq!(stats.trace[merged - 1][32..], (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_probe_sorts_follow_originals() {
+        // 统计版本必须和原来的排序走完全相同的步骤: 相同 key 的元素最后的排列也要完全一致
+        let mut seed = 5;
+        for n in [0, 1, 7, 16, 17, 33, 100, 300] {
+            let input: Vec<Keyed> = (0..n)
+                .map(|index| Keyed {
+                    key: lcg(&mut seed) % 20,
+                    index,
+                })
+                .collect();
+            for (name, sorter, probe_sorter) in comparison_sorts::<Keyed>() {
+                let mut expected = input.clone();
+                sorter(&mut expected);
+                let mut actual = input.clone();
+                sort_with_stats(&mut actual, probe_sorter, false);
+                let indices = |v: &[Keyed]| v.iter().map(|k| k.index).collect::<Vec<_>>();
+                assert_eq!(indices(&actual), indices(&expected), "{} n = {}", name, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_with_stats_panic() {
+        // 比较时 panic 不会留下任何状态, 之后的统计照常从零开始
+        #[derive(Clone, PartialEq, Eq)]
+        struct Fragile(i32);
+        impl PartialOrd for Fragile {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Fragile {
+            fn cmp(&self, other: &Self) -> Ordering {
+                assert!(self.0 != 13 && other.0 != 13, "不能比较 13");
+                self.0.cmp(&other.0)
+            }
+        }
+        let result = std::panic::catch_unwind(|| {
+            let mut vec = vec![Fragile(2), Fragile(13), Fragile(1)];
+            sort_with_stats(&mut vec, probe_insertion_sort, true)
+        });
+        assert!(result.is_err());
+        let mut vec = vec![3, 2, 1];
+        assert_eq!(
+            sort_with_stats(&mut vec, probe_insertion_sort, false).comparisons,
+            3
+        );
+        let mut plain = vec![3, 2, 1];
+        insertion_sort(&mut plain);
+        assert_eq!(plain, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_compare_sorts() {
+        let input = vec![37, 73, 57, 75, 91, 19, 46, 64];
+        let results = compare_sorts(&input);
+        assert_eq!(results.len(), 5);
+        for (_, stats) in &results {
+            assert!(stats.comparisons > 0);
+        }
+        let bubble = &results
+            .iter()
+            .find(|(name, _)| *name == "bubble_sort")
+            .unwrap()
+            .1;
+        assert_eq!(bubble.comparisons, 28);
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(Pattern::OrganPipe.generate(6, 0), vec![0, 1, 2, 2, 1, 0]);
+        assert_eq!(Pattern::Sawtooth.generate(16, 0)[..4], [0, 1, 0, 1]);
+        assert!(Pattern::FewUnique.generate(100, 1).iter().all(|x| *x < 4));
+        for pattern in Pattern::ALL {
+            assert_eq!(pattern.generate(100, 1).len(), 100);
+        }
+    }
+
+    #[test]
+    fn test_antiqsort_kills_quick_sort() {
+        let n = 500;
+        let mut killer = Pattern::AntiQsort.generate(n, 0);
+        // 生成的是 0..n 的一个排列
+        let mut values = killer.clone();
+        sort(&mut values);
+        assert_eq!(values, (0..n as u64).collect::<Vec<_>>());
+
+        let mut random = Pattern::Random.generate(n, 0);
+        let killer_stats = sort_with_stats(&mut killer, probe_quick_sort, false);
+        let random_stats = sort_with_stats(&mut random, probe_quick_sort, false);
+        // 三数取中时对手仍然能让每次划分只分出两三个元素, 比较次数约为 n^2 / 4, 还是平方级的
+        // 随机输入只需要 O(n log n) 次
+        assert!(killer_stats.comparisons > n * n / 5);
+        assert!(random_stats.comparisons < n * n / 40);
+    }
+
+    #[test]
+    fn test_check_sorted() {
+        let input: Vec<Keyed> = [2, 1, 1]
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| Keyed { key, index })
+            .collect();
+        let stable = vec![input[1], input[2], input[0]];
+        let unstable = vec![input[2], input[1], input[0]];
+        assert!(check_sorted(&input, &stable, true).is_ok());
+        assert!(check_sorted(&input, &unstable, false).is_ok());
+        assert!(check_sorted(&input, &unstable, true).is_err());
+        assert!(check_sorted(&input, &[input[1], input[1], input[0]], false).is_err());
+        assert!(check_sorted(&input, &input, false).is_err());
+    }
+
+    #[test]
+    fn test_run_benchmark() {
+        let results = run_benchmark(300, 42).unwrap();
+        assert_eq!(results.len(), Pattern::ALL.len() * algorithms().len());
+        let table = results_table(&results);
+        assert_eq!(table.lines().count(), algorithms().len() + 1);
+        let csv = results_csv(&results);
+        assert_eq!(csv.lines().count(), results.len() + 1);
+    }
+
+    // 较大规模的计时, 用 cargo test --release -- --ignored --nocapture 运行
+    #[test]
+    #[ignore]
+    fn bench_sorts() {
+        let results = run_benchmark(20_000, 42).unwrap();
+        println!("{}", results_table(&results));
+    }
+
+    // 每个测试用自己的临时目录, 结束后检查没有遗留的临时文件
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sort-test-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_external_sort_lines() {
+        let mut seed = 6;
+        let lines: Vec<String> = (0..1000)
+            .map(|_| format!("line-{}", lcg(&mut seed) % 500))
+            .collect();
+        let input = lines.join("\n");
+
+        let dir = test_dir("lines");
+        let sorter = ExternalSorter::new(LineCodec, 1024).temp_dir(&dir);
+        let mut output = Vec::new();
+        let runs = sorter.sort(input.as_bytes(), &mut output).unwrap();
+        assert!(runs > 1);
+
+        let mut expected = lines.clone();
+        sort(&mut expected);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            expected.join("\n") + "\n"
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sort_fixed_width() {
+        // 逆序的大输入, 每块都在内存中排好
+        let input: Vec<u8> = (0..20_000u32).rev().flat_map(|x| x.to_be_bytes()).collect();
+        let dir = test_dir("fixed");
+        let sorter = ExternalSorter::new(FixedWidthCodec { width: 4 }, 64 * 1024).temp_dir(&dir);
+        let mut output = Vec::new();
+        assert!(sorter.sort(&input[..], &mut output).unwrap() > 1);
+        let expected: Vec<u8> = (0..20_000u32).flat_map(|x| x.to_be_bytes()).collect();
+        assert_eq!(output, expected);
+        std::fs::remove_dir(&dir).unwrap();
+
+        // 最后一条记录不完整
+        let sorter = ExternalSorter::new(FixedWidthCodec { width: 4 }, 1024);
+        assert!(sorter.sort(&[1, 2, 3, 4, 5][..], Vec::new()).is_err());
+
+        // 宽度为 0 的记录没有意义, 直接报错而不是无限地读出空记录
+        let sorter = ExternalSorter::new(FixedWidthCodec { width: 0 }, 1024);
+        let error = sorter.sort(&[1, 2, 3][..], Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_external_sort_presorted() {
+        // 已经有序的输入, 每块都有好几万条记录
+        // 在默认大小 (2MB) 的线程栈上运行, 块内 sort 的递归深度不能随块的大小增长
+        let input: Vec<u8> = (0..200_000u32).flat_map(|x| x.to_be_bytes()).collect();
+        let output = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(move || {
+                let sorter = ExternalSorter::new(FixedWidthCodec { width: 4 }, 2 << 20);
+                let mut output = Vec::new();
+                assert!(sorter.sort(&input[..], &mut output).unwrap() > 1);
+                output
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let expected: Vec<u8> = (0..200_000u32).flat_map(|x| x.to_be_bytes()).collect();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_external_sort_length_prefixed() {
+        let records: Vec<&[u8]> = vec![b"pear", b"", b"apple", b"\x00\xff", b"fig", b"apple"];
+        let mut input = Vec::new();
+        for r in &records {
+            LengthPrefixedCodec.write_record(&mut input, r).unwrap();
+        }
+
+        // 预算很小, 每条记录单独成为一个 Run
+        let dir = test_dir("prefixed");
+        let sorter = ExternalSorter::new(LengthPrefixedCodec, 1).temp_dir(&dir);
+        let mut output = Vec::new();
+        assert_eq!(sorter.sort(&input[..], &mut output).unwrap(), records.len());
+
+        let mut reader = &output[..];
+        let mut sorted = Vec::new();
+        while let Some(r) = LengthPrefixedCodec.read_record(&mut reader).unwrap() {
+            sorted.push(r);
+        }
+        assert_eq!(
+            sorted,
+            vec![
+                b"".to_vec(),
+                b"\x00\xff".to_vec(),
+                b"apple".to_vec(),
+                b"apple".to_vec(),
+                b"fig".to_vec(),
+                b"pear".to_vec()
+            ]
+        );
+        std::fs::remove_dir(&dir).unwrap();
+
+        // 全部放得下时不产生临时文件
+        let sorter = ExternalSorter::new(LengthPrefixedCodec, 1 << 20);
+        assert_eq!(sorter.sort(&input[..], Vec::new()).unwrap(), 0);
+
+        // 损坏的长度前缀不会导致按它分配内存, 而是报告数据不完整
+        let mut corrupted = u32::MAX.to_le_bytes().to_vec();
+        corrupted.extend_from_slice(b"short");
+        let error = LengthPrefixedCodec
+            .read_record(&mut &corrupted[..])
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(sorter.sort(&corrupted[..], Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_external_sort_multi_pass() {
+        // Run 的个数远超每轮能合并的个数, 需要好几轮
+        let mut seed = 11;
+        let lines: Vec<String> = (0..10_000)
+            .map(|_| format!("{:05}", lcg(&mut seed) % 2000))
+            .collect();
+        let input = lines.join("\n");
+
+        let mut expected = lines.clone();
+        sort(&mut expected);
+        let expected = expected.join("\n") + "\n";
+
+        let dir = test_dir("multi-pass");
+        // (预算, max_fan_in, 每轮实际合并的个数)
+        for (budget, max_fan_in, fan_in) in [
+            (256, 64, 2),
+            (4 * MIN_READ_BUFFER, 3, 3),
+            (4 * MIN_READ_BUFFER, 64, 4),
+        ] {
+            let sorter = ExternalSorter::new(LineCodec, budget)
+                .temp_dir(&dir)
+                .max_fan_in(max_fan_in);
+            assert_eq!(sorter.fan_in(), fan_in);
+            let mut output = Vec::new();
+            assert!(sorter.sort(input.as_bytes(), &mut output).unwrap() > fan_in * fan_in);
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+            // 中间轮次产生的 Run 也都删掉了
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        }
+        std::fs::remove_dir(&dir).unwrap();
+
+        // 预算决定每轮合并几个: 读缓冲区不小于 MIN_READ_BUFFER, 也不超过 max_fan_in
+        assert_eq!(ExternalSorter::new(LineCodec, 1).fan_in(), 2);
+        assert_eq!(
+            ExternalSorter::new(LineCodec, 10 * MIN_READ_BUFFER).fan_in(),
+            10
+        );
+        assert_eq!(ExternalSorter::new(LineCodec, 1 << 30).fan_in(), 64);
+        assert_eq!(
+            ExternalSorter::new(LineCodec, 1 << 30)
+                .max_fan_in(8)
+                .fan_in(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_sorting_networks() {
+        // 0-1 原理: 一个比较网络能排好所有 0/1 序列, 就能排好任意序列
+        for n in 0..=NETWORK_MAX {
+            for bits in 0u32..1 << n {
+                let mut array: Vec<u32> = (0..n).map(|i| bits >> i & 1).collect();
+                network_sort_by(&mut array, &u32::cmp);
+                assert!(array.windows(2).all(|w| w[0] <= w[1]), "n = {}", n);
+            }
+        }
+        // 比较器个数都是已知的最优值
+        let sizes: Vec<usize> = NETWORKS.iter().map(|net| net.len()).collect();
+        assert_eq!(
+            sizes,
+            vec![0, 0, 1, 3, 5, 9, 12, 16, 19, 25, 29, 35, 39, 45, 51, 56, 60]
+        );
+    }
+
+    #[test]
+    fn test_stable_and_branchless_networks() {
+        let mut seed = 8;
+        for n in 0..=NETWORK_MAX {
+            let pairs: Vec<(u64, usize)> = (0..n).map(|i| (lcg(&mut seed) % 3, i)).collect();
+            let mut expected = pairs.clone();
+            insertion_sort_by(&mut expected, &|a: &(u64, usize), b: &(u64, usize)| {
+                a.0.cmp(&b.0)
+            });
+            let mut stable = pairs.clone();
+            stable_network_sort_by(&mut stable, &|a: &(u64, usize), b: &(u64, usize)| {
+                a.0.cmp(&b.0)
+            });
+            assert_eq!(stable, expected);
+
+            let mut floats: Vec<f64> = (0..n).map(|_| lcg(&mut seed) as f64 - 1e15).collect();
+            branchless_sort_by(&mut floats, |a, b| a < b);
+            assert!(floats.windows(2).all(|w| w[0] <= w[1]));
+        }
+    }
+
+    // 小数组上排序网络与插入排序的对比, 用 cargo test --release -- --ignored --nocapture 运行
+    #[test]
+    #[ignore]
+    fn bench_small_sorts() {
+        const ROUNDS: usize = 200_000;
+        let sorters: [(&str, Sorter<u32>); 4] = [
+            ("insertion_sort", |a| insertion_sort(a)),
+            ("network_sort", |a| network_sort_by(a, &u32::cmp)),
+            ("stable_network", |a| stable_network_sort_by(a, &u32::cmp)),
+            ("branchless_sort", |a| branchless_sort_by(a, |x, y| x < y)),
+        ];
+        let mut table = format!("{:<16}", "ns per slice");
+        for n in [4, 8, 12, 16] {
+            table += &format!("{:>10}", format!("n={}", n));
+        }
+        table.push('\n');
+        for (name, sorter) in sorters {
+            table += &format!("{:<16}", name);
+            for n in [4, 8, 12, 16] {
+                let mut seed = 9;
+                let input: Vec<u32> = (0..ROUNDS * n).map(|_| lcg(&mut seed) as u32).collect();
+                let mut data = input.clone();
+                let start = Instant::now();
+                for chunk in data.chunks_mut(n) {
+                    sorter(chunk);
+                }
+                let elapsed = start.elapsed();
+                assert!(data.chunks(n).all(|c| c.windows(2).all(|w| w[0] <= w[1])));
+                let ns = elapsed.as_nanos() as f64 / ROUNDS as f64;
+                table += &format!("{:>10.1}", ns);
+            }
+            table.push('\n');
+        }
+        println!("{}", table);
     }
 }
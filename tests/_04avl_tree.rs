@@ -1,17 +1,42 @@
 // 完成基本的二叉搜索树接口
 
+mod common;
+
+use common::Edge;
+use common::codec::{
+    COUNT_FLAG, CountError, Encode, escape_into, preorder_parents, read_varint, split_count,
+    unescape, write_varint,
+};
+use common::render::{RenderNode, dot_escape, write_ascii, write_dot};
+use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::fmt::Debug;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Write};
+use std::str::FromStr;
 
 #[derive(Debug)]
-struct TreeNode<T>
+struct TreeNode<T, A = (), V = ()>
 where
     T: Ord,
 {
     value: T,
-    left: Option<Box<TreeNode<T>>>,
-    right: Option<Box<TreeNode<T>>>,
+    left: Option<Box<TreeNode<T, A, V>>>,
+    right: Option<Box<TreeNode<T, A, V>>>,
     height: usize, // 新增：节点高度
+    size: usize,   // 子树中的节点数 (含自身), 用于 rank/select
+    aug: A,        // 随子树变化维护的附加信息, 普通 AVL 树为 ()
+    count: usize,  // 重数, 只有 DuplicatePolicy::Count 会大于 1
+    data: V,       // 跟着值一起存放的数据, 集合中为 (), 有序映射中 value 是键, data 是键对应的值
+}
+
+/// 节点附加信息, 由节点自身的值和左右孩子的附加信息算出
+/// 和高度一样在 update_height 中重新计算, 所以旋转之后也总是正确的
+trait Augment<T>: Sized {
+    fn compute(value: &T, left: Option<&Self>, right: Option<&Self>) -> Self;
+}
+
+impl<T> Augment<T> for () {
+    fn compute(_: &T, _: Option<&()>, _: Option<&()>) {}
 }
 
 #[derive(Debug)]
@@ -20,18 +45,59 @@ where
     T: Ord,
 {
     root: Option<Box<TreeNode<T>>>,
+    policy: DuplicatePolicy,
+}
+
+/// 插入重复值时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DuplicatePolicy {
+    Reject,  // 拒绝插入, 把新值还给调用方
+    Replace, // 用新值替换旧值
+    Count,   // 多重集合: 节点上的重数加一, 子树大小和遍历仍按不同的值计算
+}
+
+/// insert 的结果
+#[derive(Debug, PartialEq)]
+enum InsertOutcome<T> {
+    Inserted,       // 插入了新节点
+    Rejected(T),    // 值已存在, 原样退回
+    Replaced(T),    // 值已存在, 返回被替换掉的旧值
+    Counted(usize), // 值已存在, 返回新的重数
+}
+
+/// 删除的结果
+#[derive(Debug, PartialEq)]
+enum RemoveOutcome<T> {
+    Removed(T),         // 摘掉了节点, 返回其中的值
+    Decremented(usize), // 多重集合中重数大于 1, 只减一, 返回剩下的重数
 }
 
-impl<T> TreeNode<T>
+impl<T, A> TreeNode<T, A>
 where
     T: Ord,
+    A: Augment<T>,
 {
     fn new(value: T) -> Self {
+        Self::with_data(value, ())
+    }
+}
+
+impl<T, A, V> TreeNode<T, A, V>
+where
+    T: Ord,
+    A: Augment<T>,
+{
+    fn with_data(value: T, data: V) -> Self {
+        let aug = A::compute(&value, None, None);
         TreeNode {
             value,
             left: None,
             right: None,
             height: 1,
+            size: 1,
+            aug,
+            count: 1,
+            data,
         }
     }
 }
@@ -41,132 +107,379 @@ where
     T: Ord,
 {
     fn new() -> Self {
-        BinarySearchTree { root: None }
+        Self::with_policy(DuplicatePolicy::Reject)
+    }
+
+    /// 指定重复值的处理方式
+    fn with_policy(policy: DuplicatePolicy) -> Self {
+        BinarySearchTree { root: None, policy }
     }
 
     // Insert a value into the BST
-    fn insert(&mut self, value: T) {
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
         //TODO
         if let Some(ref mut root) = self.root {
-            root.insert(value);
+            root.insert(value, self.policy)
         } else {
             self.root = Some(Box::new(TreeNode::new(value)));
+            InsertOutcome::Inserted
         }
     }
 
-    /// AVL树插入入口
-    pub fn insert_avl(&mut self, value: T) {
-        self.root = match self.root.take() {
-            Some(root) => Some(root.insert_avl(value)),
-            None => Some(Box::new(TreeNode::new(value))),
+    /// AVL树插入入口, 值已存在时按 policy 处理, 返回实际发生了什么
+    pub fn insert_avl(&mut self, value: T) -> InsertOutcome<T> {
+        let (root, outcome) = match self.root.take() {
+            Some(root) => root.insert_avl(value, self.policy),
+            None => (Box::new(TreeNode::new(value)), InsertOutcome::Inserted),
         };
+        self.root = Some(root);
+        outcome
+    }
+
+    /// AVL树删除入口, 返回被删除的值
+    /// 多重集合中不管重数是多少都摘掉整个节点; 只去掉一个副本用 remove_one_avl
+    pub fn remove_avl(&mut self, value: &T) -> Option<T> {
+        let root = self.root.take()?;
+        let (root, removed) = root.remove_avl(value);
+        self.root = root;
+        removed.map(|(value, ())| value)
+    }
+
+    /// 删除一个副本, 不存在时返回 None
+    /// 多重集合中重数大于 1 时只把重数减一, 树的结构不变, 否则和 remove_avl 一样摘掉节点
+    pub fn remove_one_avl(&mut self, value: &T) -> Option<RemoveOutcome<T>> {
+        let node = self.find_mut(value)?;
+        if node.count > 1 {
+            node.count -= 1;
+            return Some(RemoveOutcome::Decremented(node.count));
+        }
+        self.remove_avl(value).map(RemoveOutcome::Removed)
     }
 
     // Search for a value in the BST
-    fn search(&self, value: T) -> bool {
+    // 和标准库的集合一样通过 Borrow 查找, 比如 String 树可以直接用 &str 查
+    fn search<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
         //TODO
         self.root.as_ref().map_or(false, |root| root.search(value))
     }
+
+    // 找到值所在的节点
+    fn find_mut(&mut self, value: &T) -> Option<&mut TreeNode<T>> {
+        let mut current = self.root.as_deref_mut();
+        while let Some(node) = current {
+            current = match value.cmp(&node.value) {
+                Ordering::Less => node.left.as_deref_mut(),
+                Ordering::Greater => node.right.as_deref_mut(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    /// 值的重数, 不存在时为 0
+    fn count(&self, value: &T) -> usize {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match value.cmp(&node.value) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return node.count,
+            };
+        }
+        0
+    }
+
+    /// 比 value 小的元素个数, value 不在树中也可以查询
+    pub fn rank(&self, value: &T) -> usize {
+        let mut rank = 0;
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            match value.cmp(&node.value) {
+                Ordering::Less => current = node.left.as_deref(),
+                Ordering::Greater => {
+                    // 左子树和当前节点都比 value 小
+                    rank += TreeNode::size(&node.left) + 1;
+                    current = node.right.as_deref();
+                }
+                Ordering::Equal => return rank + TreeNode::size(&node.left),
+            }
+        }
+        rank
+    }
+
+    /// 第 k 小的元素 (从 0 开始), 越界返回 None
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            let left = TreeNode::size(&node.left);
+            match k.cmp(&left) {
+                Ordering::Less => current = node.left.as_deref(),
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Greater => {
+                    k -= left + 1;
+                    current = node.right.as_deref();
+                }
+            }
+        }
+        None
+    }
+
+    /// 闭区间 [a, b] 内的元素个数
+    pub fn count_range(&self, a: &T, b: &T) -> usize {
+        if a > b {
+            return 0;
+        }
+        self.rank(b) - self.rank(a) + usize::from(self.search(b))
+    }
 }
 
-impl<T> TreeNode<T>
+impl<T, A> TreeNode<T, A>
 where
     T: Ord,
+    A: Augment<T>,
 {
     // Insert a node into the tree
-    fn insert(&mut self, value: T) {
+    fn insert(&mut self, value: T, policy: DuplicatePolicy) -> InsertOutcome<T> {
         //TODO
-        match value.cmp(&self.value) {
+        let outcome = match value.cmp(&self.value) {
             Ordering::Less => {
                 if let Some(ref mut left) = self.left {
-                    left.insert(value);
+                    left.insert(value, policy)
                 } else {
                     self.left = Some(Box::new(TreeNode::new(value)));
+                    InsertOutcome::Inserted
                 }
             }
             Ordering::Greater => {
                 if let Some(ref mut right) = self.right {
-                    right.insert(value);
+                    right.insert(value, policy)
                 } else {
                     self.right = Some(Box::new(TreeNode::new(value)));
+                    InsertOutcome::Inserted
                 }
             }
-            Ordering::Equal => {
-                // 重复值处理: 这里我们选择不插入重复值
-                // 也可以根据需求选择其他处理方式
+            Ordering::Equal => self.on_duplicate(value, policy),
+        };
+        self.update_height();
+        outcome
+    }
+
+    /// 重复值处理: 按策略拒绝, 替换或者累加重数
+    fn on_duplicate(&mut self, value: T, policy: DuplicatePolicy) -> InsertOutcome<T> {
+        match policy {
+            DuplicatePolicy::Reject => InsertOutcome::Rejected(value),
+            DuplicatePolicy::Replace => {
+                InsertOutcome::Replaced(std::mem::replace(&mut self.value, value))
+            }
+            DuplicatePolicy::Count => {
+                self.count += 1;
+                InsertOutcome::Counted(self.count)
             }
         }
     }
 
     // 辅助函数
-    // 递归查找节点
-    fn search(&self, value: T) -> bool {
-        match value.cmp(&self.value) {
-            Ordering::Less => self.left.as_ref().map_or(false, |left| left.search(value)),
-            Ordering::Greater => self
-                .right
-                .as_ref()
-                .map_or(false, |right| right.search(value)),
-            Ordering::Equal => true,
+    // 查找节点, 用循环沿着一条路径往下走
+    fn search<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self;
+        loop {
+            let next = match value.cmp(node.value.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+            match next {
+                Some(child) => node = child,
+                None => return false,
+            }
         }
     }
 
     /// AVL树专用插入方法
     /// 因为每次插入的平衡操作可能会消耗掉原根节点, 所以我们需要返回新的根节点
-    fn insert_avl(mut self: Box<TreeNode<T>>, value: T) -> Box<TreeNode<T>> {
+    fn insert_avl(
+        mut self: Box<TreeNode<T, A>>,
+        value: T,
+        policy: DuplicatePolicy,
+    ) -> (Box<TreeNode<T, A>>, InsertOutcome<T>) {
         // 1. 标准BST插入
-        match value.cmp(&self.value) {
-            Ordering::Less => {
-                self.left = match self.left {
-                    Some(left) => Some(left.insert_avl(value)),
-                    None => Some(Box::new(TreeNode::new(value))),
-                };
-            }
-            Ordering::Greater => {
-                self.right = match self.right {
-                    Some(right) => Some(right.insert_avl(value)),
-                    None => Some(Box::new(TreeNode::new(value))),
-                };
-            }
-            Ordering::Equal => return self, // 重复值不插入
+        let outcome = match value.cmp(&self.value) {
+            Ordering::Less => match self.left.take() {
+                Some(left) => {
+                    let (left, outcome) = left.insert_avl(value, policy);
+                    self.left = Some(left);
+                    outcome
+                }
+                None => {
+                    self.left = Some(Box::new(TreeNode::new(value)));
+                    InsertOutcome::Inserted
+                }
+            },
+            Ordering::Greater => match self.right.take() {
+                Some(right) => {
+                    let (right, outcome) = right.insert_avl(value, policy);
+                    self.right = Some(right);
+                    outcome
+                }
+                None => {
+                    self.right = Some(Box::new(TreeNode::new(value)));
+                    InsertOutcome::Inserted
+                }
+            },
+            // 重复值按策略处理, 结构不变, 但替换后附加信息可能要重算
+            Ordering::Equal => self.on_duplicate(value, policy),
         };
 
         // 2. 更新当前节点高度
         self.update_height();
 
         // 3. 平衡调整（自动处理4种旋转情况）
-        self.balance()
+        (self.balance(None), outcome)
+    }
+}
+
+// 下面的删除, 旋转和平衡对集合和有序映射通用, data 跟着所在的节点一起移动
+
+impl<T, A, V> TreeNode<T, A, V>
+where
+    T: Ord,
+    A: Augment<T>,
+{
+    /// AVL树专用删除方法
+    /// 和插入一样, 删除后回溯时每一层都可能旋转, 所以返回新的子树根 (子树可能被删空) 以及被删除的值和数据
+    /// 有序映射按借用的键查找, 所以键的类型是 Q
+    fn remove_avl<Q>(mut self: Box<Self>, value: &Q) -> (Option<Box<Self>>, Option<(T, V)>)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // 1. 标准BST删除
+        let removed = match value.cmp(self.value.borrow()) {
+            Ordering::Less => match self.left.take() {
+                Some(left) => {
+                    let (left, removed) = left.remove_avl(value);
+                    self.left = left;
+                    removed
+                }
+                None => None,
+            },
+            Ordering::Greater => match self.right.take() {
+                Some(right) => {
+                    let (right, removed) = right.remove_avl(value);
+                    self.right = right;
+                    removed
+                }
+                None => None,
+            },
+            Ordering::Equal => match (self.left.take(), self.right.take()) {
+                // 叶子直接删除
+                (None, None) => return (None, Some((self.value, self.data))),
+                // 只有一个孩子: AVL 的性质保证这个孩子是叶子, 直接顶替自己, 不需要再平衡
+                (Some(child), None) | (None, Some(child)) => {
+                    return (Some(child), Some((self.value, self.data)));
+                }
+                // 两个孩子: 用后继替换自己的值 (连同重数和数据), 再从右子树中删掉后继
+                (Some(left), Some(right)) => {
+                    let (successor, right) = right.remove_min_avl();
+                    self.left = Some(left);
+                    self.right = right;
+                    self.count = successor.count;
+                    Some((
+                        std::mem::replace(&mut self.value, successor.value),
+                        std::mem::replace(&mut self.data, successor.data),
+                    ))
+                }
+            },
+        };
+        if removed.is_none() {
+            // 没有找到, 树结构没有变化
+            return (Some(self), None);
+        }
+
+        // 2. 更新当前节点高度
+        self.update_height();
+
+        // 3. 平衡调整, 删除时一路上每一层都可能需要旋转
+        (Some(self.balance(None)), removed)
+    }
+
+    /// 摘下子树中的最小节点, 返回它以及平衡后的子树
+    fn remove_min_avl(mut self: Box<Self>) -> (Box<Self>, Option<Box<Self>>) {
+        match self.left.take() {
+            Some(left) => {
+                let (min, left) = left.remove_min_avl();
+                self.left = left;
+                self.update_height();
+                (min, Some(self.balance(None)))
+            }
+            // 没有左孩子, 自己就是最小值, 右孩子顶替自己
+            None => {
+                let right = self.right.take();
+                (self, right)
+            }
+        }
     }
 
-    /// 更新节点高度
+    /// 更新节点高度, 顺带更新子树大小和附加信息
+    /// 旋转和回溯时都会调用这里, 所以三者总是一起保持正确
     fn update_height(&mut self) {
         self.height = 1 + Self::height(&self.left).max(Self::height(&self.right));
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+        self.aug = A::compute(
+            &self.value,
+            self.left.as_ref().map(|n| &n.aug),
+            self.right.as_ref().map(|n| &n.aug),
+        );
     }
 
     /// 子树高度
-    fn height(node: &Option<Box<TreeNode<T>>>) -> usize {
+    fn height(node: &Option<Box<Self>>) -> usize {
         node.as_ref().map_or(0, |n| n.height)
     }
 
+    /// 子树大小
+    fn size(node: &Option<Box<Self>>) -> usize {
+        node.as_ref().map_or(0, |n| n.size)
+    }
+
     /// 平衡调整主逻辑
-    fn balance(mut self: Box<TreeNode<T>>) -> Box<TreeNode<T>> {
+    /// path 是从这棵子树的根到某个节点的路径 (有序映射插入时用来找回新节点), 随旋转一起调整
+    fn balance(mut self: Box<Self>, mut path: Option<&mut Path>) -> Box<Self> {
         let bf = self.balance_factor();
-        // Left-Left 情况
-        if bf > 1 && self.left.as_ref().unwrap().balance_factor() >= 0 {
-            return self.rotate_right();
-        }
-        // Left-Right 情况
-        if bf > 1 && self.left.as_ref().unwrap().balance_factor() < 0 {
-            self.left = Some(self.left.take().unwrap().rotate_left());
+        if bf > 1 {
+            // Left-Right 情况: 先把左孩子左旋, 变成 Left-Left
+            if self.left.as_ref().unwrap().balance_factor() < 0 {
+                self.left = Some(self.left.take().unwrap().rotate_left());
+                if let Some(path) = path.as_deref_mut() {
+                    path.lift_child(Side::Left, Side::Right);
+                }
+            }
+            // Left-Left 情况
+            if let Some(path) = path {
+                path.lift(Side::Left);
+            }
             return self.rotate_right();
         }
-        // Right-Right 情况
-        if bf < -1 && self.right.as_ref().unwrap().balance_factor() <= 0 {
-            return self.rotate_left();
-        }
-        // Right-Left 情况
-        if bf < -1 && self.right.as_ref().unwrap().balance_factor() > 0 {
-            self.right = Some(self.right.take().unwrap().rotate_right());
+        if bf < -1 {
+            // Right-Left 情况: 先把右孩子右旋, 变成 Right-Right
+            if self.right.as_ref().unwrap().balance_factor() > 0 {
+                self.right = Some(self.right.take().unwrap().rotate_right());
+                if let Some(path) = path.as_deref_mut() {
+                    path.lift_child(Side::Right, Side::Left);
+                }
+            }
+            // Right-Right 情况
+            if let Some(path) = path {
+                path.lift(Side::Right);
+            }
             return self.rotate_left();
         }
 
@@ -175,7 +488,7 @@ where
     }
 
     /// 右旋操作
-    fn rotate_right(mut self: Box<TreeNode<T>>) -> Box<TreeNode<T>> {
+    fn rotate_right(mut self: Box<Self>) -> Box<Self> {
         let mut new_root = self.left.take().unwrap();
         self.left = new_root.right.take();
         self.update_height();
@@ -185,7 +498,7 @@ where
     }
 
     /// 左旋操作
-    fn rotate_left(mut self: Box<TreeNode<T>>) -> Box<TreeNode<T>> {
+    fn rotate_left(mut self: Box<Self>) -> Box<Self> {
         let mut new_root = self.right.take().unwrap();
         self.right = new_root.left.take();
         self.update_height();
@@ -200,61 +513,1927 @@ where
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// 拼接, 切分和集合运算
+// join 和 split 都只沿一条路径走, 是 O(log n); 集合运算在它们之上分治,
+// 较小的树有 m 个节点时代价是 O(m log(n/m + 1)), 比逐个插入快
 
-    #[test]
-    fn test_insert_and_search() {
-        let mut bst = BinarySearchTree::new();
+type Link<T, A> = Option<Box<TreeNode<T, A>>>;
 
-        assert_eq!(bst.search(1), false);
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 把 left, pivot, right 拼成一棵树, 要求 left 全部小于 pivot, right 全部大于 pivot
+    pub fn join(left: Self, pivot: T, right: Self) -> Self {
+        let root = TreeNode::join(left.root, Box::new(TreeNode::new(pivot)), right.root);
+        BinarySearchTree {
+            root: Some(root),
+            policy: left.policy,
+        }
+    }
 
-        bst.insert(5);
-        bst.insert(3);
-        bst.insert(7);
-        bst.insert(2);
-        bst.insert(4);
+    /// 按 value 切成两棵树: 小于 value 的和大于 value 的, 中间返回 value 本身 (如果存在)
+    pub fn split(self, value: &T) -> (Self, Option<T>, Self) {
+        let (left, found, right) = TreeNode::split(self.root, value);
+        (
+            BinarySearchTree {
+                root: left,
+                policy: self.policy,
+            },
+            found.map(|node| node.value),
+            BinarySearchTree {
+                root: right,
+                policy: self.policy,
+            },
+        )
+    }
 
-        assert_eq!(bst.search(5), true);
-        assert_eq!(bst.search(3), true);
-        assert_eq!(bst.search(7), true);
-        assert_eq!(bst.search(2), true);
-        assert_eq!(bst.search(4), true);
+    /// 并集, 两边都有的值保留 self 中的那个
+    pub fn union(self, other: Self) -> Self {
+        BinarySearchTree {
+            root: TreeNode::union(self.root, other.root),
+            policy: self.policy,
+        }
+    }
 
-        assert_eq!(bst.search(1), false);
-        assert_eq!(bst.search(6), false);
+    /// 交集, 保留 self 中的值
+    pub fn intersection(self, other: Self) -> Self {
+        BinarySearchTree {
+            root: TreeNode::intersection(self.root, other.root),
+            policy: self.policy,
+        }
     }
 
-    #[test]
-    fn test_insert_duplicate() {
-        let mut bst = BinarySearchTree::new();
+    /// 差集: 在 self 中但不在 other 中
+    pub fn difference(self, other: Self) -> Self {
+        BinarySearchTree {
+            root: TreeNode::difference(self.root, other.root),
+            policy: self.policy,
+        }
+    }
 
-        bst.insert(1);
-        bst.insert(1);
+    /// 对称差: 只在其中一棵树中出现的值
+    pub fn symmetric_difference(self, other: Self) -> Self {
+        BinarySearchTree {
+            root: TreeNode::symmetric_difference(self.root, other.root),
+            policy: self.policy,
+        }
+    }
+}
+
+impl<T, A> TreeNode<T, A>
+where
+    T: Ord,
+    A: Augment<T>,
+{
+    /// 拼接: 沿较高一侧的边缘下降到高度相差不超过 1 的位置挂上 pivot, 回溯时像插入一样平衡
+    fn join(left: Link<T, A>, mut pivot: Box<Self>, right: Link<T, A>) -> Box<Self> {
+        let (lh, rh) = (Self::height(&left), Self::height(&right));
+        if lh > rh + 1 {
+            let mut left = left.unwrap();
+            left.right = Some(Self::join(left.right.take(), pivot, right));
+            left.update_height();
+            left.balance(None)
+        } else if rh > lh + 1 {
+            let mut right = right.unwrap();
+            right.left = Some(Self::join(left, pivot, right.left.take()));
+            right.update_height();
+            right.balance(None)
+        } else {
+            pivot.left = left;
+            pivot.right = right;
+            pivot.update_height();
+            pivot
+        }
+    }
 
-        assert_eq!(bst.search(1), true);
+    /// 没有中间节点的拼接: 借右边的最小节点当 pivot
+    fn join2(left: Link<T, A>, right: Link<T, A>) -> Link<T, A> {
+        match right {
+            None => left,
+            Some(right) => {
+                let (min, right) = right.remove_min_avl();
+                Some(Self::join(left, min, right))
+            }
+        }
+    }
 
-        match bst.root {
-            Some(ref node) => {
-                assert!(node.left.is_none());
-                assert!(node.right.is_none());
+    /// 切分: 沿查找路径把每个节点和不需要的那一侧子树 join 到结果上
+    fn split(node: Link<T, A>, value: &T) -> (Link<T, A>, Link<T, A>, Link<T, A>) {
+        let Some(mut node) = node else {
+            return (None, None, None);
+        };
+        let (left, right) = (node.left.take(), node.right.take());
+        match value.cmp(&node.value) {
+            Ordering::Less => {
+                let (less, found, greater) = Self::split(left, value);
+                (less, found, Some(Self::join(greater, node, right)))
             }
-            None => panic!("Root should not be None after insertion"),
+            Ordering::Greater => {
+                let (less, found, greater) = Self::split(right, value);
+                (Some(Self::join(left, node, less)), found, greater)
+            }
+            Ordering::Equal => (left, Some(node), right),
         }
     }
 
-    #[test]
-    fn test_avl_insert() {
-        let mut avl = BinarySearchTree::new();
-        avl.insert_avl(3);
-        avl.insert_avl(2);
-        avl.insert_avl(1); // 触发右旋
-        assert_eq!(avl.root.as_ref().unwrap().value, 2);
-        assert_eq!(avl.root.as_ref().unwrap().height, 2);
+    /// 摘下根节点, 返回左子树, 根, 右子树
+    fn expose(mut node: Box<Self>) -> (Link<T, A>, Box<Self>, Link<T, A>) {
+        let (left, right) = (node.left.take(), node.right.take());
+        (left, node, right)
+    }
 
-        avl.insert_avl(4);
-        avl.insert_avl(5); // 触发左旋
-        assert_eq!(avl.root.as_ref().unwrap().right.as_ref().unwrap().value, 4);
+    fn union(a: Link<T, A>, b: Link<T, A>) -> Link<T, A> {
+        let Some(a) = a else { return b };
+        let Some(b) = b else { return Some(a) };
+        let (a_left, pivot, a_right) = Self::expose(a);
+        let (b_left, _, b_right) = Self::split(Some(b), &pivot.value);
+        let left = Self::union(a_left, b_left);
+        let right = Self::union(a_right, b_right);
+        Some(Self::join(left, pivot, right))
+    }
+
+    fn intersection(a: Link<T, A>, b: Link<T, A>) -> Link<T, A> {
+        let (Some(a), Some(b)) = (a, b) else {
+            return None;
+        };
+        let (a_left, pivot, a_right) = Self::expose(a);
+        let (b_left, found, b_right) = Self::split(Some(b), &pivot.value);
+        let left = Self::intersection(a_left, b_left);
+        let right = Self::intersection(a_right, b_right);
+        match found {
+            Some(_) => Some(Self::join(left, pivot, right)),
+            None => Self::join2(left, right),
+        }
+    }
+
+    fn difference(a: Link<T, A>, b: Link<T, A>) -> Link<T, A> {
+        let a = a?;
+        let Some(b) = b else { return Some(a) };
+        let (b_left, pivot, b_right) = Self::expose(b);
+        let (a_left, _, a_right) = Self::split(Some(a), &pivot.value);
+        let left = Self::difference(a_left, b_left);
+        let right = Self::difference(a_right, b_right);
+        Self::join2(left, right)
+    }
+
+    fn symmetric_difference(a: Link<T, A>, b: Link<T, A>) -> Link<T, A> {
+        let Some(a) = a else { return b };
+        let Some(b) = b else { return Some(a) };
+        let (a_left, pivot, a_right) = Self::expose(a);
+        let (b_left, found, b_right) = Self::split(Some(b), &pivot.value);
+        let left = Self::symmetric_difference(a_left, b_left);
+        let right = Self::symmetric_difference(a_right, b_right);
+        match found {
+            Some(_) => Self::join2(left, right),
+            None => Some(Self::join(left, pivot, right)),
+        }
+    }
+}
+
+// 遍历
+// 都用显式的栈或队列实现, 不会因为树太深而栈溢出
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 中序遍历, 也就是从小到大
+    fn iter(&self) -> InOrder<'_, T> {
+        self.in_order()
+    }
+
+    /// 中序遍历: 左子树, 根, 右子树
+    fn in_order(&self) -> InOrder<'_, T> {
+        let mut iter = InOrder { stack: Vec::new() };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+
+    /// 前序遍历: 根, 左子树, 右子树
+    fn pre_order(&self) -> PreOrder<'_, T> {
+        PreOrder {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    /// 后序遍历: 左子树, 右子树, 根
+    fn post_order(&self) -> PostOrder<'_, T> {
+        PostOrder {
+            stack: self
+                .root
+                .as_deref()
+                .map(|n| (n, false))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// 层序遍历: 按层从上到下, 每层从左到右
+    fn level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder {
+            queue: self.root.as_deref().into_iter().collect(),
+        }
+    }
+}
+
+/// 中序遍历的迭代器
+/// 栈里是还没有返回的节点, 它们的左子树都已经处理完或者正在处理
+struct InOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+}
+
+impl<'a, T> InOrder<'a, T>
+where
+    T: Ord,
+{
+    // 从 node 开始一路向左, 沿途的节点都压栈
+    fn push_left(&mut self, mut node: Option<&'a TreeNode<T>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, T> Iterator for InOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        // 左子树已经访问完, 接下来是右子树中最小的节点
+        self.push_left(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+/// 前序遍历的迭代器
+struct PreOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+}
+
+impl<'a, T> Iterator for PreOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        // 先压右再压左, 这样左子树先出栈
+        if let Some(right) = node.right.as_deref() {
+            self.stack.push(right);
+        }
+        if let Some(left) = node.left.as_deref() {
+            self.stack.push(left);
+        }
+        Some(&node.value)
+    }
+}
+
+/// 后序遍历的迭代器
+/// 节点第一次出栈时还不能返回, 要先把它标记为已展开再压回去, 然后压入两个子树
+/// 第二次出栈时, 它的两个子树都已经访问完了
+struct PostOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<(&'a TreeNode<T>, bool)>,
+}
+
+impl<'a, T> Iterator for PostOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(&node.value);
+            }
+            self.stack.push((node, true));
+            if let Some(right) = node.right.as_deref() {
+                self.stack.push((right, false));
+            }
+            if let Some(left) = node.left.as_deref() {
+                self.stack.push((left, false));
+            }
+        }
+    }
+}
+
+/// 层序遍历的迭代器, 就是树上的 BFS
+struct LevelOrder<'a, T>
+where
+    T: Ord,
+{
+    queue: VecDeque<&'a TreeNode<T>>,
+}
+
+impl<'a, T> Iterator for LevelOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.left.as_deref());
+        self.queue.extend(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+/// 按从小到大的顺序取出所有值的迭代器, 会消耗整棵树
+struct IntoIter<T>
+where
+    T: Ord,
+{
+    stack: Vec<Box<TreeNode<T>>>,
+}
+
+impl<T> IntoIter<T>
+where
+    T: Ord,
+{
+    // 和 InOrder::push_left 一样, 只是这里拿走了左子树的所有权
+    fn push_left(&mut self, mut node: Option<Box<TreeNode<T>>>) {
+        while let Some(mut n) = node {
+            node = n.left.take();
+            self.stack.push(n);
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T>
+where
+    T: Ord,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut node = self.stack.pop()?;
+        self.push_left(node.right.take());
+        Some(node.value)
+    }
+}
+
+impl<T> IntoIterator for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        let mut iter = IntoIter { stack: Vec::new() };
+        iter.push_left(self.root);
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BinarySearchTree<T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+    type IntoIter = InOrder<'a, T>;
+
+    fn into_iter(self) -> InOrder<'a, T> {
+        self.in_order()
+    }
+}
+
+impl<T> FromIterator<T> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = BinarySearchTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<T> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert_avl(value);
+        }
+    }
+}
+
+// 可视化
+// 画法在 common/render.rs 里, 这里只决定节点上显示什么
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord + Display,
+{
+    /// 横向打印: 根在最左边, 右子树画在上面, 左子树画在下面
+    pub fn to_ascii(&self) -> String {
+        let mut out = String::new();
+        if let Some(root) = self.root.as_deref() {
+            write_ascii(root, &mut out);
+        }
+        out
+    }
+
+    /// 导出 Graphviz DOT, 可以用 `dot -Tpng` 画出来
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        if let Some(root) = self.root.as_deref() {
+            write_dot(root, &mut out);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<T, A> RenderNode for TreeNode<T, A>
+where
+    T: Ord + Display,
+    A: Augment<T>,
+{
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+
+    /// 值以及高度和平衡因子
+    fn ascii_label(&self) -> String {
+        format!(
+            "{} (h={}, bf={})",
+            self.value,
+            self.height,
+            self.balance_factor()
+        )
+    }
+
+    fn dot_attrs(&self) -> String {
+        format!(
+            "label=\"{}\\nh={} bf={}\"",
+            dot_escape(&self.value.to_string()),
+            self.height,
+            self.balance_factor()
+        )
+    }
+}
+
+// 序列化
+// 搜索树的形状由前序序列唯一确定, 所以只按前序写出每个节点, 同时带上高度, 恢复时用来校验
+// 用 insert 建的树不一定平衡, 所以只校验高度, 不校验平衡, 这样任何形状都能原样恢复
+// 文本格式: 节点之间用空格分隔, 每个节点写成 "值:高度", 重数大于 1 时再加上 "*重数"
+// 值中的空白、'*' 和反斜杠会被转义
+// 字节格式: 每个节点一个标记字节 1, 最高位表示值后面还跟着重数, 之后是变长整数表示的高度和值的编码
+// 和其他树共用的编码, 转义和前序重建在 common/codec.rs 里
+
+/// 反序列化失败的原因
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    UnexpectedEnd,        // 字节流在一个节点的中间结束
+    InvalidTag(u8),       // 无法识别的标记字节
+    InvalidToken(String), // 文本中无法解析的记号
+    OutOfOrder,           // 不是任何一棵搜索树的前序序列, 包括有重复值的情况
+    HeightMismatch,       // 记录的高度和实际的形状不符
+    ZeroCount,            // 记录的重数为 0
+}
+
+impl From<CountError> for DecodeError {
+    fn from(error: CountError) -> Self {
+        match error {
+            CountError::Zero => DecodeError::ZeroCount,
+            CountError::Invalid(token) => DecodeError::InvalidToken(token),
+        }
+    }
+}
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 按前序访问每个节点
+    fn walk_preorder(&self, mut visit: impl FnMut(&TreeNode<T>)) {
+        let mut stack: Vec<&TreeNode<T>> = self.root.as_deref().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            visit(node);
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+        }
+    }
+
+    /// 序列化成文本, 例如 "4:3 2:2 1:1 3:1 6:1*2"
+    fn to_preorder_string(&self) -> String
+    where
+        T: Display,
+    {
+        let mut out = String::new();
+        self.walk_preorder(|node| {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            escape_into(&node.value.to_string(), &mut out);
+            write!(out, ":{}", node.height).unwrap();
+            if node.count > 1 {
+                write!(out, "*{}", node.count).unwrap();
+            }
+        });
+        out
+    }
+
+    /// 从 to_preorder_string 的结果恢复, 重复值策略恢复成默认的 Reject
+    fn from_preorder_str(s: &str) -> Result<Self, DecodeError>
+    where
+        T: FromStr,
+    {
+        let nodes = s
+            .split_whitespace()
+            .map(|token| {
+                let invalid = || DecodeError::InvalidToken(token.to_string());
+                let (rest, count) = split_count(token)?;
+                let (value, height) = rest.rsplit_once(':').ok_or_else(invalid)?;
+                let value = unescape(value).and_then(|value| value.parse().ok());
+                let mut node = Box::new(TreeNode::new(value.ok_or_else(invalid)?));
+                node.height = height.parse().map_err(|_| invalid())?;
+                node.count = count;
+                Ok(node)
+            })
+            .collect::<Result<_, DecodeError>>()?;
+        Self::from_preorder_nodes(nodes)
+    }
+
+    /// 序列化成字节
+    fn to_bytes(&self) -> Vec<u8>
+    where
+        T: Encode,
+    {
+        let mut out = Vec::new();
+        self.walk_preorder(|node| {
+            out.push(if node.count > 1 { 1 | COUNT_FLAG } else { 1 });
+            write_varint(node.height as u64, &mut out);
+            node.value.encode(&mut out);
+            if node.count > 1 {
+                write_varint(node.count as u64, &mut out);
+            }
+        });
+        out
+    }
+
+    /// 从 to_bytes 的结果恢复, 重复值策略恢复成默认的 Reject
+    fn from_bytes(mut input: &[u8]) -> Result<Self, DecodeError>
+    where
+        T: Encode,
+    {
+        let mut nodes = Vec::new();
+        while let Some((&tag, rest)) = input.split_first() {
+            input = rest;
+            if tag & !COUNT_FLAG != 1 {
+                return Err(DecodeError::InvalidTag(tag));
+            }
+            let height = read_varint(&mut input).ok_or(DecodeError::UnexpectedEnd)?;
+            let value = T::decode(&mut input).ok_or(DecodeError::UnexpectedEnd)?;
+            let mut node = Box::new(TreeNode::new(value));
+            node.height = usize::try_from(height).map_err(|_| DecodeError::HeightMismatch)?;
+            if tag & COUNT_FLAG != 0 {
+                node.count = match read_varint(&mut input).ok_or(DecodeError::UnexpectedEnd)? {
+                    0 => return Err(DecodeError::ZeroCount),
+                    count => count as usize,
+                };
+            }
+            nodes.push(node);
+        }
+        Self::from_preorder_nodes(nodes)
+    }
+
+    fn from_preorder_nodes(nodes: Vec<Box<TreeNode<T>>>) -> Result<Self, DecodeError> {
+        let parents =
+            preorder_parents(&nodes, |node| &node.value).ok_or(DecodeError::OutOfOrder)?;
+        Ok(BinarySearchTree {
+            root: TreeNode::assemble(nodes, &parents)?,
+            policy: DuplicatePolicy::Reject,
+        })
+    }
+}
+
+impl<T, A> TreeNode<T, A>
+where
+    T: Ord,
+    A: Augment<T>,
+{
+    /// 按 parents 把节点挂起来, 返回根
+    /// 父节点总是排在孩子前面, 所以倒着挂时每个节点的子树都已经完整, 可以在这时校验它的高度
+    fn assemble(
+        nodes: Vec<Box<Self>>,
+        parents: &[(usize, Edge)],
+    ) -> Result<Option<Box<Self>>, DecodeError> {
+        let mut slots: Vec<Option<Box<Self>>> = nodes.into_iter().map(Some).collect();
+        for (i, &(parent, edge)) in parents.iter().enumerate().rev() {
+            let mut node = slots[i].take().unwrap();
+            let recorded = node.height;
+            // 顺带算出子树大小和附加信息
+            node.update_height();
+            if node.height != recorded {
+                return Err(DecodeError::HeightMismatch);
+            }
+            match edge {
+                Edge::Root => return Ok(Some(node)),
+                Edge::Left => slots[parent].as_mut().unwrap().left = Some(node),
+                Edge::Right => slots[parent].as_mut().unwrap().right = Some(node),
+            }
+        }
+        Ok(None)
+    }
+}
+
+// 有序映射
+// 直接用上面的 TreeNode 存放键值对: value 是键, data 是键对应的值
+// 平衡, 旋转和删除都和集合共用同一份实现
+
+/// 有序映射的节点, 没有附加信息
+type MapNode<K, V> = TreeNode<K, (), V>;
+
+// 插入时要把新值的可变引用交给调用方, 但旋转会移动节点所在的 Box, 不能预先记下值的地址
+// 所以插入时同时维护从子树根到新节点的路径, 旋转时跟着调整, 插入完成后沿路径重新走到新节点
+// entry() 查找时记下的也是这样的路径, 之后读写值和插入新节点都沿路径走, 不必再比较键
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// 从子树根到某个节点 (或某个空位) 的路径, 反向存放: 最后一个元素是从根出发的第一步
+#[derive(Debug, Default)]
+struct Path(Vec<Side>);
+
+impl Path {
+    /// 由从根出发依次走过的方向构造
+    fn from_steps(mut steps: Vec<Side>) -> Self {
+        steps.reverse();
+        Path(steps)
+    }
+
+    /// 子树根 up 侧的孩子被旋转上来成为新的根
+    fn lift(&mut self, up: Side) {
+        let down = up.opposite();
+        match self.0.pop() {
+            // 目标是原来的根, 它成了新根 down 侧的孩子
+            None => self.0.push(down),
+            // 目标在原来的根的另一侧, 上面多了一层
+            Some(side) if side == down => {
+                self.0.push(down);
+                self.0.push(down);
+            }
+            Some(_) => match self.0.pop() {
+                // 目标就是被提上来的孩子
+                None => {}
+                // 目标在孩子的外侧子树, 跟着孩子上升一层
+                Some(side) if side == up => self.0.push(up),
+                // 目标在孩子的内侧子树, 这棵子树被挂到原来的根的 up 侧
+                Some(_) => {
+                    self.0.push(up);
+                    self.0.push(down);
+                }
+            },
+        }
+    }
+
+    /// 子树根 side 侧的孩子做了一次旋转, 孩子的 up 侧孩子被提上来
+    fn lift_child(&mut self, side: Side, up: Side) {
+        if self.0.last() == Some(&side) {
+            self.0.pop();
+            self.lift(up);
+            self.0.push(side);
+        }
+    }
+
+    /// 沿路径从子树根走到目标节点
+    fn get<'n, T: Ord, A, V>(&self, mut node: &'n TreeNode<T, A, V>) -> &'n TreeNode<T, A, V> {
+        for &side in self.0.iter().rev() {
+            node = node.child(side).as_deref().unwrap();
+        }
+        node
+    }
+
+    fn get_mut<'n, T: Ord, A, V>(
+        &self,
+        mut node: &'n mut TreeNode<T, A, V>,
+    ) -> &'n mut TreeNode<T, A, V> {
+        for &side in self.0.iter().rev() {
+            node = node.child_mut(side).as_deref_mut().unwrap();
+        }
+        node
+    }
+}
+
+impl<T, A, V> TreeNode<T, A, V>
+where
+    T: Ord,
+{
+    fn child(&self, side: Side) -> &Option<Box<Self>> {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    fn child_mut(&mut self, side: Side) -> &mut Option<Box<Self>> {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+        }
+    }
+}
+
+impl<K, V> MapNode<K, V>
+where
+    K: Ord,
+{
+    /// 沿 path 走到它指向的空位, 挂上新节点, 返回平衡后的子树根
+    /// 返回时 path 是从新的子树根到新节点的路径
+    fn attach(mut self: Box<Self>, node: Box<Self>, path: &mut Path) -> Box<Self> {
+        let side = path.0.pop().unwrap();
+        let child = self.child_mut(side);
+        *child = Some(match child.take() {
+            Some(child) => child.attach(node, path),
+            None => node,
+        });
+        path.0.push(side);
+        self.update_height();
+        self.balance(Some(path))
+    }
+}
+
+/// 基于 AVL 树的有序映射
+#[derive(Debug)]
+struct AvlMap<K, V>
+where
+    K: Ord,
+{
+    root: Option<Box<MapNode<K, V>>>,
+}
+
+impl<K, V> AvlMap<K, V>
+where
+    K: Ord,
+{
+    pub fn new() -> Self {
+        AvlMap { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        TreeNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// 插入键值对, 键已存在时替换值并返回旧值 (键本身保持不变)
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match key.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.data),
+            };
+        }
+        None
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_deref_mut();
+        while let Some(node) = current {
+            current = match key.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref_mut(),
+                Ordering::Greater => node.right.as_deref_mut(),
+                Ordering::Equal => return Some(&mut node.data),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// 删除键, 返回对应的值
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let root = self.root.take()?;
+        let (root, removed) = root.remove_avl(key);
+        self.root = root;
+        removed.map(|(_, value)| value)
+    }
+
+    /// 取得键对应的位置, 用法和标准库的 entry 相同
+    /// 只查找这一次, 记下从根到键所在节点 (或应该插入的空位) 的路径
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let mut steps = Vec::new();
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            let side = match key.cmp(&node.value) {
+                Ordering::Less => Side::Left,
+                Ordering::Greater => Side::Right,
+                Ordering::Equal => {
+                    let path = Path::from_steps(steps);
+                    return Entry::Occupied(OccupiedEntry {
+                        map: self,
+                        key,
+                        path,
+                    });
+                }
+            };
+            steps.push(side);
+            current = node.child(side).as_deref();
+        }
+        let path = Path::from_steps(steps);
+        Entry::Vacant(VacantEntry {
+            map: self,
+            key,
+            path,
+        })
+    }
+
+    /// 按键从小到大遍历 (&K, &V)
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+/// entry() 返回的位置, 键可能已存在也可能不存在
+enum Entry<'a, K, V>
+where
+    K: Ord,
+{
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+struct OccupiedEntry<'a, K, V>
+where
+    K: Ord,
+{
+    map: &'a mut AvlMap<K, V>,
+    key: K,
+    path: Path, // 从根到键所在节点的路径
+}
+
+struct VacantEntry<'a, K, V>
+where
+    K: Ord,
+{
+    map: &'a mut AvlMap<K, V>,
+    key: K,
+    path: Path, // 从根到新节点应该挂上的空位的路径
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// 不存在时插入 default, 返回值的可变引用
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// 已存在时先修改一下值
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.path.get(self.map.root.as_deref().unwrap()).data
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self
+            .path
+            .get_mut(self.map.root.as_deref_mut().unwrap())
+            .data
+    }
+
+    /// 转成和 map 借用一样长的可变引用
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self
+            .path
+            .get_mut(self.map.root.as_deref_mut().unwrap())
+            .data
+    }
+
+    /// 替换值, 返回旧值
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// 从 map 中删除, 返回值
+    /// 删除要在回溯时重新平衡, 所以还是从根按键下行, 这一趟就是删除本身
+    pub fn remove(self) -> V {
+        self.map.remove(&self.key).unwrap()
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// 插入值, 返回它的可变引用
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { map, key, mut path } = self;
+        let node = Box::new(TreeNode::with_data(key, value));
+        // 沿 entry() 记下的路径挂上新节点, attach 回溯时的旋转会同步调整路径
+        let root = match map.root.take() {
+            Some(root) => root.attach(node, &mut path),
+            None => node,
+        };
+        &mut path.get_mut(map.root.insert(root)).data
+    }
+}
+
+/// 按键中序遍历的迭代器
+struct Iter<'a, K, V>
+where
+    K: Ord,
+{
+    stack: Vec<&'a MapNode<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V>
+where
+    K: Ord,
+{
+    fn push_left(&mut self, mut node: Option<&'a MapNode<K, V>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V>
+where
+    K: Ord,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.as_deref());
+        Some((&node.value, &node.data))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a AvlMap<K, V>
+where
+    K: Ord,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for AvlMap<K, V>
+where
+    K: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = AvlMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+// 区间树
+// 节点按 (start, end) 排序, 附加信息是子树中最大的右端点, 插入删除直接复用 AVL 的实现
+
+/// 闭区间 [start, end]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Interval<T> {
+    start: T,
+    end: T,
+}
+
+impl<T> Interval<T>
+where
+    T: Ord,
+{
+    pub fn new(start: T, end: T) -> Self {
+        assert!(start <= end, "区间左端点不能大于右端点");
+        Interval { start, end }
+    }
+
+    /// 两个闭区间是否相交
+    pub fn overlaps(&self, other: &Interval<T>) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// 子树中最大的右端点
+#[derive(Debug)]
+struct MaxEnd<T>(T);
+
+impl<T> Augment<Interval<T>> for MaxEnd<T>
+where
+    T: Ord + Clone,
+{
+    fn compute(value: &Interval<T>, left: Option<&Self>, right: Option<&Self>) -> Self {
+        let mut max = &value.end;
+        for child in [left, right].into_iter().flatten() {
+            if child.0 > *max {
+                max = &child.0;
+            }
+        }
+        MaxEnd(max.clone())
+    }
+}
+
+#[derive(Debug)]
+struct IntervalTree<T>
+where
+    T: Ord,
+{
+    root: Option<Box<TreeNode<Interval<T>, MaxEnd<T>>>>,
+}
+
+impl<T> IntervalTree<T>
+where
+    T: Ord + Clone,
+{
+    pub fn new() -> Self {
+        IntervalTree { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        TreeNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// 插入区间, 完全相同的区间只保存一份, 返回是否插入成功
+    pub fn insert(&mut self, interval: Interval<T>) -> bool {
+        let (root, outcome) = match self.root.take() {
+            Some(root) => root.insert_avl(interval, DuplicatePolicy::Reject),
+            None => (Box::new(TreeNode::new(interval)), InsertOutcome::Inserted),
+        };
+        self.root = Some(root);
+        outcome == InsertOutcome::Inserted
+    }
+
+    /// 删除区间, 返回被删除的区间
+    pub fn remove(&mut self, interval: &Interval<T>) -> Option<Interval<T>> {
+        let root = self.root.take()?;
+        let (root, removed) = root.remove_avl(interval);
+        self.root = root;
+        removed.map(|(interval, ())| interval)
+    }
+
+    /// 与 query 相交的所有区间, 按 (start, end) 从小到大
+    pub fn overlapping(&self, query: Interval<T>) -> Overlapping<'_, T> {
+        let mut iter = Overlapping {
+            stack: Vec::new(),
+            query,
+        };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+
+    /// 包含 point 的所有区间
+    pub fn stabbing(&self, point: &T) -> Overlapping<'_, T> {
+        self.overlapping(Interval::new(point.clone(), point.clone()))
+    }
+}
+
+/// 带剪枝的中序遍历
+/// 子树最大右端点小于 query.start 时整棵子树都不可能相交, 直接跳过
+struct Overlapping<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<Interval<T>, MaxEnd<T>>>,
+    query: Interval<T>,
+}
+
+impl<'a, T> Overlapping<'a, T>
+where
+    T: Ord,
+{
+    fn push_left(&mut self, mut node: Option<&'a TreeNode<Interval<T>, MaxEnd<T>>>) {
+        while let Some(n) = node {
+            if n.aug.0 < self.query.start {
+                break;
+            }
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, T> Iterator for Overlapping<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a Interval<T>;
+
+    fn next(&mut self) -> Option<&'a Interval<T>> {
+        while let Some(node) = self.stack.pop() {
+            // 中序后面的区间左端点只会更大, 都不可能再相交
+            if node.value.start > self.query.end {
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(node.right.as_deref());
+            if node.value.overlaps(&self.query) {
+                return Some(&node.value);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 简单的线性同余生成器
+    fn lcg(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
+    #[test]
+    fn test_insert_and_search() {
+        let mut bst = BinarySearchTree::new();
+
+        assert_eq!(bst.search(&1), false);
+
+        bst.insert(5);
+        bst.insert(3);
+        bst.insert(7);
+        bst.insert(2);
+        bst.insert(4);
+
+        assert_eq!(bst.search(&5), true);
+        assert_eq!(bst.search(&3), true);
+        assert_eq!(bst.search(&7), true);
+        assert_eq!(bst.search(&2), true);
+        assert_eq!(bst.search(&4), true);
+
+        assert_eq!(bst.search(&1), false);
+        assert_eq!(bst.search(&6), false);
+
+        // 通过 Borrow 查找, 不需要先构造 String
+        let mut names = BinarySearchTree::new();
+        names.insert_avl("avl".to_string());
+        assert!(names.search("avl"));
+        assert!(!names.search("rb"));
+    }
+
+    #[test]
+    fn test_insert_duplicate() {
+        let mut bst = BinarySearchTree::new();
+
+        bst.insert(1);
+        bst.insert(1);
+
+        assert_eq!(bst.search(&1), true);
+
+        match bst.root {
+            Some(ref node) => {
+                assert!(node.left.is_none());
+                assert!(node.right.is_none());
+            }
+            None => panic!("Root should not be None after insertion"),
+        }
+    }
+
+    #[test]
+    fn test_avl_insert() {
+        let mut avl = BinarySearchTree::new();
+        avl.insert_avl(3);
+        avl.insert_avl(2);
+        avl.insert_avl(1); // 触发右旋
+        assert_eq!(avl.root.as_ref().unwrap().value, 2);
+        assert_eq!(avl.root.as_ref().unwrap().height, 2);
+
+        avl.insert_avl(4);
+        avl.insert_avl(5); // 触发左旋
+        assert_eq!(avl.root.as_ref().unwrap().right.as_ref().unwrap().value, 4);
+    }
+
+    #[test]
+    fn test_traversals() {
+        // 顺序插入 1..=7 会被平衡成一棵满二叉树
+        //        4
+        //     2     6
+        //    1 3   5 7
+        let avl: BinarySearchTree<i32> = (1..=7).collect();
+        assert_eq!(
+            avl.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            avl.pre_order().copied().collect::<Vec<_>>(),
+            vec![4, 2, 1, 3, 6, 5, 7]
+        );
+        assert_eq!(
+            avl.post_order().copied().collect::<Vec<_>>(),
+            vec![1, 3, 2, 5, 7, 6, 4]
+        );
+        assert_eq!(
+            avl.level_order().copied().collect::<Vec<_>>(),
+            vec![4, 2, 6, 1, 3, 5, 7]
+        );
+    }
+
+    #[test]
+    fn test_into_iter_and_extend() {
+        let mut avl: BinarySearchTree<i32> = (0..100).rev().collect();
+        avl.extend(50..150);
+        // extend 同样走 AVL 插入, 树高仍然是对数级别
+        assert!(avl.root.as_ref().unwrap().height <= 10);
+        assert_eq!((&avl).into_iter().count(), 150);
+        assert_eq!(
+            avl.into_iter().collect::<Vec<_>>(),
+            (0..150).collect::<Vec<_>>()
+        );
+    }
+
+    // 检查每个节点: 记录的高度正确, 平衡因子的绝对值不超过 1, 并且满足 BST 的顺序
+    // 返回子树的高度和节点数
+    fn check_avl<T: Ord>(node: &Option<Box<TreeNode<T>>>) -> (usize, usize) {
+        match node {
+            None => (0, 0),
+            Some(n) => {
+                let (lh, ln) = check_avl(&n.left);
+                let (rh, rn) = check_avl(&n.right);
+                assert_eq!(n.height, 1 + lh.max(rh), "高度记录错误");
+                assert!(n.balance_factor().abs() <= 1, "平衡因子超出范围");
+                assert_eq!(n.size, 1 + ln + rn, "子树大小记录错误");
+                assert!(n.left.as_ref().is_none_or(|l| l.value < n.value));
+                assert!(n.right.as_ref().is_none_or(|r| r.value > n.value));
+                (n.height, 1 + ln + rn)
+            }
+        }
+    }
+
+    #[test]
+    fn test_avl_remove() {
+        let mut avl: BinarySearchTree<i32> = (1..=7).collect();
+        // 删除有两个孩子的根, 后继 5 顶上
+        assert_eq!(avl.remove_avl(&4), Some(4));
+        assert_eq!(avl.root.as_ref().unwrap().value, 5);
+        assert_eq!(avl.remove_avl(&4), None);
+        // 删掉右边的 5, 6, 7 之后左边过高, 触发旋转
+        for x in [5, 6, 7] {
+            assert_eq!(avl.remove_avl(&x), Some(x));
+            check_avl(&avl.root);
+        }
+        assert_eq!(avl.root.as_ref().unwrap().value, 2);
+        assert_eq!(avl.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        for x in [1, 2, 3] {
+            avl.remove_avl(&x);
+        }
+        assert!(avl.root.is_none());
+    }
+
+    #[test]
+    fn test_avl_random_insert_remove() {
+        let mut seed: u64 = 2025;
+        let mut next = || lcg(&mut seed) % 2000;
+        let mut avl = BinarySearchTree::new();
+        let mut model = std::collections::BTreeSet::new();
+        for round in 0..20_000 {
+            let x = next();
+            // 前半段插入多于删除, 后半段删除多于插入, 让树先长大再缩小
+            let insert = if round < 10_000 {
+                next() % 3 != 0
+            } else {
+                next() % 3 == 0
+            };
+            if insert {
+                avl.insert_avl(x);
+                model.insert(x);
+            } else {
+                assert_eq!(avl.remove_avl(&x), model.take(&x));
+            }
+            if round % 100 == 0 {
+                let (height, n) = check_avl(&avl.root);
+                assert_eq!(n, model.len());
+                // AVL 树高度的上界约为 1.44 * log2(n + 2)
+                assert!(height as f64 <= 1.44 * ((n + 2) as f64).log2());
+            }
+        }
+        assert!(avl.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn test_avl_duplicate_policies() {
+        let mut avl = BinarySearchTree::new();
+        assert_eq!(avl.insert_avl(1), InsertOutcome::Inserted);
+        assert_eq!(avl.insert_avl(1), InsertOutcome::Rejected(1));
+        assert_eq!(avl.insert(1), InsertOutcome::Rejected(1));
+
+        // 按第一个分量比较, 观察替换效果
+        let mut avl = BinarySearchTree::with_policy(DuplicatePolicy::Replace);
+        avl.insert_avl(std::cmp::Reverse(2));
+        avl.insert_avl(std::cmp::Reverse(1));
+        assert_eq!(
+            avl.insert_avl(std::cmp::Reverse(1)),
+            InsertOutcome::Replaced(std::cmp::Reverse(1))
+        );
+
+        // 多重集合, 大量重复值插入后仍然平衡
+        let mut avl = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        for x in 0..300 {
+            avl.insert_avl(x % 100);
+        }
+        assert_eq!(avl.insert_avl(42), InsertOutcome::Counted(4));
+        assert_eq!(avl.insert(43), InsertOutcome::Counted(4));
+        assert_eq!(avl.count(&42), 4);
+        assert_eq!(avl.count(&100), 0);
+        let (_, n) = check_avl(&avl.root);
+        assert_eq!(n, 100);
+
+        // 删除一次只去掉一个副本, 重数减到 1 之后再删除才摘掉节点
+        assert_eq!(avl.remove_one_avl(&42), Some(RemoveOutcome::Decremented(3)));
+        assert_eq!(avl.count(&42), 3);
+        for expected in [2, 1] {
+            assert_eq!(
+                avl.remove_one_avl(&42),
+                Some(RemoveOutcome::Decremented(expected))
+            );
+        }
+        assert_eq!(avl.remove_one_avl(&42), Some(RemoveOutcome::Removed(42)));
+        assert_eq!(avl.remove_one_avl(&42), None);
+        let (_, n) = check_avl(&avl.root);
+        assert_eq!(n, 99);
+
+        // remove_avl 去掉所有副本; 删除根时后继顶替, 重数跟着移动
+        let root = avl.root.as_ref().unwrap().value;
+        let successor = *avl.select(avl.rank(&root) + 1).unwrap();
+        avl.insert_avl(successor);
+        assert_eq!(avl.count(&root), 3);
+        assert_eq!(avl.remove_avl(&root), Some(root));
+        assert_eq!(avl.remove_avl(&root), None);
+        assert_eq!(avl.count(&root), 0);
+        assert_eq!(avl.count(&successor), 4);
+        check_avl(&avl.root);
+    }
+
+    #[test]
+    fn test_avl_rank_select() {
+        let avl: BinarySearchTree<i32> = [50, 20, 80, 10, 30, 70, 90].into_iter().collect();
+        assert_eq!(avl.rank(&10), 0);
+        assert_eq!(avl.rank(&50), 3);
+        assert_eq!(avl.rank(&55), 4); // 不在树中
+        assert_eq!(avl.rank(&100), 7);
+        assert_eq!(avl.select(0), Some(&10));
+        assert_eq!(avl.select(3), Some(&50));
+        assert_eq!(avl.select(6), Some(&90));
+        assert_eq!(avl.select(7), None);
+        assert_eq!(avl.count_range(&20, &70), 4);
+        assert_eq!(avl.count_range(&21, &69), 2);
+        assert_eq!(avl.count_range(&0, &100), 7);
+        assert_eq!(avl.count_range(&70, &20), 0);
+    }
+
+    #[test]
+    fn test_avl_rank_select_random() {
+        let mut seed: u64 = 38;
+        let mut next = || lcg(&mut seed) % 1000;
+        let mut avl = BinarySearchTree::new();
+        let mut model = std::collections::BTreeSet::new();
+        for round in 0..5000 {
+            let x = next();
+            if next() % 3 == 0 {
+                avl.remove_avl(&x);
+                model.remove(&x);
+            } else {
+                avl.insert_avl(x);
+                model.insert(x);
+            }
+            if round % 50 == 0 {
+                check_avl(&avl.root);
+                let sorted: Vec<u64> = model.iter().copied().collect();
+                for (k, v) in sorted.iter().enumerate() {
+                    assert_eq!(avl.select(k), Some(v));
+                    assert_eq!(avl.rank(v), k);
+                }
+                let (a, b) = (next(), next());
+                let expected = model.iter().filter(|&&v| a <= v && v <= b).count();
+                assert_eq!(avl.count_range(&a, &b), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_avl_map_basic() {
+        let mut map = AvlMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("c".to_string(), 3), None);
+        assert_eq!(map.insert("b".to_string(), 20), Some(2));
+        assert_eq!(map.len(), 3);
+
+        // 可以用 &str 查 String 键
+        assert_eq!(map.get("b"), Some(&20));
+        assert_eq!(map.get("d"), None);
+        *map.get_mut("a").unwrap() += 10;
+        assert_eq!(map.get("a"), Some(&11));
+
+        let pairs: Vec<(&str, i32)> = map.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(pairs, vec![("a", 11), ("b", 20), ("c", 3)]);
+
+        assert_eq!(map.remove("b"), Some(20));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.len(), 2);
+        assert!(map.keys().eq(["a", "c"].iter()));
+        assert!(map.values().eq([11, 3].iter()));
+    }
+
+    #[test]
+    fn test_avl_map_entry() {
+        // 单词计数
+        let mut counts: AvlMap<&str, usize> = AvlMap::new();
+        for word in "the cat and the dog and the bird".split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        let result: Vec<(&str, usize)> = counts.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(
+            result,
+            vec![("and", 2), ("bird", 1), ("cat", 1), ("dog", 1), ("the", 3)]
+        );
+
+        counts.entry("cat").and_modify(|v| *v += 10).or_default();
+        counts.entry("cow").and_modify(|v| *v += 10).or_default();
+        assert_eq!(counts.get("cat"), Some(&11));
+        assert_eq!(counts.get("cow"), Some(&0));
+
+        match counts.entry("dog") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &"dog");
+                assert_eq!(entry.get(), &1);
+                assert_eq!(entry.insert(5), 1);
+                assert_eq!(entry.remove(), 5);
+            }
+            Entry::Vacant(_) => panic!("dog should be present"),
+        }
+        match counts.entry("emu") {
+            Entry::Occupied(_) => panic!("emu should be absent"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &"emu");
+                assert_eq!(entry.into_key(), "emu");
+            }
+        }
+        assert_eq!(counts.entry("ant").key(), &"ant");
+        assert!(!counts.contains_key("dog"));
+        assert!(!counts.contains_key("emu"));
+        assert_eq!(counts.len(), 5);
+
+        // 插入时的旋转会移动节点, 返回的引用必须指向新插入的那个值
+        // 有序, 逆序和随机的键分别触发各种旋转
+        let mut seed = 3u64;
+        let shuffled: Vec<u32> = (0..200).map(|_| lcg(&mut seed) as u32 % 1000).collect();
+        for keys in [(0..200).collect(), (0..200).rev().collect(), shuffled] {
+            let mut map = AvlMap::new();
+            for &key in &keys {
+                *map.entry(key).or_insert(0) += key + 1;
+            }
+            for &key in &keys {
+                let times = keys.iter().filter(|&&k| k == key).count() as u32;
+                assert_eq!(map.get(&key), Some(&((key + 1) * times)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_avl_map_random() {
+        // 检查高度和平衡因子, 返回子树高度
+        fn check_map<K: Ord, V>(node: &Option<Box<MapNode<K, V>>>) -> usize {
+            match node {
+                None => 0,
+                Some(n) => {
+                    let lh = check_map(&n.left);
+                    let rh = check_map(&n.right);
+                    assert!(lh.abs_diff(rh) <= 1);
+                    assert_eq!(n.height, 1 + lh.max(rh));
+                    n.height
+                }
+            }
+        }
+
+        let mut seed: u64 = 7;
+        let mut next = || lcg(&mut seed);
+        let mut map = AvlMap::new();
+        let mut model = std::collections::BTreeMap::new();
+        for round in 0..20_000 {
+            let key = next() % 1000;
+            match next() % 4 {
+                0 => assert_eq!(map.remove(&key), model.remove(&key)),
+                1 => {
+                    *map.entry(key).or_insert(0) += round;
+                    *model.entry(key).or_insert(0) += round;
+                }
+                _ => assert_eq!(map.insert(key, round), model.insert(key, round)),
+            }
+            if round % 100 == 0 {
+                check_map(&map.root);
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        assert!(map.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let mut tree = IntervalTree::new();
+        assert!(tree.is_empty());
+        for (start, end) in [(15, 20), (10, 30), (17, 19), (5, 20), (12, 15), (30, 40)] {
+            assert!(tree.insert(Interval::new(start, end)));
+        }
+        assert!(!tree.insert(Interval::new(12, 15)));
+        assert_eq!(tree.len(), 6);
+
+        let found: Vec<(i32, i32)> = tree
+            .overlapping(Interval::new(14, 16))
+            .map(|i| (i.start, i.end))
+            .collect();
+        assert_eq!(found, vec![(5, 20), (10, 30), (12, 15), (15, 20)]);
+
+        let found: Vec<(i32, i32)> = tree.stabbing(&30).map(|i| (i.start, i.end)).collect();
+        assert_eq!(found, vec![(10, 30), (30, 40)]);
+        assert_eq!(tree.stabbing(&41).count(), 0);
+        assert_eq!(tree.stabbing(&4).count(), 0);
+
+        assert_eq!(
+            tree.remove(&Interval::new(10, 30)),
+            Some(Interval::new(10, 30))
+        );
+        assert_eq!(tree.remove(&Interval::new(10, 30)), None);
+        let found: Vec<(i32, i32)> = tree.stabbing(&30).map(|i| (i.start, i.end)).collect();
+        assert_eq!(found, vec![(30, 40)]);
+        assert_eq!(tree.len(), 5);
+    }
+
+    #[test]
+    fn test_interval_tree_random() {
+        // 检查 AVL 性质和最大右端点, 返回子树高度
+        fn check(node: &Option<Box<TreeNode<Interval<u64>, MaxEnd<u64>>>>) -> usize {
+            match node {
+                None => 0,
+                Some(n) => {
+                    let lh = check(&n.left);
+                    let rh = check(&n.right);
+                    assert!(lh.abs_diff(rh) <= 1);
+                    let max = [&n.left, &n.right]
+                        .into_iter()
+                        .flatten()
+                        .map(|c| c.aug.0)
+                        .fold(n.value.end, u64::max);
+                    assert_eq!(n.aug.0, max, "最大右端点记录错误");
+                    1 + lh.max(rh)
+                }
+            }
+        }
+
+        let mut seed: u64 = 39;
+        let mut next = || lcg(&mut seed);
+        let mut tree = IntervalTree::new();
+        let mut model = std::collections::BTreeSet::new();
+        for round in 0..5000 {
+            let start = next() % 1000;
+            let interval = Interval::new(start, start + next() % 50);
+            if next() % 3 == 0 {
+                assert_eq!(tree.remove(&interval), model.take(&interval));
+            } else {
+                assert_eq!(tree.insert(interval.clone()), model.insert(interval));
+            }
+            if round % 50 == 0 {
+                check(&tree.root);
+                let a = next() % 1050;
+                let query = Interval::new(a, a + next() % 20);
+                let expected: Vec<&Interval<u64>> =
+                    model.iter().filter(|i| i.overlaps(&query)).collect();
+                assert!(tree.overlapping(query).eq(expected));
+                let point = next() % 1050;
+                let expected = model.iter().filter(|i| i.start <= point && point <= i.end);
+                assert!(tree.stabbing(&point).eq(expected));
+            }
+        }
+        assert_eq!(tree.len(), model.len());
+    }
+
+    #[test]
+    fn test_avl_to_ascii_and_dot() {
+        let avl: BinarySearchTree<i32> = (1..=3).collect();
+        let expected = "    /-- 3 (h=1, bf=0)\n2 (h=2, bf=0)\n    \\-- 1 (h=1, bf=0)\n";
+        assert_eq!(avl.to_ascii(), expected);
+
+        let avl: BinarySearchTree<i32> = (1..=2).collect();
+        let expected = concat!(
+            "digraph {\n",
+            "    n0 [label=\"1\\nh=2 bf=-1\"];\n",
+            "    n1 [shape=point];\n",
+            "    n0 -> n1;\n",
+            "    n2 [label=\"2\\nh=1 bf=0\"];\n",
+            "    n0 -> n2;\n",
+            "}\n",
+        );
+        assert_eq!(avl.to_dot(), expected);
+    }
+
+    #[test]
+    fn test_avl_join_split() {
+        let left: BinarySearchTree<i32> = (0..100).collect();
+        let right: BinarySearchTree<i32> = (101..110).collect();
+        let tree = BinarySearchTree::join(left, 100, right);
+        let (_, n) = check_avl(&tree.root);
+        assert_eq!(n, 110);
+        assert!(tree.iter().copied().eq(0..110));
+
+        let (less, found, greater) = tree.split(&42);
+        assert_eq!(found, Some(42));
+        check_avl(&less.root);
+        check_avl(&greater.root);
+        assert!(less.iter().copied().eq(0..42));
+        assert!(greater.iter().copied().eq(43..110));
+
+        // 不存在的值也能切
+        let (less, found, greater) = greater.split(&200);
+        assert_eq!(found, None);
+        assert!(less.iter().copied().eq(43..110));
+        assert!(greater.root.is_none());
+
+        // 两边高度差很大时拼接后仍然平衡
+        let tall: BinarySearchTree<i32> = (10..5000).collect();
+        let tree = BinarySearchTree::join(BinarySearchTree::new(), 5, tall);
+        let (_, n) = check_avl(&tree.root);
+        assert_eq!(n, 4991);
+    }
+
+    #[test]
+    fn test_avl_set_operations() {
+        use std::collections::BTreeSet;
+
+        let mut seed: u64 = 42;
+        let mut next = || lcg(&mut seed);
+        // 大小悬殊和大小相近的组合都试一下
+        for (na, nb) in [(0, 50), (3, 2000), (500, 500), (2000, 7), (1000, 0)] {
+            let a: BTreeSet<u64> = (0..na).map(|_| next() % 3000).collect();
+            let b: BTreeSet<u64> = (0..nb).map(|_| next() % 3000).collect();
+            let tree =
+                |set: &BTreeSet<u64>| -> BinarySearchTree<u64> { set.iter().copied().collect() };
+
+            let result = tree(&a).union(tree(&b));
+            check_avl(&result.root);
+            assert!(result.iter().eq(a.union(&b)));
+
+            let result = tree(&a).intersection(tree(&b));
+            check_avl(&result.root);
+            assert!(result.iter().eq(a.intersection(&b)));
+
+            let result = tree(&a).difference(tree(&b));
+            check_avl(&result.root);
+            assert!(result.iter().eq(a.difference(&b)));
+
+            let result = tree(&a).symmetric_difference(tree(&b));
+            check_avl(&result.root);
+            assert!(result.iter().eq(a.symmetric_difference(&b)));
+        }
+    }
+
+    // 按前序输出结构, 空孩子记为 #, 用来比较两棵树的形状和节点上记录的信息
+    fn shape<T: Ord + Display>(node: &Option<Box<TreeNode<T>>>) -> String {
+        match node {
+            None => "#".to_string(),
+            Some(n) => format!(
+                "({}:{}:{}x{} {} {})",
+                n.value,
+                n.height,
+                n.size,
+                n.count,
+                shape(&n.left),
+                shape(&n.right)
+            ),
+        }
+    }
+
+    #[test]
+    fn test_avl_serialize_round_trip() {
+        let avl: BinarySearchTree<i32> = (1..=7).collect();
+        assert_eq!(avl.to_preorder_string(), "4:3 2:2 1:1 3:1 6:2 5:1 7:1");
+
+        let mut avl = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        let mut seed: u64 = 17;
+        for _ in 0..500 {
+            avl.insert_avl(lcg(&mut seed) as i32 % 300);
+        }
+        let text = avl.to_preorder_string();
+        let from_text = BinarySearchTree::<i32>::from_preorder_str(&text).unwrap();
+        check_avl(&from_text.root);
+        assert_eq!(shape(&from_text.root), shape(&avl.root));
+
+        let bytes = avl.to_bytes();
+        let from_bytes = BinarySearchTree::<i32>::from_bytes(&bytes).unwrap();
+        check_avl(&from_bytes.root);
+        assert_eq!(shape(&from_bytes.root), shape(&avl.root));
+        assert_eq!(from_bytes.to_bytes(), bytes);
+
+        let words: BinarySearchTree<String> = ["kiwi", "fig", "lime", "date", "pear"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let decoded = BinarySearchTree::<String>::from_bytes(&words.to_bytes()).unwrap();
+        assert_eq!(shape(&decoded.root), shape(&words.root));
+
+        // 值中的空白、'*' 和反斜杠都要转义
+        let mut odd = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        for word in ["a b", "a*2", "a*2", "x\\y:1", "\t", ""] {
+            odd.insert_avl(word.to_string());
+        }
+        let text = odd.to_preorder_string();
+        assert_eq!(text, r"a\u{2a}2:3*2 \u{9}:2 \e:1 a\u{20}b:1 x\\y:1:1");
+        let from_text = BinarySearchTree::<String>::from_preorder_str(&text).unwrap();
+        assert_eq!(shape(&from_text.root), shape(&odd.root));
+        assert_eq!(from_text.to_preorder_string(), text);
+
+        // 用普通的 insert 建出 200 层的链: 高度放不进一个字节, 也不满足平衡条件, 仍然原样恢复
+        let mut chain = BinarySearchTree::new();
+        for x in 0..200 {
+            chain.insert(x);
+        }
+        assert_eq!(chain.root.as_ref().unwrap().height, 200);
+        let from_text = BinarySearchTree::<i32>::from_preorder_str(&chain.to_preorder_string());
+        assert_eq!(shape(&from_text.unwrap().root), shape(&chain.root));
+        let bytes = chain.to_bytes();
+        let from_bytes = BinarySearchTree::<i32>::from_bytes(&bytes).unwrap();
+        assert_eq!(shape(&from_bytes.root), shape(&chain.root));
+        assert_eq!(from_bytes.to_bytes(), bytes);
+
+        let empty = BinarySearchTree::<i32>::from_bytes(&[]).unwrap();
+        assert!(empty.root.is_none());
+    }
+
+    #[test]
+    fn test_avl_decode_errors() {
+        let parse = BinarySearchTree::<i32>::from_preorder_str;
+        assert_eq!(
+            parse("2:2 1:1 3:1").unwrap().to_preorder_string(),
+            "2:2 1:1 3:1"
+        );
+        assert_eq!(
+            parse("2:2 1:1 3").unwrap_err(),
+            DecodeError::InvalidToken("3".to_string())
+        );
+        assert_eq!(
+            parse("2:2 1:x").unwrap_err(),
+            DecodeError::InvalidToken("1:x".to_string())
+        );
+        assert_eq!(parse("2:2 3:1 1:1").unwrap_err(), DecodeError::OutOfOrder);
+        assert_eq!(
+            parse("2:3 1:1 3:1").unwrap_err(),
+            DecodeError::HeightMismatch
+        );
+        // 不平衡的链只要高度如实记录就能恢复
+        assert_eq!(
+            parse("1:3 2:2 3:1").unwrap().to_preorder_string(),
+            "1:3 2:2 3:1"
+        );
+        assert_eq!(parse("2:2 1:1*0").unwrap_err(), DecodeError::ZeroCount);
+        assert_eq!(
+            parse("2:2 1:1*x").unwrap_err(),
+            DecodeError::InvalidToken("1:1*x".to_string())
+        );
+
+        let bytes = parse("2:2 1:1 3:1").unwrap().to_bytes();
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&bytes[..bytes.len() - 2]).unwrap_err(),
+            DecodeError::UnexpectedEnd
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[0, 1, 0, 0, 0]).unwrap_err(),
+            DecodeError::InvalidTag(0)
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[1 | COUNT_FLAG, 1, 1, 0, 0, 0, 0]).unwrap_err(),
+            DecodeError::ZeroCount
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[1, 0, 1, 0, 0, 0]).unwrap_err(),
+            DecodeError::HeightMismatch
+        );
     }
 }
@@ -0,0 +1,1399 @@
+// 几种平衡策略的对比: treap, 伸展树和替罪羊树, 以及作为参照的 AVL 和左倾红黑树的平衡方式
+// 五种树都在本文件中实现, 共用同一种精简的节点和同一套接口, 最后在随机, 顺序和 Zipf 负载上比较性能
+// 节点上只有平衡所需的信息, 都只拒绝重复值, 所以比较的是平衡方式本身;
+// _04avl_tree 和 _04black_red_tree 中的完整实现 (重复值策略, 子树大小, 附加信息) 不在比较之内
+
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct TreeNode<T, M>
+where
+    T: Ord,
+{
+    value: T,
+    left: Link<T, M>,
+    right: Link<T, M>,
+    meta: M, // 每种树各自需要的信息: 优先级, 子树大小, 高度或者颜色
+}
+
+type Link<T, M> = Option<Box<TreeNode<T, M>>>;
+
+impl<T, M> TreeNode<T, M>
+where
+    T: Ord,
+{
+    fn new(value: T, meta: M) -> Self {
+        TreeNode {
+            value,
+            left: None,
+            right: None,
+            meta,
+        }
+    }
+
+    // 只调整结构的旋转, 附带的信息由各自的树在旋转后修正
+    fn rotate_left(mut self: Box<Self>) -> Box<Self> {
+        let mut new_root = self.right.take().unwrap();
+        self.right = new_root.left.take();
+        new_root.left = Some(self);
+        new_root
+    }
+
+    fn rotate_right(mut self: Box<Self>) -> Box<Self> {
+        let mut new_root = self.left.take().unwrap();
+        self.left = new_root.right.take();
+        new_root.right = Some(self);
+        new_root
+    }
+
+    // 不调整结构的查找, 除了伸展树都用它
+    fn search(mut node: &Link<T, M>, value: &T) -> bool {
+        while let Some(n) = node {
+            node = match value.cmp(&n.value) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    // 用栈把节点逐个拆开再释放, 派生的析构会沿着 Box 递归
+    fn dismantle(root: Link<T, M>) {
+        let mut stack: Vec<Box<Self>> = root.into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+/// insert 的结果, 和 _04black_red_tree 中的 InsertOutcome 相同
+/// 这里的树都拒绝重复值, 没有 DuplicatePolicy, 所以只有这两种
+#[derive(Debug, PartialEq)]
+enum InsertOutcome<T> {
+    Inserted,    // 插入了新节点
+    Rejected(T), // 值已存在, 原样退回
+}
+
+/// 五种树共同的接口, 基准测试和对拍都只通过它操作树
+trait SearchTree<T>
+where
+    T: Ord,
+{
+    type Meta;
+    const NAME: &'static str;
+
+    fn new() -> Self;
+
+    fn len(&self) -> usize;
+
+    fn root(&self) -> &Link<T, Self::Meta>;
+
+    /// 插入一个值, 值已经存在时原样退回
+    fn insert(&mut self, value: T) -> InsertOutcome<T>;
+
+    /// 伸展树查找时也会调整结构, 所以这里统一用 &mut self
+    fn search(&mut self, value: &T) -> bool;
+
+    /// 删除一个值, 返回被删除的值, 不存在时返回 None
+    fn remove(&mut self, value: &T) -> Option<T>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 中序遍历, 从小到大
+    fn iter(&self) -> InOrder<'_, T, Self::Meta> {
+        let mut iter = InOrder { stack: Vec::new() };
+        iter.push_left(self.root().as_deref());
+        iter
+    }
+
+    /// 树高, 空树为 0; 伸展树可能很深, 所以用显式的栈
+    fn height(&self) -> usize {
+        let mut height = 0;
+        let mut stack: Vec<(&TreeNode<T, Self::Meta>, usize)> =
+            self.root().iter().map(|n| (&**n, 1)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            height = height.max(depth);
+            for child in [&node.left, &node.right].into_iter().flatten() {
+                stack.push((child, depth + 1));
+            }
+        }
+        height
+    }
+}
+
+/// 中序遍历的迭代器, 和 _04binary_search_tree 中的一样用显式的栈
+struct InOrder<'a, T, M>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T, M>>,
+}
+
+impl<'a, T, M> InOrder<'a, T, M>
+where
+    T: Ord,
+{
+    // 从 node 开始一路向左, 沿途的节点都压栈
+    fn push_left(&mut self, mut node: Option<&'a TreeNode<T, M>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, T, M> Iterator for InOrder<'a, T, M>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+// 简单的线性同余生成器, 用来产生 treap 的优先级和可复现的测试数据
+fn lcg(seed: &mut u64) -> u64 {
+    *seed = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    *seed >> 11
+}
+
+// Treap
+// 每个节点带一个随机优先级, 按值是搜索树, 按优先级是大根堆
+// 形状和按优先级顺序插入的普通搜索树相同, 所以期望高度是 O(log n)
+
+#[derive(Debug)]
+struct Priority(u64);
+
+#[derive(Debug)]
+struct Treap<T>
+where
+    T: Ord,
+{
+    root: Link<T, Priority>,
+    len: usize,
+    seed: u64, // 优先级的随机数种子
+}
+
+impl<T> SearchTree<T> for Treap<T>
+where
+    T: Ord,
+{
+    type Meta = Priority;
+    const NAME: &'static str = "treap";
+
+    fn new() -> Self {
+        Treap {
+            root: None,
+            len: 0,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn root(&self) -> &Link<T, Priority> {
+        &self.root
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        let priority = lcg(&mut self.seed);
+        let (root, outcome) = TreeNode::insert_treap(self.root.take(), value, priority);
+        self.root = Some(root);
+        if outcome == InsertOutcome::Inserted {
+            self.len += 1;
+        }
+        outcome
+    }
+
+    fn search(&mut self, value: &T) -> bool {
+        TreeNode::search(&self.root, value)
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = TreeNode::remove_treap(&mut self.root, value);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+}
+
+impl<T> TreeNode<T, Priority>
+where
+    T: Ord,
+{
+    /// 先按搜索树插入, 回溯时孩子的优先级更高就把它转上来
+    fn insert_treap(
+        node: Link<T, Priority>,
+        value: T,
+        priority: u64,
+    ) -> (Box<Self>, InsertOutcome<T>) {
+        let Some(mut node) = node else {
+            let node = TreeNode::new(value, Priority(priority));
+            return (Box::new(node), InsertOutcome::Inserted);
+        };
+        let outcome = match value.cmp(&node.value) {
+            Ordering::Less => {
+                let (left, outcome) = Self::insert_treap(node.left.take(), value, priority);
+                let rotate = left.meta.0 > node.meta.0;
+                node.left = Some(left);
+                if rotate {
+                    node = node.rotate_right();
+                }
+                outcome
+            }
+            Ordering::Greater => {
+                let (right, outcome) = Self::insert_treap(node.right.take(), value, priority);
+                let rotate = right.meta.0 > node.meta.0;
+                node.right = Some(right);
+                if rotate {
+                    node = node.rotate_left();
+                }
+                outcome
+            }
+            Ordering::Equal => InsertOutcome::Rejected(value),
+        };
+        (node, outcome)
+    }
+
+    /// 找到目标节点后, 用它的两棵子树合并的结果顶替它
+    fn remove_treap(mut node: &mut Link<T, Priority>, value: &T) -> Option<T> {
+        loop {
+            match value.cmp(&node.as_ref()?.value) {
+                Ordering::Less => node = &mut node.as_mut().unwrap().left,
+                Ordering::Greater => node = &mut node.as_mut().unwrap().right,
+                Ordering::Equal => break,
+            }
+        }
+        let mut removed = node.take().unwrap();
+        *node = Self::merge(removed.left.take(), removed.right.take());
+        Some(removed.value)
+    }
+
+    /// 合并两棵 treap, 调用方保证 left 中的值都小于 right 中的值
+    /// 优先级高的根留在上面, 另一棵并进它靠近中间的那棵子树
+    fn merge(left: Link<T, Priority>, right: Link<T, Priority>) -> Link<T, Priority> {
+        match (left, right) {
+            (None, node) | (node, None) => node,
+            (Some(mut l), Some(mut r)) => {
+                if l.meta.0 > r.meta.0 {
+                    l.right = Self::merge(l.right.take(), Some(r));
+                    Some(l)
+                } else {
+                    r.left = Self::merge(Some(l), r.left.take());
+                    Some(r)
+                }
+            }
+        }
+    }
+}
+
+// 伸展树
+// 每次访问都把目标节点转到根, 不维护任何平衡信息
+// 单次操作最坏 O(n), 但均摊 O(log n), 并且最近访问过的值离根很近, 适合访问集中的负载
+
+#[derive(Debug)]
+struct SplayTree<T>
+where
+    T: Ord,
+{
+    root: Link<T, ()>,
+    len: usize,
+}
+
+impl<T> SearchTree<T> for SplayTree<T>
+where
+    T: Ord,
+{
+    type Meta = ();
+    const NAME: &'static str = "splay";
+
+    fn new() -> Self {
+        SplayTree { root: None, len: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn root(&self) -> &Link<T, ()> {
+        &self.root
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        let Some(root) = self.root.take() else {
+            self.root = Some(Box::new(TreeNode::new(value, ())));
+            self.len += 1;
+            return InsertOutcome::Inserted;
+        };
+        // 伸展之后根是 value 的前驱或后继, 新节点直接作为新根把它分到一边
+        let mut root = TreeNode::splay(root, &value);
+        let mut node = Box::new(TreeNode::new(value, ()));
+        match node.value.cmp(&root.value) {
+            Ordering::Less => {
+                node.left = root.left.take();
+                node.right = Some(root);
+            }
+            Ordering::Greater => {
+                node.right = root.right.take();
+                node.left = Some(root);
+            }
+            Ordering::Equal => {
+                self.root = Some(root);
+                return InsertOutcome::Rejected(node.value);
+            }
+        }
+        self.root = Some(node);
+        self.len += 1;
+        InsertOutcome::Inserted
+    }
+
+    fn search(&mut self, value: &T) -> bool {
+        let Some(root) = self.root.take() else {
+            return false;
+        };
+        let root = self.root.insert(TreeNode::splay(root, value));
+        root.value == *value
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        if !self.search(value) {
+            return None;
+        }
+        // 目标已经在根上, 把左子树的最大值转上来, 它没有右孩子, 正好接上原来的右子树
+        let mut root = self.root.take().unwrap();
+        self.root = match root.left.take() {
+            None => root.right.take(),
+            Some(left) => {
+                let mut left = TreeNode::splay(left, value);
+                left.right = root.right.take();
+                Some(left)
+            }
+        };
+        self.len -= 1;
+        Some(root.value)
+    }
+}
+
+impl<T> TreeNode<T, ()>
+where
+    T: Ord,
+{
+    /// 自顶向下伸展: 把 value 所在的节点转到根, 找不到时转上来的是最后访问的节点
+    /// 下行时比 value 小的部分挂到左树, 大的挂到右树, 最后再拼回来, 整个过程不需要递归
+    fn splay(mut root: Box<Self>, value: &T) -> Box<Self> {
+        // left 中的节点依次是上一个的右孩子, right 中的依次是上一个的左孩子
+        let mut left: Vec<Box<Self>> = Vec::new();
+        let mut right: Vec<Box<Self>> = Vec::new();
+        loop {
+            match value.cmp(&root.value) {
+                Ordering::Less => {
+                    let Some(mut child) = root.left.take() else {
+                        break;
+                    };
+                    if *value < child.value {
+                        // zig-zig: 先右旋, 再往下走
+                        root.left = child.right.take();
+                        child.right = Some(root);
+                        root = child;
+                        let Some(next) = root.left.take() else {
+                            break;
+                        };
+                        right.push(root);
+                        root = next;
+                    } else {
+                        right.push(root);
+                        root = child;
+                    }
+                }
+                Ordering::Greater => {
+                    let Some(mut child) = root.right.take() else {
+                        break;
+                    };
+                    if *value > child.value {
+                        root.right = child.left.take();
+                        child.left = Some(root);
+                        root = child;
+                        let Some(next) = root.right.take() else {
+                            break;
+                        };
+                        left.push(root);
+                        root = next;
+                    } else {
+                        left.push(root);
+                        root = child;
+                    }
+                }
+                Ordering::Equal => break,
+            }
+        }
+        // 从下往上拼: 根原来的左右子树分别接在左树的最右端和右树的最左端
+        let mut subtree = root.left.take();
+        for mut node in left.into_iter().rev() {
+            node.right = subtree;
+            subtree = Some(node);
+        }
+        root.left = subtree;
+        let mut subtree = root.right.take();
+        for mut node in right.into_iter().rev() {
+            node.left = subtree;
+            subtree = Some(node);
+        }
+        root.right = subtree;
+        root
+    }
+}
+
+// 顺序访问会让伸展树变成很长的链, 派生的析构会递归到栈溢出
+impl<T> Drop for SplayTree<T>
+where
+    T: Ord,
+{
+    fn drop(&mut self) {
+        TreeNode::dismantle(self.root.take());
+    }
+}
+
+// 替罪羊树
+// 不做旋转, 插入的节点太深时往上找到第一个失衡的祖先 (替罪羊), 把它的子树整个重建成完全平衡的
+// 删除过多时重建整棵树; 节点上只需要记录子树大小
+
+#[derive(Debug)]
+struct Size(usize);
+
+#[derive(Debug)]
+struct ScapegoatTree<T>
+where
+    T: Ord,
+{
+    root: Link<T, Size>,
+    len: usize,
+    max_len: usize, // 上次整体重建以来 len 的最大值
+}
+
+/// 插入的结果: 被拒绝, 已完成, 或者插得太深, 还需要在祖先中找替罪羊
+enum Inserted<T> {
+    Rejected(T),
+    Done,
+    TooDeep,
+}
+
+// 平衡因子 α = 2/3: 任何孩子的大小都不能超过自己的 2/3, 树高不超过 log_{3/2} n
+fn depth_limit(len: usize) -> usize {
+    ((len as f64).ln() / 1.5f64.ln()).floor() as usize
+}
+
+impl<T> SearchTree<T> for ScapegoatTree<T>
+where
+    T: Ord,
+{
+    type Meta = Size;
+    const NAME: &'static str = "scapegoat";
+
+    fn new() -> Self {
+        ScapegoatTree {
+            root: None,
+            len: 0,
+            max_len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn root(&self) -> &Link<T, Size> {
+        &self.root
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        let limit = depth_limit(self.len + 1);
+        if let Inserted::Rejected(value) =
+            TreeNode::insert_scapegoat(&mut self.root, value, 0, limit)
+        {
+            return InsertOutcome::Rejected(value);
+        }
+        self.len += 1;
+        self.max_len = self.max_len.max(self.len);
+        InsertOutcome::Inserted
+    }
+
+    fn search(&mut self, value: &T) -> bool {
+        TreeNode::search(&self.root, value)
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = TreeNode::remove_scapegoat(&mut self.root, value)?;
+        self.len -= 1;
+        if self.len * 3 < self.max_len * 2 {
+            TreeNode::rebuild(&mut self.root);
+            self.max_len = self.len;
+        }
+        Some(removed)
+    }
+}
+
+impl<T> TreeNode<T, Size>
+where
+    T: Ord,
+{
+    fn size(node: &Link<T, Size>) -> usize {
+        node.as_ref().map_or(0, |n| n.meta.0)
+    }
+
+    /// 某个孩子超过了自己大小的 2/3
+    fn is_unbalanced(&self) -> bool {
+        Self::size(&self.left).max(Self::size(&self.right)) * 3 > self.meta.0 * 2
+    }
+
+    /// 按搜索树插入, depth 是 node 的深度, 新节点的深度超过 limit 时回溯寻找替罪羊
+    fn insert_scapegoat(
+        node: &mut Link<T, Size>,
+        value: T,
+        depth: usize,
+        limit: usize,
+    ) -> Inserted<T> {
+        let current = match node {
+            Some(current) => current,
+            None => {
+                *node = Some(Box::new(TreeNode::new(value, Size(1))));
+                return if depth > limit {
+                    Inserted::TooDeep
+                } else {
+                    Inserted::Done
+                };
+            }
+        };
+        let inserted = match value.cmp(&current.value) {
+            Ordering::Less => Self::insert_scapegoat(&mut current.left, value, depth + 1, limit),
+            Ordering::Greater => {
+                Self::insert_scapegoat(&mut current.right, value, depth + 1, limit)
+            }
+            Ordering::Equal => return Inserted::Rejected(value),
+        };
+        if let Inserted::Rejected(_) = inserted {
+            return inserted;
+        }
+        current.meta.0 += 1;
+        if let Inserted::TooDeep = inserted
+            && current.is_unbalanced()
+        {
+            Self::rebuild(node);
+            return Inserted::Done;
+        }
+        inserted
+    }
+
+    /// 删除 value, 沿途的子树大小减一; 不会让树变高, 所以不需要局部重建
+    fn remove_scapegoat(node: &mut Link<T, Size>, value: &T) -> Option<T> {
+        let current = node.as_mut()?;
+        let removed = match value.cmp(&current.value) {
+            Ordering::Less => Self::remove_scapegoat(&mut current.left, value),
+            Ordering::Greater => Self::remove_scapegoat(&mut current.right, value),
+            // 两个孩子: 用后继的值替换自己
+            Ordering::Equal if current.left.is_some() && current.right.is_some() => {
+                let successor = Self::take_min(&mut current.right);
+                Some(std::mem::replace(&mut current.value, successor.value))
+            }
+            // 至多一个孩子: 让孩子顶替自己
+            Ordering::Equal => {
+                let mut removed = node.take().unwrap();
+                *node = removed.left.take().or(removed.right.take());
+                return Some(removed.value);
+            }
+        };
+        if removed.is_some() {
+            current.meta.0 -= 1;
+        }
+        removed
+    }
+
+    /// 从非空子树中摘掉最小的节点, 沿途的子树大小减一
+    fn take_min(mut node: &mut Link<T, Size>) -> Box<Self> {
+        while node.as_ref().unwrap().left.is_some() {
+            let current = node.as_mut().unwrap();
+            current.meta.0 -= 1;
+            node = &mut current.left;
+        }
+        let mut min = node.take().unwrap();
+        *node = min.right.take();
+        min
+    }
+
+    /// 把子树重建成完全平衡的: 先按中序拆成节点序列, 再从中间开始重新挂起来
+    fn rebuild(node: &mut Link<T, Size>) {
+        let mut nodes = Vec::with_capacity(Self::size(node));
+        let mut stack = Vec::new();
+        let mut current = node.take();
+        loop {
+            while let Some(mut n) = current {
+                current = n.left.take();
+                stack.push(n);
+            }
+            let Some(mut n) = stack.pop() else {
+                break;
+            };
+            current = n.right.take();
+            nodes.push(n);
+        }
+        let len = nodes.len();
+        *node = Self::build(len, &mut nodes.into_iter());
+    }
+
+    /// 用 nodes 中接下来的 len 个节点搭一棵完全平衡的子树
+    fn build(len: usize, nodes: &mut impl Iterator<Item = Box<Self>>) -> Link<T, Size> {
+        if len == 0 {
+            return None;
+        }
+        let left = Self::build(len / 2, nodes);
+        let mut node = nodes.next().unwrap();
+        node.left = left;
+        node.right = Self::build(len - len / 2 - 1, nodes);
+        node.meta = Size(len);
+        Some(node)
+    }
+}
+
+// 参照: AVL 树的平衡方式
+// 插入删除的算法和 _04avl_tree 相同, 但节点上只有高度, 没有重复值策略, 子树大小和附加信息
+// 所以计时只反映 AVL 的平衡方式本身, 不代表那边完整实现的开销
+
+#[derive(Debug)]
+struct Height(usize);
+
+#[derive(Debug)]
+struct AvlTree<T>
+where
+    T: Ord,
+{
+    root: Link<T, Height>,
+    len: usize,
+}
+
+impl<T> SearchTree<T> for AvlTree<T>
+where
+    T: Ord,
+{
+    type Meta = Height;
+    const NAME: &'static str = "avl";
+
+    fn new() -> Self {
+        AvlTree { root: None, len: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn root(&self) -> &Link<T, Height> {
+        &self.root
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        let (root, outcome) = TreeNode::insert_avl(self.root.take(), value);
+        self.root = Some(root);
+        if outcome == InsertOutcome::Inserted {
+            self.len += 1;
+        }
+        outcome
+    }
+
+    fn search(&mut self, value: &T) -> bool {
+        TreeNode::search(&self.root, value)
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        let (root, removed) = TreeNode::remove_avl(self.root.take()?, value);
+        self.root = root;
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+}
+
+impl<T> TreeNode<T, Height>
+where
+    T: Ord,
+{
+    fn insert_avl(node: Link<T, Height>, value: T) -> (Box<Self>, InsertOutcome<T>) {
+        let Some(mut node) = node else {
+            let node = TreeNode::new(value, Height(1));
+            return (Box::new(node), InsertOutcome::Inserted);
+        };
+        let outcome = match value.cmp(&node.value) {
+            Ordering::Less => {
+                let (left, outcome) = Self::insert_avl(node.left.take(), value);
+                node.left = Some(left);
+                outcome
+            }
+            Ordering::Greater => {
+                let (right, outcome) = Self::insert_avl(node.right.take(), value);
+                node.right = Some(right);
+                outcome
+            }
+            Ordering::Equal => return (node, InsertOutcome::Rejected(value)),
+        };
+        node.update_height();
+        (node.balance(), outcome)
+    }
+
+    fn remove_avl(mut self: Box<Self>, value: &T) -> (Link<T, Height>, Option<T>) {
+        let removed = match value.cmp(&self.value) {
+            Ordering::Less => match self.left.take() {
+                Some(left) => {
+                    let (left, removed) = left.remove_avl(value);
+                    self.left = left;
+                    removed
+                }
+                None => None,
+            },
+            Ordering::Greater => match self.right.take() {
+                Some(right) => {
+                    let (right, removed) = right.remove_avl(value);
+                    self.right = right;
+                    removed
+                }
+                None => None,
+            },
+            Ordering::Equal => match (self.left.take(), self.right.take()) {
+                (None, None) => return (None, Some(self.value)),
+                (Some(child), None) | (None, Some(child)) => {
+                    return (Some(child), Some(self.value));
+                }
+                (Some(left), Some(right)) => {
+                    let (successor, right) = right.remove_min_avl();
+                    self.left = Some(left);
+                    self.right = right;
+                    Some(std::mem::replace(&mut self.value, successor.value))
+                }
+            },
+        };
+        if removed.is_none() {
+            return (Some(self), None);
+        }
+        self.update_height();
+        (Some(self.balance()), removed)
+    }
+
+    fn remove_min_avl(mut self: Box<Self>) -> (Box<Self>, Link<T, Height>) {
+        match self.left.take() {
+            Some(left) => {
+                let (min, left) = left.remove_min_avl();
+                self.left = left;
+                self.update_height();
+                (min, Some(self.balance()))
+            }
+            None => {
+                let right = self.right.take();
+                (self, right)
+            }
+        }
+    }
+
+    fn height(node: &Link<T, Height>) -> usize {
+        node.as_ref().map_or(0, |n| n.meta.0)
+    }
+
+    fn update_height(&mut self) {
+        self.meta.0 = 1 + Self::height(&self.left).max(Self::height(&self.right));
+    }
+
+    fn balance_factor(&self) -> i32 {
+        Self::height(&self.left) as i32 - Self::height(&self.right) as i32
+    }
+
+    fn balance(mut self: Box<Self>) -> Box<Self> {
+        let bf = self.balance_factor();
+        if bf > 1 {
+            if self.left.as_ref().unwrap().balance_factor() < 0 {
+                self.left = Some(self.left.take().unwrap().rotate_left_avl());
+            }
+            return self.rotate_right_avl();
+        }
+        if bf < -1 {
+            if self.right.as_ref().unwrap().balance_factor() > 0 {
+                self.right = Some(self.right.take().unwrap().rotate_right_avl());
+            }
+            return self.rotate_left_avl();
+        }
+        self
+    }
+
+    // 旋转后先更新下沉的旧根, 再更新新根
+    fn rotate_left_avl(self: Box<Self>) -> Box<Self> {
+        let mut root = self.rotate_left();
+        root.left.as_mut().unwrap().update_height();
+        root.update_height();
+        root
+    }
+
+    fn rotate_right_avl(self: Box<Self>) -> Box<Self> {
+        let mut root = self.rotate_right();
+        root.right.as_mut().unwrap().update_height();
+        root.update_height();
+        root
+    }
+}
+
+// 参照: 左倾红黑树的平衡方式
+// 插入删除的算法和 _04black_red_tree 相同, 但节点上只有颜色, 没有重复值策略和子树大小
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Color {
+    Red,
+    Black,
+}
+
+impl Color {
+    fn flip(&mut self) {
+        *self = match self {
+            Color::Red => Color::Black,
+            Color::Black => Color::Red,
+        };
+    }
+}
+
+#[derive(Debug)]
+struct RbTree<T>
+where
+    T: Ord,
+{
+    root: Link<T, Color>,
+    len: usize,
+}
+
+impl<T> SearchTree<T> for RbTree<T>
+where
+    T: Ord,
+{
+    type Meta = Color;
+    const NAME: &'static str = "llrb";
+
+    fn new() -> Self {
+        RbTree { root: None, len: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn root(&self) -> &Link<T, Color> {
+        &self.root
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        let (mut root, outcome) = TreeNode::insert_rb(self.root.take(), value);
+        root.meta = Color::Black;
+        self.root = Some(root);
+        if outcome == InsertOutcome::Inserted {
+            self.len += 1;
+        }
+        outcome
+    }
+
+    fn search(&mut self, value: &T) -> bool {
+        TreeNode::search(&self.root, value)
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        // 删除时一路向下调整颜色, 所以要先确认值存在
+        if !TreeNode::search(&self.root, value) {
+            return None;
+        }
+        let mut root = self.root.take().unwrap();
+        // 两个孩子都是黑色时先把根染红, 下行时才有红链接可借
+        if !TreeNode::is_red(&root.left) && !TreeNode::is_red(&root.right) {
+            root.meta = Color::Red;
+        }
+        let (root, removed) = root.delete_rb(value);
+        self.root = root;
+        if let Some(root) = self.root.as_mut() {
+            root.meta = Color::Black;
+        }
+        self.len -= 1;
+        Some(removed)
+    }
+}
+
+impl<T> TreeNode<T, Color>
+where
+    T: Ord,
+{
+    fn is_red(node: &Link<T, Color>) -> bool {
+        node.as_ref().is_some_and(|n| n.meta == Color::Red)
+    }
+
+    fn insert_rb(node: Link<T, Color>, value: T) -> (Box<Self>, InsertOutcome<T>) {
+        let Some(mut node) = node else {
+            let node = TreeNode::new(value, Color::Red);
+            return (Box::new(node), InsertOutcome::Inserted);
+        };
+        let outcome = match value.cmp(&node.value) {
+            Ordering::Less => {
+                let (left, outcome) = Self::insert_rb(node.left.take(), value);
+                node.left = Some(left);
+                outcome
+            }
+            Ordering::Greater => {
+                let (right, outcome) = Self::insert_rb(node.right.take(), value);
+                node.right = Some(right);
+                outcome
+            }
+            Ordering::Equal => return (node, InsertOutcome::Rejected(value)),
+        };
+        (node.fixup(), outcome)
+    }
+
+    fn fixup(mut self: Box<Self>) -> Box<Self> {
+        if Self::is_red(&self.right) && !Self::is_red(&self.left) {
+            self = self.rotate_left_rb();
+        }
+        if Self::is_red(&self.left) && Self::is_red(&self.left.as_ref().unwrap().left) {
+            self = self.rotate_right_rb();
+        }
+        if Self::is_red(&self.left) && Self::is_red(&self.right) {
+            self.flip_colors();
+        }
+        self
+    }
+
+    // 旋转后新根继承旧根的颜色, 旧根变红
+    fn rotate_left_rb(self: Box<Self>) -> Box<Self> {
+        let color = self.meta;
+        let mut root = self.rotate_left();
+        root.left.as_mut().unwrap().meta = Color::Red;
+        root.meta = color;
+        root
+    }
+
+    fn rotate_right_rb(self: Box<Self>) -> Box<Self> {
+        let color = self.meta;
+        let mut root = self.rotate_right();
+        root.right.as_mut().unwrap().meta = Color::Red;
+        root.meta = color;
+        root
+    }
+
+    fn flip_colors(&mut self) {
+        self.meta.flip();
+        self.left.as_mut().unwrap().meta.flip();
+        self.right.as_mut().unwrap().meta.flip();
+    }
+
+    fn move_red_left(mut self: Box<Self>) -> Box<Self> {
+        self.flip_colors();
+        if Self::is_red(&self.right.as_ref().unwrap().left) {
+            self.right = Some(self.right.take().unwrap().rotate_right_rb());
+            self = self.rotate_left_rb();
+            self.flip_colors();
+        }
+        self
+    }
+
+    fn move_red_right(mut self: Box<Self>) -> Box<Self> {
+        self.flip_colors();
+        if Self::is_red(&self.left.as_ref().unwrap().left) {
+            self = self.rotate_right_rb();
+            self.flip_colors();
+        }
+        self
+    }
+
+    fn delete_min_rb(mut self: Box<Self>) -> (Link<T, Color>, Box<Self>) {
+        if self.left.is_none() {
+            return (None, self);
+        }
+        if !Self::is_red(&self.left) && !Self::is_red(&self.left.as_ref().unwrap().left) {
+            self = self.move_red_left();
+        }
+        let (left, min) = self.left.take().unwrap().delete_min_rb();
+        self.left = left;
+        (Some(self.fixup()), min)
+    }
+
+    /// 删除子树中的指定值 (调用方保证该值存在)
+    fn delete_rb(mut self: Box<Self>, value: &T) -> (Link<T, Color>, T) {
+        let removed;
+        if *value < self.value {
+            if !Self::is_red(&self.left) && !Self::is_red(&self.left.as_ref().unwrap().left) {
+                self = self.move_red_left();
+            }
+            let (left, value) = self.left.take().unwrap().delete_rb(value);
+            self.left = left;
+            removed = value;
+        } else {
+            if Self::is_red(&self.left) {
+                self = self.rotate_right_rb();
+            }
+            if *value == self.value && self.right.is_none() {
+                return (None, self.value);
+            }
+            if !Self::is_red(&self.right) && !Self::is_red(&self.right.as_ref().unwrap().left) {
+                self = self.move_red_right();
+            }
+            if *value == self.value {
+                let (right, successor) = self.right.take().unwrap().delete_min_rb();
+                self.right = right;
+                removed = std::mem::replace(&mut self.value, successor.value);
+            } else {
+                let (right, value) = self.right.take().unwrap().delete_rb(value);
+                self.right = right;
+                removed = value;
+            }
+        }
+        (Some(self.fixup()), removed)
+    }
+}
+
+// 基准测试
+
+/// 访问模式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Workload {
+    Random,     // 均匀随机
+    Sequential, // 按升序插入, 查找和删除
+    Zipf,       // 少数热点值占了大部分访问
+}
+
+impl Workload {
+    const ALL: [Workload; 3] = [Workload::Random, Workload::Sequential, Workload::Zipf];
+
+    fn name(self) -> &'static str {
+        match self {
+            Workload::Random => "random",
+            Workload::Sequential => "sequential",
+            Workload::Zipf => "zipf",
+        }
+    }
+
+    /// 生成 n 个操作用的值
+    fn generate(self, n: usize, mut seed: u64) -> Vec<u64> {
+        match self {
+            Workload::Random => (0..n).map(|_| lcg(&mut seed) % (2 * n as u64)).collect(),
+            Workload::Sequential => (0..n as u64).collect(),
+            Workload::Zipf => zipf(n, n, seed),
+        }
+    }
+}
+
+/// 按 Zipf 分布 (s = 1) 抽取 n 个值: 在 m 个排名中, 排名 k 被抽中的概率正比于 1 / (k + 1)
+/// 排名再映射成分散的值, 免得热点恰好都挤在树的一侧
+fn zipf(n: usize, m: usize, mut seed: u64) -> Vec<u64> {
+    let mut cdf = Vec::with_capacity(m);
+    let mut total = 0.0;
+    for k in 0..m {
+        total += 1.0 / (k + 1) as f64;
+        cdf.push(total);
+    }
+    (0..n)
+        .map(|_| {
+            let u = lcg(&mut seed) as f64 / (1u64 << 53) as f64 * total;
+            let rank = cdf.partition_point(|&c| c < u).min(m - 1) as u64;
+            rank.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        })
+        .collect()
+}
+
+/// 一次计时结果, height 是插入阶段结束时的树高
+#[derive(Debug)]
+struct BenchResult {
+    tree: &'static str,
+    workload: Workload,
+    n: usize,
+    elapsed: Duration,
+    height: usize,
+}
+
+/// 在一种负载上运行一棵树: 插入 n 个值, 查找 n 个值, 再删除 n / 2 个值
+/// 计时结束后用 BTreeSet 重放同样的操作, 任何一步的结果不一致就返回错误
+fn run_tree<S: SearchTree<u64>>(
+    workload: Workload,
+    n: usize,
+    seed: u64,
+) -> Result<BenchResult, String> {
+    let inserts = workload.generate(n, seed);
+    let searches = workload.generate(n, seed.wrapping_add(1));
+    let removes = workload.generate(n / 2, seed.wrapping_add(2));
+
+    let mut tree = S::new();
+    let start = Instant::now();
+    let inserted: Vec<bool> = inserts
+        .iter()
+        .map(|&v| tree.insert(v) == InsertOutcome::Inserted)
+        .collect();
+    let mut elapsed = start.elapsed();
+    let height = tree.height();
+    let start = Instant::now();
+    let found: Vec<bool> = searches.iter().map(|v| tree.search(v)).collect();
+    let removed: Vec<bool> = removes.iter().map(|v| tree.remove(v).is_some()).collect();
+    elapsed += start.elapsed();
+
+    let error = |what: &str| {
+        format!(
+            "{} 在 {} 负载上{}的结果不正确",
+            S::NAME,
+            workload.name(),
+            what
+        )
+    };
+    let mut model = std::collections::BTreeSet::new();
+    if inserts.iter().map(|&v| model.insert(v)).ne(inserted) {
+        return Err(error("插入"));
+    }
+    if searches.iter().map(|v| model.contains(v)).ne(found) {
+        return Err(error("查找"));
+    }
+    if removes.iter().map(|v| model.remove(v)).ne(removed) {
+        return Err(error("删除"));
+    }
+    if tree.len() != model.len() || tree.iter().ne(model.iter()) {
+        return Err(error("遍历"));
+    }
+    Ok(BenchResult {
+        tree: S::NAME,
+        workload,
+        n,
+        elapsed,
+        height,
+    })
+}
+
+/// 每种负载上依次运行五种树
+fn run_benchmark(n: usize, seed: u64) -> Result<Vec<BenchResult>, String> {
+    let mut results = Vec::new();
+    for workload in Workload::ALL {
+        results.push(run_tree::<Treap<u64>>(workload, n, seed)?);
+        results.push(run_tree::<SplayTree<u64>>(workload, n, seed)?);
+        results.push(run_tree::<ScapegoatTree<u64>>(workload, n, seed)?);
+        results.push(run_tree::<AvlTree<u64>>(workload, n, seed)?);
+        results.push(run_tree::<RbTree<u64>>(workload, n, seed)?);
+    }
+    Ok(results)
+}
+
+/// 以表格形式输出, 每行一棵树, 每列一种负载, 格子里是毫秒数和插入后的树高
+/// 第一行是标题, 说明比较的是本文件中的五种树
+fn results_table(results: &[BenchResult]) -> String {
+    let mut trees: Vec<&str> = results.iter().map(|r| r.tree).collect();
+    trees.dedup();
+    trees.truncate(results.len() / Workload::ALL.len());
+    let mut table =
+        "同一种精简节点上的平衡策略 (都在本文件中实现, 不是 _04 其他文件中的树)\n".to_string();
+    table += &format!("{:<12}", "tree");
+    for workload in Workload::ALL {
+        table += &format!("{:>20}", workload.name());
+    }
+    table.push('\n');
+    for tree in trees {
+        table += &format!("{:<12}", tree);
+        for workload in Workload::ALL {
+            let cell = results
+                .iter()
+                .find(|r| r.tree == tree && r.workload == workload)
+                .map_or("-".to_string(), |r| {
+                    format!("{:.3} (h={})", r.elapsed.as_secs_f64() * 1000.0, r.height)
+                });
+            table += &format!("{:>20}", cell);
+        }
+        table.push('\n');
+    }
+    table
+}
+
+/// 以 CSV 形式输出, 方便导入其他工具
+fn results_csv(results: &[BenchResult]) -> String {
+    let mut csv = "tree,workload,n,nanos,height\n".to_string();
+    for r in results {
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            r.tree,
+            r.workload.name(),
+            r.n,
+            r.elapsed.as_nanos(),
+            r.height
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    // 随机的插入, 查找和删除, 每一步都和 BTreeSet 对比
+    fn check_against_model<S: SearchTree<u64>>() {
+        let mut tree = S::new();
+        let mut model = BTreeSet::new();
+        let mut seed = 7;
+        for _ in 0..3000 {
+            let value = lcg(&mut seed) % 500;
+            match lcg(&mut seed) % 3 {
+                0 => {
+                    let expected = if model.insert(value) {
+                        InsertOutcome::Inserted
+                    } else {
+                        InsertOutcome::Rejected(value)
+                    };
+                    assert_eq!(tree.insert(value), expected, "{}", S::NAME);
+                }
+                1 => assert_eq!(tree.search(&value), model.contains(&value), "{}", S::NAME),
+                _ => assert_eq!(tree.remove(&value), model.take(&value), "{}", S::NAME),
+            }
+            assert_eq!(tree.len(), model.len());
+        }
+        assert!(tree.iter().eq(model.iter()), "{}", S::NAME);
+        for value in model {
+            assert_eq!(tree.remove(&value), Some(value));
+        }
+        assert!(tree.is_empty());
+        assert!(tree.root().is_none());
+    }
+
+    #[test]
+    fn test_all_trees_match_model() {
+        check_against_model::<Treap<u64>>();
+        check_against_model::<SplayTree<u64>>();
+        check_against_model::<ScapegoatTree<u64>>();
+        check_against_model::<AvlTree<u64>>();
+        check_against_model::<RbTree<u64>>();
+    }
+
+    // 检查 treap 的堆性质, 返回节点个数
+    fn check_treap<T: Ord>(node: &Link<T, Priority>) -> usize {
+        let Some(n) = node else {
+            return 0;
+        };
+        for child in [&n.left, &n.right].into_iter().flatten() {
+            assert!(child.meta.0 <= n.meta.0);
+        }
+        1 + check_treap(&n.left) + check_treap(&n.right)
+    }
+
+    #[test]
+    fn test_treap() {
+        // 顺序插入也能得到对数高度
+        let mut treap = Treap::new();
+        for i in 0..10_000 {
+            assert_eq!(treap.insert(i), InsertOutcome::Inserted);
+        }
+        assert_eq!(treap.insert(42), InsertOutcome::Rejected(42));
+        assert_eq!(check_treap(&treap.root), 10_000);
+        assert!(treap.height() < 50);
+        for i in (0..10_000).step_by(2) {
+            assert_eq!(treap.remove(&i), Some(i));
+        }
+        assert_eq!(treap.remove(&0), None);
+        assert_eq!(check_treap(&treap.root), 5000);
+        assert!(treap.iter().copied().eq((1..10_000).step_by(2)));
+    }
+
+    #[test]
+    fn test_splay_moves_accessed_value_to_root() {
+        let mut splay = SplayTree::new();
+        for i in [5, 3, 8, 1, 4, 7, 9] {
+            splay.insert(i);
+            assert_eq!(splay.root.as_ref().unwrap().value, i);
+        }
+        assert!(splay.search(&1));
+        assert_eq!(splay.root.as_ref().unwrap().value, 1);
+        // 找不到时转上来的是最后访问的节点, 也就是前驱或后继
+        assert!(!splay.search(&6));
+        assert!([5, 7].contains(&splay.root.as_ref().unwrap().value));
+        assert_eq!(splay.remove(&5), Some(5));
+        assert_eq!(splay.insert(4), InsertOutcome::Rejected(4));
+        assert_eq!(splay.root.as_ref().unwrap().value, 4);
+        assert!(splay.iter().copied().eq([1, 3, 4, 7, 8, 9]));
+    }
+
+    #[test]
+    fn test_splay_deep_chain() {
+        // 顺序插入得到一条 10 万层的链, 伸展, 遍历和析构都不能递归
+        let mut splay = SplayTree::new();
+        for i in 0..100_000 {
+            splay.insert(i);
+        }
+        assert_eq!(splay.height(), 100_000);
+        assert!(splay.search(&0));
+        // 自顶向下伸展大约把沿途的深度减半
+        assert!(splay.height() < 60_000);
+        assert_eq!(splay.iter().count(), 100_000);
+    }
+
+    // 检查替罪羊树的子树大小, 返回节点个数
+    fn check_scapegoat<T: Ord>(node: &Link<T, Size>) -> usize {
+        let Some(n) = node else {
+            return 0;
+        };
+        let size = 1 + check_scapegoat(&n.left) + check_scapegoat(&n.right);
+        assert_eq!(n.meta.0, size);
+        size
+    }
+
+    #[test]
+    fn test_scapegoat() {
+        let mut tree = ScapegoatTree::new();
+        for i in 0..10_000 {
+            assert_eq!(tree.insert(i), InsertOutcome::Inserted);
+            assert!(tree.height() <= depth_limit(tree.len()) + 1);
+        }
+        assert_eq!(check_scapegoat(&tree.root), 10_000);
+        // 删掉大部分之后整体重建, max_len 跟着重置
+        for i in 0..8000 {
+            assert_eq!(tree.remove(&i), Some(i));
+        }
+        assert_eq!(check_scapegoat(&tree.root), 2000);
+        assert!(tree.max_len < 10_000);
+        assert!(tree.height() <= depth_limit(tree.len()) + 1);
+        assert!(tree.iter().copied().eq(8000..10_000));
+    }
+
+    #[test]
+    fn test_zipf_is_skewed() {
+        let values = zipf(10_000, 1000, 3);
+        let hottest = values.iter().filter(|&&v| v == 0).count();
+        let coldest = values
+            .iter()
+            .filter(|&&v| v == 999u64.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .count();
+        // 排名 0 的概率约为 1 / H(1000) ≈ 13%
+        assert!(hottest > 1000 && hottest < 1600, "{}", hottest);
+        assert!(coldest < 10);
+    }
+
+    #[test]
+    fn test_run_benchmark() {
+        let results = run_benchmark(500, 42).unwrap();
+        assert_eq!(results.len(), Workload::ALL.len() * 5);
+        let table = results_table(&results);
+        // 标题, 表头和五棵树
+        assert_eq!(table.lines().count(), 7);
+        assert!(table.starts_with("同一种精简节点上的平衡策略"));
+        assert!(table.contains("\navl ") && table.contains("\nllrb "));
+        let csv = results_csv(&results);
+        assert_eq!(csv.lines().count(), results.len() + 1);
+    }
+
+    // 较大规模的计时, 用 cargo test --release -- --ignored --nocapture 运行
+    #[test]
+    #[ignore]
+    fn bench_trees() {
+        let results = run_benchmark(200_000, 42).unwrap();
+        println!("{}", results_table(&results));
+        println!("{}", results_csv(&results));
+    }
+}
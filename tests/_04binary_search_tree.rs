@@ -1,7 +1,21 @@
 // 完成基本的二叉搜索树接口
+// 除了插入和查找, 还支持删除, 最值, 前驱后继, 以及范围查询
 
+mod common;
+
+use common::Edge;
+use common::codec::{
+    COUNT_FLAG, CountError, Encode, escape_into, preorder_parents, read_varint, split_count,
+    unescape, write_varint,
+};
+use common::render::{RenderNode, dot_escape, write_ascii, write_dot};
+use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::fmt::Debug;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Write};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
 
 #[derive(Debug)]
 struct TreeNode<T>
@@ -11,6 +25,7 @@ where
     value: T,
     left: Option<Box<TreeNode<T>>>,
     right: Option<Box<TreeNode<T>>>,
+    count: usize, // 重数, 只有 DuplicatePolicy::Count 会大于 1
 }
 
 #[derive(Debug)]
@@ -19,6 +34,32 @@ where
     T: Ord,
 {
     root: Option<Box<TreeNode<T>>>,
+    len: usize, // 节点个数
+    policy: DuplicatePolicy,
+}
+
+/// 插入重复值时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DuplicatePolicy {
+    Reject,  // 拒绝插入, 把新值还给调用方
+    Replace, // 用新值替换旧值
+    Count,   // 多重集合: 节点上的重数加一, len 和遍历仍按不同的值计算
+}
+
+/// insert 的结果
+#[derive(Debug, PartialEq)]
+enum InsertOutcome<T> {
+    Inserted,       // 插入了新节点
+    Rejected(T),    // 值已存在, 原样退回
+    Replaced(T),    // 值已存在, 返回被替换掉的旧值
+    Counted(usize), // 值已存在, 返回新的重数
+}
+
+/// remove 的结果
+#[derive(Debug, PartialEq)]
+enum RemoveOutcome<T> {
+    Removed(T),         // 摘掉了节点, 返回其中的值
+    Decremented(usize), // 多重集合中重数大于 1, 只减一, 返回剩下的重数
 }
 
 impl<T> TreeNode<T>
@@ -30,6 +71,7 @@ where
             value,
             left: None,
             right: None,
+            count: 1,
         }
     }
 }
@@ -39,24 +81,298 @@ where
     T: Ord,
 {
     fn new() -> Self {
-        BinarySearchTree { root: None }
+        Self::with_policy(DuplicatePolicy::Reject)
+    }
+
+    /// 指定重复值的处理方式
+    fn with_policy(policy: DuplicatePolicy) -> Self {
+        BinarySearchTree {
+            root: None,
+            len: 0,
+            policy,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[allow(dead_code)]
+    fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     // Insert a value into the BST
-    fn insert(&mut self, value: T) {
+    // 值已存在时按 policy 处理, 返回实际发生了什么
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
         //TODO
-        if let Some(ref mut root) = self.root {
-            root.insert(value);
+        let outcome = if let Some(ref mut root) = self.root {
+            root.insert(value, self.policy)
         } else {
             self.root = Some(Box::new(TreeNode::new(value)));
+            InsertOutcome::Inserted
+        };
+        if outcome == InsertOutcome::Inserted {
+            self.len += 1;
+        }
+        outcome
+    }
+
+    /// 值的重数, 不存在时为 0
+    fn count<Q>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match value.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return node.count,
+            };
         }
+        0
     }
 
     // Search for a value in the BST
-    fn search(&self, value: T) -> bool {
+    // 和标准库的集合一样通过 Borrow 查找, 比如 String 树可以直接用 &str 查
+    fn search<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
         //TODO
         self.root.as_ref().map_or(false, |root| root.search(value))
     }
+
+    /// 删除一个值, 不存在时返回 None
+    /// 多重集合中重数大于 1 时只把重数减一, 节点保留; 要一次删掉所有副本用 remove_all
+    fn remove<Q>(&mut self, value: &Q) -> Option<RemoveOutcome<T>>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = self.find_mut(value)?;
+        if node.count > 1 {
+            node.count -= 1;
+            return Some(RemoveOutcome::Decremented(node.count));
+        }
+        self.remove_all(value).map(RemoveOutcome::Removed)
+    }
+
+    /// 删除值所在的节点, 不管重数是多少, 返回节点中的值, 不存在时返回 None
+    fn remove_all<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = TreeNode::remove(&mut self.root, value);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    // 找到值所在的节点
+    fn find_mut<Q>(&mut self, value: &Q) -> Option<&mut TreeNode<T>>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_deref_mut();
+        while let Some(node) = current {
+            current = match value.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref_mut(),
+                Ordering::Greater => node.right.as_deref_mut(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    /// 最小值: 一直向左走到底
+    fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = node.left.as_ref() {
+            node = left;
+        }
+        Some(&node.value)
+    }
+
+    /// 最大值: 一直向右走到底
+    fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = node.right.as_ref() {
+            node = right;
+        }
+        Some(&node.value)
+    }
+
+    /// 小于等于 value 的最大值
+    fn floor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.below(value, true)
+    }
+
+    /// 大于等于 value 的最小值
+    fn ceiling<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.above(value, true)
+    }
+
+    /// 严格小于 value 的最大值, value 本身不需要在树中
+    fn predecessor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.below(value, false)
+    }
+
+    /// 严格大于 value 的最小值, value 本身不需要在树中
+    fn successor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.above(value, false)
+    }
+
+    // floor 和 predecessor 的共同逻辑
+    // 从根向下走, 当前节点满足条件时记下它, 然后去右边找更大的, 否则去左边
+    fn below<Q>(&self, value: &Q, inclusive: bool) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut best = None;
+        let mut current = self.root.as_ref();
+        while let Some(node) = current {
+            match node.value.borrow().cmp(value) {
+                Ordering::Equal if inclusive => return Some(&node.value),
+                Ordering::Less => {
+                    best = Some(&node.value);
+                    current = node.right.as_ref();
+                }
+                _ => current = node.left.as_ref(),
+            }
+        }
+        best
+    }
+
+    // ceiling 和 successor 的共同逻辑, 与 below 对称
+    fn above<Q>(&self, value: &Q, inclusive: bool) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut best = None;
+        let mut current = self.root.as_ref();
+        while let Some(node) = current {
+            match node.value.borrow().cmp(value) {
+                Ordering::Equal if inclusive => return Some(&node.value),
+                Ordering::Greater => {
+                    best = Some(&node.value);
+                    current = node.left.as_ref();
+                }
+                _ => current = node.right.as_ref(),
+            }
+        }
+        best
+    }
+
+    /// 按从小到大的顺序遍历落在 range 中的值, 例如 tree.range(3..7)
+    /// 和 BTreeMap::range 一样, 用 &str 查 String 树时要写成 (Bound<&str>, Bound<&str>)
+    fn range<Q, R>(&self, range: R) -> Range<'_, T, Q, R>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut iter = Range {
+            stack: Vec::new(),
+            range,
+            _query: PhantomData,
+        };
+        // 找到第一个不小于下界的节点, 沿途所有满足下界的节点都压栈
+        // 这和中序遍历 "一路向左压栈" 是一样的, 只是跳过了太小的左子树
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            if iter.above_start(node.value.borrow()) {
+                iter.stack.push(node);
+                current = node.left.as_deref();
+            } else {
+                current = node.right.as_deref();
+            }
+        }
+        iter
+    }
+}
+
+/// 范围查询的迭代器
+/// 栈顶是下一个要返回的节点, 栈里的节点都还没有访问过它们的右子树
+struct Range<'a, T, Q: ?Sized, R>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+    range: R,
+    _query: PhantomData<fn(&Q)>,
+}
+
+impl<T, Q, R> Range<'_, T, Q, R>
+where
+    T: Ord,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    fn above_start(&self, value: &Q) -> bool {
+        match self.range.start_bound() {
+            Bound::Included(start) => value >= start,
+            Bound::Excluded(start) => value > start,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn below_end(&self, value: &Q) -> bool {
+        match self.range.end_bound() {
+            Bound::Included(end) => value <= end,
+            Bound::Excluded(end) => value < end,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'a, T, Q, R> Iterator for Range<'a, T, Q, R>
+where
+    T: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        if !self.below_end(node.value.borrow()) {
+            // 之后的值只会更大, 直接结束
+            self.stack.clear();
+            return None;
+        }
+        // 右子树中的值都比当前节点大, 自然满足下界, 把它的左链压栈即可
+        let mut current = node.right.as_deref();
+        while let Some(next) = current {
+            self.stack.push(next);
+            current = next.left.as_deref();
+        }
+        Some(&node.value)
+    }
 }
 
 impl<T> TreeNode<T>
@@ -64,41 +380,820 @@ where
     T: Ord,
 {
     // Insert a node into the tree
-    fn insert(&mut self, value: T) {
+    // 树不平衡, 有序输入会让它退化成链表, 所以这里用循环而不是递归往下走
+    fn insert(&mut self, value: T, policy: DuplicatePolicy) -> InsertOutcome<T> {
         //TODO
-        match value.cmp(&self.value) {
-            Ordering::Less => {
-                if let Some(ref mut left) = self.left {
-                    left.insert(value);
-                } else {
-                    self.left = Some(Box::new(TreeNode::new(value)));
+        let mut node = self;
+        loop {
+            let next = match value.cmp(&node.value) {
+                Ordering::Less => &mut node.left,
+                Ordering::Greater => &mut node.right,
+                Ordering::Equal => return node.on_duplicate(value, policy),
+            };
+            match next {
+                Some(child) => node = child,
+                None => {
+                    *next = Some(Box::new(TreeNode::new(value)));
+                    return InsertOutcome::Inserted;
                 }
             }
-            Ordering::Greater => {
-                if let Some(ref mut right) = self.right {
-                    right.insert(value);
-                } else {
-                    self.right = Some(Box::new(TreeNode::new(value)));
-                }
+        }
+    }
+
+    /// 重复值处理: 按策略拒绝, 替换或者累加重数
+    fn on_duplicate(&mut self, value: T, policy: DuplicatePolicy) -> InsertOutcome<T> {
+        match policy {
+            DuplicatePolicy::Reject => InsertOutcome::Rejected(value),
+            DuplicatePolicy::Replace => {
+                InsertOutcome::Replaced(std::mem::replace(&mut self.value, value))
             }
-            Ordering::Equal => {
-                // 重复值处理: 这里我们选择不插入重复值
-                // 也可以根据需求选择其他处理方式
+            DuplicatePolicy::Count => {
+                self.count += 1;
+                InsertOutcome::Counted(self.count)
             }
         }
     }
 
     // 辅助函数
-    // 递归查找节点
-    fn search(&self, value: T) -> bool {
-        match value.cmp(&self.value) {
-            Ordering::Less => self.left.as_ref().map_or(false, |left| left.search(value)),
-            Ordering::Greater => self
-                .right
-                .as_ref()
-                .map_or(false, |right| right.search(value)),
-            Ordering::Equal => true,
+    // 循环查找节点
+    fn search<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self;
+        loop {
+            let next = match value.cmp(node.value.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+            match next {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+    }
+
+    /// 从以 node 为根的子树中删除 value
+    /// 删除可能会换掉子树的根, 所以这里操作的是指向子树的那个 Option
+    fn remove<Q>(mut node: &mut Option<Box<TreeNode<T>>>, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // 先循环找到指向目标节点的那个 Option
+        // 比较时只借用不可变引用, 确定方向后再重新可变借用, 否则借用检查器不让 node 前进
+        loop {
+            match value.cmp(node.as_ref()?.value.borrow()) {
+                Ordering::Less => node = &mut node.as_mut().unwrap().left,
+                Ordering::Greater => node = &mut node.as_mut().unwrap().right,
+                Ordering::Equal => break,
+            }
+        }
+        let mut removed = node.take().unwrap();
+        *node = match (removed.left.take(), removed.right.take()) {
+            // 叶子节点直接删除
+            (None, None) => None,
+            // 只有一个孩子, 让孩子顶替自己
+            (Some(child), None) | (None, Some(child)) => Some(child),
+            // 两个孩子: 用后继 (右子树中最小的值) 替换自己的值, 再从右子树中摘掉后继
+            // 后继没有左孩子, 所以摘掉它只需要让它的右孩子顶上
+            (Some(left), Some(right)) => {
+                let mut right = Some(right);
+                let successor = Self::take_min(&mut right);
+                let value = std::mem::replace(&mut removed.value, successor.value);
+                removed.count = successor.count;
+                removed.left = Some(left);
+                removed.right = right;
+                *node = Some(removed);
+                return Some(value);
+            }
+        };
+        Some(removed.value)
+    }
+
+    /// 从非空子树中摘掉最小的节点并返回, 它的右孩子顶替它原来的位置
+    fn take_min(mut node: &mut Option<Box<TreeNode<T>>>) -> Box<TreeNode<T>> {
+        while node.as_ref().unwrap().left.is_some() {
+            node = &mut node.as_mut().unwrap().left;
+        }
+        let mut min = node.take().unwrap();
+        *node = min.right.take();
+        min
+    }
+
+    /// 逐个拆开节点再释放
+    /// 派生的析构会沿着 Box 递归, 退化成链表的树同样会把栈撑爆
+    fn dismantle(mut stack: Vec<Box<TreeNode<T>>>) {
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+// 遍历
+// 都用显式的栈或队列实现, 不会因为树太深而栈溢出
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 中序遍历, 也就是从小到大
+    fn iter(&self) -> InOrder<'_, T> {
+        self.in_order()
+    }
+
+    /// 中序遍历: 左子树, 根, 右子树
+    fn in_order(&self) -> InOrder<'_, T> {
+        let mut iter = InOrder { stack: Vec::new() };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+
+    /// 前序遍历: 根, 左子树, 右子树
+    fn pre_order(&self) -> PreOrder<'_, T> {
+        PreOrder {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    /// 后序遍历: 左子树, 右子树, 根
+    fn post_order(&self) -> PostOrder<'_, T> {
+        PostOrder {
+            stack: self
+                .root
+                .as_deref()
+                .map(|n| (n, false))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// 层序遍历: 按层从上到下, 每层从左到右
+    fn level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder {
+            queue: self.root.as_deref().into_iter().collect(),
+        }
+    }
+}
+
+/// 中序遍历的迭代器
+/// 栈里是还没有返回的节点, 它们的左子树都已经处理完或者正在处理
+struct InOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+}
+
+impl<'a, T> InOrder<'a, T>
+where
+    T: Ord,
+{
+    // 从 node 开始一路向左, 沿途的节点都压栈
+    fn push_left(&mut self, mut node: Option<&'a TreeNode<T>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, T> Iterator for InOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        // 左子树已经访问完, 接下来是右子树中最小的节点
+        self.push_left(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+/// 前序遍历的迭代器
+struct PreOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+}
+
+impl<'a, T> Iterator for PreOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        // 先压右再压左, 这样左子树先出栈
+        if let Some(right) = node.right.as_deref() {
+            self.stack.push(right);
+        }
+        if let Some(left) = node.left.as_deref() {
+            self.stack.push(left);
+        }
+        Some(&node.value)
+    }
+}
+
+/// 后序遍历的迭代器
+/// 节点第一次出栈时还不能返回, 要先把它标记为已展开再压回去, 然后压入两个子树
+/// 第二次出栈时, 它的两个子树都已经访问完了
+struct PostOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<(&'a TreeNode<T>, bool)>,
+}
+
+impl<'a, T> Iterator for PostOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(&node.value);
+            }
+            self.stack.push((node, true));
+            if let Some(right) = node.right.as_deref() {
+                self.stack.push((right, false));
+            }
+            if let Some(left) = node.left.as_deref() {
+                self.stack.push((left, false));
+            }
+        }
+    }
+}
+
+/// 层序遍历的迭代器, 就是树上的 BFS
+struct LevelOrder<'a, T>
+where
+    T: Ord,
+{
+    queue: VecDeque<&'a TreeNode<T>>,
+}
+
+impl<'a, T> Iterator for LevelOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.left.as_deref());
+        self.queue.extend(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+/// 按从小到大的顺序取出所有值的迭代器, 会消耗整棵树
+struct IntoIter<T>
+where
+    T: Ord,
+{
+    stack: Vec<Box<TreeNode<T>>>,
+}
+
+impl<T> IntoIter<T>
+where
+    T: Ord,
+{
+    // 和 InOrder::push_left 一样, 只是这里拿走了左子树的所有权
+    fn push_left(&mut self, mut node: Option<Box<TreeNode<T>>>) {
+        while let Some(mut n) = node {
+            node = n.left.take();
+            self.stack.push(n);
+        }
+    }
+}
+
+// 只消耗了一部分就丢弃时, 栈里的节点还挂着整棵右子树
+impl<T> Drop for IntoIter<T>
+where
+    T: Ord,
+{
+    fn drop(&mut self) {
+        TreeNode::dismantle(std::mem::take(&mut self.stack));
+    }
+}
+
+impl<T> Iterator for IntoIter<T>
+where
+    T: Ord,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut node = self.stack.pop()?;
+        self.push_left(node.right.take());
+        Some(node.value)
+    }
+}
+
+impl<T> IntoIterator for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> IntoIter<T> {
+        let mut iter = IntoIter { stack: Vec::new() };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BinarySearchTree<T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+    type IntoIter = InOrder<'a, T>;
+
+    fn into_iter(self) -> InOrder<'a, T> {
+        self.in_order()
+    }
+}
+
+impl<T> FromIterator<T> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = BinarySearchTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<T> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T> Drop for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn drop(&mut self) {
+        TreeNode::dismantle(self.root.take().into_iter().collect());
+    }
+}
+
+// 层级查询和度量
+// 树可能退化成链表, 所以都用循环和显式的栈, 深度是从根出发经过的边数, 根的深度为 0
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 从根到 value 所在节点经过的所有值, 包括两端, value 不在树中时返回 None
+    fn path_to<Q>(&self, value: &Q) -> Option<Vec<&T>>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut path = Vec::new();
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            path.push(&node.value);
+            current = match value.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(path),
+            };
         }
+        None
+    }
+
+    /// value 所在节点的深度
+    fn depth<Q>(&self, value: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut depth = 0;
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match value.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(depth),
+            };
+            depth += 1;
+        }
+        None
+    }
+
+    /// 向上第 k 层的祖先, k 为 0 时是节点自己, 超过根时返回 None
+    fn kth_ancestor<Q>(&self, value: &Q, k: usize) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let path = self.path_to(value)?;
+        let index = path.len().checked_sub(k.checked_add(1)?)?;
+        Some(path[index])
+    }
+
+    /// 最近公共祖先, 一个节点也算自己的祖先, 任意一个值不在树中时返回 None
+    fn lca<Q>(&self, a: &Q, b: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (path_a, path_b) = (self.path_to(a)?, self.path_to(b)?);
+        let common = Self::common_prefix(&path_a, &path_b);
+        Some(path_a[common - 1])
+    }
+
+    /// 两个节点之间的边数, 也就是各自到最近公共祖先的距离之和
+    fn distance<Q>(&self, a: &Q, b: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (path_a, path_b) = (self.path_to(a)?, self.path_to(b)?);
+        let common = Self::common_prefix(&path_a, &path_b);
+        Some(path_a.len() + path_b.len() - 2 * common)
+    }
+
+    // 两条从根出发的路径在分叉之前共有的节点数, 至少有根这一个
+    // 搜索树的一条路径上每个值各不相同, 逐个比较值就能找到分叉点
+    fn common_prefix(a: &[&T], b: &[&T]) -> usize {
+        a.iter().zip(b).take_while(|(x, y)| x == y).count()
+    }
+
+    /// 树高: 最长的从根到叶子的路径上的节点数, 空树为 0, 与 AVL 树中的高度定义一致
+    fn height(&self) -> usize {
+        self.fold_up(0, |left, right| 1 + left.max(right))
+    }
+
+    /// 是否每个节点左右子树的高度差都不超过 1
+    fn is_balanced(&self) -> bool {
+        self.fold_up(Some(0usize), |left, right| match (left, right) {
+            (Some(left), Some(right)) if left.abs_diff(right) <= 1 => Some(1 + left.max(right)),
+            _ => None,
+        })
+        .is_some()
+    }
+
+    /// 直径: 任意两个节点之间最长路径的边数
+    /// 最长路径一定在某个节点处拐弯, 长度是这个节点左右子树的高度之和
+    fn diameter(&self) -> usize {
+        let mut diameter = 0;
+        self.fold_up(0, |left, right| {
+            diameter = diameter.max(left + right);
+            1 + left.max(right)
+        });
+        diameter
+    }
+
+    /// 检查搜索树的性质: 中序遍历严格递增
+    /// 正常的插入删除不会破坏它, 用来检查直接改动节点之后的树
+    fn is_valid_bst(&self) -> bool {
+        self.iter().zip(self.iter().skip(1)).all(|(a, b)| a < b)
+    }
+
+    // 后序遍历, 用左右孩子的结果算出每个节点的结果, 空子树的结果是 empty
+    // 结果放在另一个栈里, 处理一个节点时, 它的两个孩子的结果正好在栈顶
+    fn fold_up<R: Copy>(&self, empty: R, mut combine: impl FnMut(R, R) -> R) -> R {
+        let mut results = Vec::new();
+        let mut stack: Vec<_> = self
+            .root
+            .as_deref()
+            .map(|node| (node, false))
+            .into_iter()
+            .collect();
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                let right = if node.right.is_some() {
+                    results.pop().unwrap()
+                } else {
+                    empty
+                };
+                let left = if node.left.is_some() {
+                    results.pop().unwrap()
+                } else {
+                    empty
+                };
+                results.push(combine(left, right));
+            } else {
+                stack.push((node, true));
+                stack.extend(node.right.as_deref().map(|right| (right, false)));
+                stack.extend(node.left.as_deref().map(|left| (left, false)));
+            }
+        }
+        results.pop().unwrap_or(empty)
+    }
+}
+
+// 可视化
+// 画法在 common/render.rs 里, 这里只决定节点上显示什么
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord + Display,
+{
+    /// 横向打印: 根在最左边, 右子树画在上面, 左子树画在下面
+    fn to_ascii(&self) -> String {
+        let mut out = String::new();
+        if let Some(root) = self.root.as_deref() {
+            write_ascii(root, &mut out);
+        }
+        out
+    }
+
+    /// 导出 Graphviz DOT, 可以用 `dot -Tpng` 画出来
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        if let Some(root) = self.root.as_deref() {
+            write_dot(root, &mut out);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<T> RenderNode for TreeNode<T>
+where
+    T: Ord + Display,
+{
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+
+    /// 值, 重数大于 1 时附上重数
+    fn ascii_label(&self) -> String {
+        if self.count > 1 {
+            format!("{} (x{})", self.value, self.count)
+        } else {
+            self.value.to_string()
+        }
+    }
+
+    fn dot_attrs(&self) -> String {
+        format!("label=\"{}\"", dot_escape(&self.ascii_label()))
+    }
+}
+
+// 序列化
+// 文本格式: 节点之间用空格分隔, 重数大于 1 时写成 "值*重数"
+// 字节格式: 每个节点一个标记字节 1, 后面是值的编码
+// 和 AVL, 红黑树共用的编码, 转义和前序重建在 common/codec.rs 里, 这里的节点不多记别的信息
+
+/// 反序列化或重建失败的原因
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    UnexpectedEnd,        // 字节流在一个节点的中间结束
+    InvalidTag(u8),       // 无法识别的标记字节
+    InvalidToken(String), // 文本中无法解析的记号
+    OutOfOrder,           // 不是任何一棵搜索树的前序序列, 包括有重复值的情况
+    MismatchedTraversals, // 两个遍历序列描述的不是同一棵树
+    ZeroCount,            // 记录的重数为 0
+}
+
+impl From<CountError> for DecodeError {
+    fn from(error: CountError) -> Self {
+        match error {
+            CountError::Zero => DecodeError::ZeroCount,
+            CountError::Invalid(token) => DecodeError::InvalidToken(token),
+        }
+    }
+}
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 由前序序列重建搜索树, 序列不是某棵搜索树的前序时返回 OutOfOrder
+    /// 用单调栈一趟完成, 是 O(n) 的, 退化成链表的树也不会递归
+    fn from_preorder<I: IntoIterator<Item = T>>(values: I) -> Result<Self, DecodeError> {
+        let nodes = values
+            .into_iter()
+            .map(|value| Box::new(TreeNode::new(value)))
+            .collect();
+        Self::from_preorder_nodes(nodes)
+    }
+
+    fn from_preorder_nodes(nodes: Vec<Box<TreeNode<T>>>) -> Result<Self, DecodeError> {
+        let len = nodes.len();
+        let parents =
+            preorder_parents(&nodes, |node| &node.value).ok_or(DecodeError::OutOfOrder)?;
+        Ok(BinarySearchTree {
+            root: TreeNode::assemble(nodes, &parents),
+            len,
+            policy: DuplicatePolicy::Reject,
+        })
+    }
+
+    /// 按前序访问每个节点
+    fn walk_preorder(&self, mut visit: impl FnMut(&TreeNode<T>)) {
+        let mut stack: Vec<&TreeNode<T>> = self.root.as_deref().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            visit(node);
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+        }
+    }
+
+    /// 序列化成文本, 例如 "5 3 1*2 8"
+    fn to_preorder_string(&self) -> String
+    where
+        T: Display,
+    {
+        let mut out = String::new();
+        self.walk_preorder(|node| {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            escape_into(&node.value.to_string(), &mut out);
+            if node.count > 1 {
+                write!(out, "*{}", node.count).unwrap();
+            }
+        });
+        out
+    }
+
+    /// 从 to_preorder_string 的结果恢复, 重复值策略恢复成默认的 Reject
+    fn from_preorder_str(s: &str) -> Result<Self, DecodeError>
+    where
+        T: FromStr,
+    {
+        let nodes = s
+            .split_whitespace()
+            .map(|token| {
+                let (value, count) = split_count(token)?;
+                let value = unescape(value)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| DecodeError::InvalidToken(token.to_string()))?;
+                let mut node = Box::new(TreeNode::new(value));
+                node.count = count;
+                Ok(node)
+            })
+            .collect::<Result<_, DecodeError>>()?;
+        Self::from_preorder_nodes(nodes)
+    }
+
+    /// 序列化成字节
+    fn to_bytes(&self) -> Vec<u8>
+    where
+        T: Encode,
+    {
+        let mut out = Vec::new();
+        self.walk_preorder(|node| {
+            if node.count > 1 {
+                out.push(1 | COUNT_FLAG);
+                node.value.encode(&mut out);
+                write_varint(node.count as u64, &mut out);
+            } else {
+                out.push(1);
+                node.value.encode(&mut out);
+            }
+        });
+        out
+    }
+
+    /// 从 to_bytes 的结果恢复, 重复值策略恢复成默认的 Reject
+    fn from_bytes(mut input: &[u8]) -> Result<Self, DecodeError>
+    where
+        T: Encode,
+    {
+        let mut nodes = Vec::new();
+        while let Some((&tag, rest)) = input.split_first() {
+            input = rest;
+            if tag & !COUNT_FLAG != 1 {
+                return Err(DecodeError::InvalidTag(tag));
+            }
+            let value = T::decode(&mut input).ok_or(DecodeError::UnexpectedEnd)?;
+            let mut node = Box::new(TreeNode::new(value));
+            if tag & COUNT_FLAG != 0 {
+                node.count = match read_varint(&mut input).ok_or(DecodeError::UnexpectedEnd)? {
+                    0 => return Err(DecodeError::ZeroCount),
+                    count => count as usize,
+                };
+            }
+            nodes.push(node);
+        }
+        Self::from_preorder_nodes(nodes)
+    }
+}
+
+impl<T> TreeNode<T>
+where
+    T: Ord,
+{
+    /// 按 parents 把节点挂起来, 返回根
+    /// 父节点总是排在孩子前面, 所以倒着挂时每个节点自己的子树都已经挂好了
+    fn assemble(nodes: Vec<Box<Self>>, parents: &[(usize, Edge)]) -> Option<Box<Self>> {
+        let mut slots: Vec<Option<Box<Self>>> = nodes.into_iter().map(Some).collect();
+        for (i, &(parent, edge)) in parents.iter().enumerate().skip(1).rev() {
+            let node = slots[i].take();
+            let parent = slots[parent].as_mut().unwrap();
+            match edge {
+                Edge::Left => parent.left = node,
+                Edge::Right => parent.right = node,
+                Edge::Root => unreachable!("只有第一个节点是根"),
+            }
+        }
+        slots.into_iter().next().flatten()
+    }
+
+    /// 由前序和中序序列重建一般的二叉树 (不要求是搜索树), 值不能重复
+    fn from_preorder_inorder(
+        preorder: Vec<T>,
+        inorder: &[T],
+    ) -> Result<Option<Box<Self>>, DecodeError> {
+        Self::from_traversals(preorder, inorder.iter(), inorder, Edge::Left, Edge::Right)
+    }
+
+    /// 由后序和中序序列重建一般的二叉树, 值不能重复
+    /// 倒过来的后序 (根, 右, 左) 是镜像树的前序, 倒过来的中序是镜像树的中序
+    fn from_postorder_inorder(
+        mut postorder: Vec<T>,
+        inorder: &[T],
+    ) -> Result<Option<Box<Self>>, DecodeError> {
+        postorder.reverse();
+        Self::from_traversals(
+            postorder,
+            inorder.iter().rev(),
+            inorder,
+            Edge::Right,
+            Edge::Left,
+        )
+    }
+
+    /// order 是前序 (或镜像树的前序), seq 是与之对应的中序
+    /// 栈里是还没有结束的节点; 栈顶等于中序的下一个值时, 说明它 near 一侧的子树已经结束,
+    /// 弹出所有这样的节点, 下一个值就是最后弹出的那个 far 一侧的孩子
+    fn from_traversals<'a>(
+        order: Vec<T>,
+        mut seq: impl Iterator<Item = &'a T>,
+        inorder: &[T],
+        near: Edge,
+        far: Edge,
+    ) -> Result<Option<Box<Self>>, DecodeError>
+    where
+        T: 'a,
+    {
+        if order.len() != inorder.len() {
+            return Err(DecodeError::MismatchedTraversals);
+        }
+        let mut parents = Vec::with_capacity(order.len());
+        let mut stack: Vec<usize> = Vec::new();
+        let mut next = seq.next();
+        for i in 0..order.len() {
+            match stack.last() {
+                None => parents.push((0, Edge::Root)),
+                Some(&top) if Some(&order[top]) != next => parents.push((top, near)),
+                Some(_) => {
+                    let mut parent = 0;
+                    while let Some(&top) = stack.last()
+                        && Some(&order[top]) == next
+                    {
+                        parent = top;
+                        stack.pop();
+                        next = seq.next();
+                    }
+                    parents.push((parent, far));
+                }
+            }
+            stack.push(i);
+        }
+        let nodes = order
+            .into_iter()
+            .map(|value| Box::new(TreeNode::new(value)))
+            .collect();
+        let root = Self::assemble(nodes, &parents);
+        // 前序由构造过程保证, 中序对得上才说明两个序列描述的是同一棵树
+        let mut iter = InOrder { stack: Vec::new() };
+        iter.push_left(root.as_deref());
+        if !iter.eq(inorder) {
+            return Err(DecodeError::MismatchedTraversals);
+        }
+        Ok(root)
     }
 }
 
@@ -106,11 +1201,19 @@ where
 mod tests {
     use super::*;
 
+    // 简单的线性同余生成器
+    fn lcg(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
     #[test]
     fn test_insert_and_search() {
         let mut bst = BinarySearchTree::new();
 
-        assert_eq!(bst.search(1), false);
+        assert_eq!(bst.search(&1), false);
 
         bst.insert(5);
         bst.insert(3);
@@ -118,14 +1221,14 @@ mod tests {
         bst.insert(2);
         bst.insert(4);
 
-        assert_eq!(bst.search(5), true);
-        assert_eq!(bst.search(3), true);
-        assert_eq!(bst.search(7), true);
-        assert_eq!(bst.search(2), true);
-        assert_eq!(bst.search(4), true);
+        assert_eq!(bst.search(&5), true);
+        assert_eq!(bst.search(&3), true);
+        assert_eq!(bst.search(&7), true);
+        assert_eq!(bst.search(&2), true);
+        assert_eq!(bst.search(&4), true);
 
-        assert_eq!(bst.search(1), false);
-        assert_eq!(bst.search(6), false);
+        assert_eq!(bst.search(&1), false);
+        assert_eq!(bst.search(&6), false);
     }
 
     #[test]
@@ -135,7 +1238,7 @@ mod tests {
         bst.insert(1);
         bst.insert(1);
 
-        assert_eq!(bst.search(1), true);
+        assert_eq!(bst.search(&1), true);
 
         match bst.root {
             Some(ref node) => {
@@ -145,4 +1248,666 @@ mod tests {
             None => panic!("Root should not be None after insertion"),
         }
     }
+
+    #[test]
+    fn test_duplicate_policies() {
+        // 默认拒绝, 把值退回
+        let mut bst = BinarySearchTree::new();
+        assert_eq!(bst.insert(1), InsertOutcome::Inserted);
+        assert_eq!(bst.insert(1), InsertOutcome::Rejected(1));
+        assert_eq!(bst.len(), 1);
+
+        // 替换: 用只按 key 比较的值观察替换效果
+        #[derive(Debug)]
+        struct Item(i32, &'static str);
+        impl PartialEq for Item {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Item {}
+        impl PartialOrd for Item {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Item {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+        let mut bst = BinarySearchTree::with_policy(DuplicatePolicy::Replace);
+        bst.insert(Item(1, "old"));
+        match bst.insert(Item(1, "new")) {
+            InsertOutcome::Replaced(old) => assert_eq!(old.1, "old"),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(bst.min().unwrap().1, "new");
+        assert_eq!(bst.len(), 1);
+
+        // 多重集合
+        let mut bst = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        for x in [5, 3, 5, 8, 5, 3, 8] {
+            bst.insert(x);
+        }
+        assert_eq!(bst.insert(5), InsertOutcome::Counted(4));
+        assert_eq!(bst.count(&5), 4);
+        assert_eq!(bst.count(&3), 2);
+        assert_eq!(bst.count(&8), 2);
+        assert_eq!(bst.count(&4), 0);
+        assert_eq!(bst.len(), 3);
+        // 删除一次只去掉一个副本
+        assert_eq!(bst.remove(&5), Some(RemoveOutcome::Decremented(3)));
+        assert_eq!(bst.count(&5), 3);
+        assert_eq!(bst.len(), 3);
+        // remove_all 去掉所有副本; 删除有两个孩子的 5 后, 后继 8 的重数跟着移动
+        assert_eq!(bst.remove_all(&5), Some(5));
+        assert_eq!(bst.count(&5), 0);
+        assert_eq!(bst.count(&8), 2);
+        assert_eq!(bst.remove_all(&5), None);
+        assert_eq!(bst.len(), 2);
+        // 重数减到 1 之后再删除才摘掉节点
+        assert_eq!(bst.remove(&3), Some(RemoveOutcome::Decremented(1)));
+        assert_eq!(bst.remove(&3), Some(RemoveOutcome::Removed(3)));
+        assert_eq!(bst.remove(&3), None);
+        assert_eq!(bst.count(&3), 0);
+        assert_eq!(bst.len(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut bst = BinarySearchTree::new();
+        for x in [50, 30, 70, 20, 40, 60, 80, 35, 45, 65] {
+            bst.insert(x);
+        }
+        assert_eq!(bst.len(), 10);
+
+        // 两个孩子, 后继 35 是右子树的最小值
+        assert_eq!(bst.remove(&30), Some(RemoveOutcome::Removed(30)));
+        assert_eq!(bst.root.as_ref().unwrap().left.as_ref().unwrap().value, 35);
+        // 叶子
+        assert_eq!(bst.remove(&20), Some(RemoveOutcome::Removed(20)));
+        // 只有一个孩子
+        assert_eq!(bst.remove(&60), Some(RemoveOutcome::Removed(60)));
+        assert_eq!(
+            bst.root
+                .as_ref()
+                .unwrap()
+                .right
+                .as_ref()
+                .unwrap()
+                .left
+                .as_ref()
+                .unwrap()
+                .value,
+            65
+        );
+        // 根节点
+        assert_eq!(bst.remove(&50), Some(RemoveOutcome::Removed(50)));
+        assert_eq!(bst.remove(&50), None);
+        assert_eq!(bst.len(), 6);
+
+        let values: Vec<i32> = bst.iter().copied().collect();
+        assert_eq!(values, vec![35, 40, 45, 65, 70, 80]);
+
+        for x in values {
+            assert_eq!(bst.remove(&x), Some(RemoveOutcome::Removed(x)));
+        }
+        assert!(bst.is_empty() && bst.root.is_none());
+    }
+
+    #[test]
+    fn test_min_max_floor_ceiling() {
+        let mut bst = BinarySearchTree::new();
+        assert_eq!(bst.min(), None);
+        assert_eq!(bst.floor(&1), None);
+        for x in [50, 30, 70, 20, 40, 60, 80] {
+            bst.insert(x);
+        }
+        assert_eq!(bst.min(), Some(&20));
+        assert_eq!(bst.max(), Some(&80));
+
+        assert_eq!(bst.floor(&45), Some(&40));
+        assert_eq!(bst.floor(&40), Some(&40));
+        assert_eq!(bst.floor(&10), None);
+        assert_eq!(bst.ceiling(&45), Some(&50));
+        assert_eq!(bst.ceiling(&50), Some(&50));
+        assert_eq!(bst.ceiling(&90), None);
+
+        assert_eq!(bst.predecessor(&50), Some(&40));
+        assert_eq!(bst.predecessor(&20), None);
+        assert_eq!(bst.successor(&50), Some(&60));
+        assert_eq!(bst.successor(&55), Some(&60));
+        assert_eq!(bst.successor(&80), None);
+    }
+
+    #[test]
+    fn test_range_and_borrow() {
+        let mut bst = BinarySearchTree::new();
+        for x in [50, 30, 70, 20, 40, 60, 80] {
+            bst.insert(x);
+        }
+        assert_eq!(
+            bst.range(30..70).collect::<Vec<_>>(),
+            vec![&30, &40, &50, &60]
+        );
+        assert_eq!(
+            bst.range(31..=70).collect::<Vec<_>>(),
+            vec![&40, &50, &60, &70]
+        );
+        assert_eq!(bst.range(..).count(), 7);
+        assert_eq!(bst.range(81..).count(), 0);
+
+        let mut words = BinarySearchTree::new();
+        for w in ["pear", "apple", "fig", "banana"] {
+            words.insert(w.to_string());
+        }
+        assert!(words.search("fig"));
+        assert_eq!(words.floor("c").map(String::as_str), Some("banana"));
+        let range: Vec<&str> = words
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("g")))
+            .map(String::as_str)
+            .collect();
+        assert_eq!(range, vec!["banana", "fig"]);
+        assert_eq!(
+            words.remove("apple"),
+            Some(RemoveOutcome::Removed("apple".to_string()))
+        );
+        assert_eq!(words.len(), 3);
+    }
+
+    #[test]
+    fn test_traversals() {
+        //       5
+        //     3   7
+        //    2 4    8
+        let bst: BinarySearchTree<i32> = [5, 3, 7, 2, 4, 8].into_iter().collect();
+        assert_eq!(bst.len(), 6);
+        assert_eq!(
+            bst.in_order().copied().collect::<Vec<_>>(),
+            vec![2, 3, 4, 5, 7, 8]
+        );
+        assert_eq!(
+            bst.pre_order().copied().collect::<Vec<_>>(),
+            vec![5, 3, 2, 4, 7, 8]
+        );
+        assert_eq!(
+            bst.post_order().copied().collect::<Vec<_>>(),
+            vec![2, 4, 3, 8, 7, 5]
+        );
+        assert_eq!(
+            bst.level_order().copied().collect::<Vec<_>>(),
+            vec![5, 3, 7, 2, 4, 8]
+        );
+
+        let empty: BinarySearchTree<i32> = BinarySearchTree::new();
+        assert_eq!(empty.iter().count(), 0);
+        assert_eq!(empty.post_order().count(), 0);
+    }
+
+    #[test]
+    fn test_into_iter_and_extend() {
+        let mut bst: BinarySearchTree<String> = ["b", "a"].iter().map(|s| s.to_string()).collect();
+        bst.extend(["d".to_string(), "c".to_string(), "a".to_string()]);
+        assert_eq!(bst.len(), 4);
+
+        let mut borrowed = Vec::new();
+        for s in &bst {
+            borrowed.push(s.as_str());
+        }
+        assert_eq!(borrowed, vec!["a", "b", "c", "d"]);
+
+        let owned: Vec<String> = bst.into_iter().collect();
+        assert_eq!(owned, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_to_ascii_and_dot() {
+        let bst: BinarySearchTree<i32> = [4, 2, 6, 1, 3, 5, 7].into_iter().collect();
+        let expected = concat!(
+            "        /-- 7\n",
+            "    /-- 6\n",
+            "    |   \\-- 5\n",
+            "4\n",
+            "    |   /-- 3\n",
+            "    \\-- 2\n",
+            "        \\-- 1\n",
+        );
+        assert_eq!(bst.to_ascii(), expected);
+
+        // 只有一个孩子时另一边画成点
+        let bst: BinarySearchTree<i32> = [2, 1].into_iter().collect();
+        let expected = concat!(
+            "digraph {\n",
+            "    n0 [label=\"2\"];\n",
+            "    n1 [label=\"1\"];\n",
+            "    n0 -> n1;\n",
+            "    n2 [shape=point];\n",
+            "    n0 -> n2;\n",
+            "}\n",
+        );
+        assert_eq!(bst.to_dot(), expected);
+
+        // 引号要转义, 重数会标出来
+        let mut bst = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        bst.insert("say \"hi\"".to_string());
+        bst.insert("say \"hi\"".to_string());
+        assert_eq!(bst.to_ascii(), "say \"hi\" (x2)\n");
+        assert!(bst.to_dot().contains(r#"n0 [label="say \"hi\" (x2)"];"#));
+        assert_eq!(BinarySearchTree::<i32>::new().to_dot(), "digraph {\n}\n");
+
+        // 退化成链表也不会栈溢出: 每个节点一行, 除了叶子每个节点还有一个空孩子的点和两条边
+        let n = 100_000;
+        let chain = BinarySearchTree::from_preorder(0..n).unwrap();
+        assert_eq!(chain.to_dot().lines().count(), 4 * n as usize - 1);
+        let chain = BinarySearchTree::from_preorder((0..2000).rev()).unwrap();
+        let ascii = chain.to_ascii();
+        assert_eq!(ascii.lines().count(), 2000);
+        assert!(ascii.ends_with(&format!("{}\\-- 0\n", " ".repeat(4 * 1998))));
+    }
+
+    #[test]
+    fn test_sorted_million_keys() {
+        // 有序输入让树退化成一百万层的链表, 插入, 查找, 删除和析构都不能递归
+        // 逐个 insert 一百万个有序值是平方复杂度, 所以先用 from_preorder 线性地建出前面的链,
+        // 最后 TAIL 个值再逐个 insert, 每次都要走到一百万层深的链的末端
+        const N: u32 = 1_000_000;
+        const TAIL: u32 = 100;
+        let mut bst = BinarySearchTree::from_preorder(0..N - TAIL).unwrap();
+        for value in N - TAIL..N {
+            assert_eq!(bst.insert(value), InsertOutcome::Inserted);
+        }
+        assert_eq!(bst.len(), N as usize);
+        assert_eq!(bst.min(), Some(&0));
+        assert_eq!(bst.max(), Some(&(N - 1)));
+        assert_eq!(bst.depth(&(N - 1)), Some(N as usize - 1));
+
+        // 单次插入和查找都要走完整条链
+        assert_eq!(bst.insert(N), InsertOutcome::Inserted);
+        assert_eq!(bst.insert(N - 1), InsertOutcome::Rejected(N - 1));
+        assert!(bst.search(&(N - 1)));
+        assert!(!bst.search(&(N + 1)));
+        assert_eq!(bst.floor(&(N + 5)), Some(&N));
+
+        assert_eq!(bst.remove(&(N / 2)), Some(RemoveOutcome::Removed(N / 2)));
+        assert_eq!(bst.remove(&(N / 2)), None);
+        assert!(!bst.search(&(N / 2)));
+        assert_eq!(bst.len(), N as usize);
+        assert_eq!(bst.iter().count(), N as usize);
+
+        // 只消耗一部分的 IntoIter 也要能安全丢弃
+        let mut iter = bst.into_iter();
+        assert_eq!(iter.next(), Some(0));
+        drop(iter);
+    }
+
+    #[test]
+    fn test_sorted_keys_via_insert() {
+        // 不经过 Extend, 从空树开始逐个 insert 升序和降序的值, 每次插入都走到两万层深的链的末端
+        const N: u32 = 20_000;
+        let mut ascending = BinarySearchTree::new();
+        let mut descending = BinarySearchTree::new();
+        for i in 0..N {
+            assert_eq!(ascending.insert(i), InsertOutcome::Inserted);
+            assert_eq!(descending.insert(N - 1 - i), InsertOutcome::Inserted);
+        }
+        for bst in [&ascending, &descending] {
+            assert_eq!(bst.len(), N as usize);
+            assert_eq!(bst.min(), Some(&0));
+            assert_eq!(bst.max(), Some(&(N - 1)));
+            assert!(bst.search(&(N / 3)));
+            assert!(bst.iter().copied().eq(0..N));
+        }
+        assert_eq!(ascending.insert(N - 1), InsertOutcome::Rejected(N - 1));
+        assert_eq!(descending.remove(&0), Some(RemoveOutcome::Removed(0)));
+        assert_eq!(descending.min(), Some(&1));
+    }
+
+    // 按前序输出结构, 空孩子记为 #, 用来比较两棵树的形状
+    fn shape<T: Ord + Display>(node: &Option<Box<TreeNode<T>>>) -> String {
+        match node {
+            None => "#".to_string(),
+            Some(n) => format!(
+                "({}x{} {} {})",
+                n.value,
+                n.count,
+                shape(&n.left),
+                shape(&n.right)
+            ),
+        }
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut bst = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        let mut seed: u64 = 5;
+        for _ in 0..300 {
+            bst.insert(lcg(&mut seed) as i32 % 200 - 100);
+        }
+        let text = bst.to_preorder_string();
+        assert!(text.contains('*'));
+        let from_text = BinarySearchTree::<i32>::from_preorder_str(&text).unwrap();
+        assert_eq!(shape(&from_text.root), shape(&bst.root));
+        assert_eq!(from_text.len(), bst.len());
+        assert_eq!(from_text.to_preorder_string(), text);
+
+        let bytes = bst.to_bytes();
+        let from_bytes = BinarySearchTree::<i32>::from_bytes(&bytes).unwrap();
+        assert_eq!(shape(&from_bytes.root), shape(&bst.root));
+        assert_eq!(from_bytes.to_bytes(), bytes);
+
+        // 空树
+        let empty: BinarySearchTree<i32> = BinarySearchTree::new();
+        assert_eq!(empty.to_preorder_string(), "");
+        assert!(
+            BinarySearchTree::<i32>::from_bytes(&empty.to_bytes())
+                .unwrap()
+                .is_empty()
+        );
+
+        // 字符串值
+        let words: BinarySearchTree<String> = ["pear", "apple", "plum", "fig"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let decoded = BinarySearchTree::<String>::from_bytes(&words.to_bytes()).unwrap();
+        assert_eq!(shape(&decoded.root), shape(&words.root));
+        assert_eq!(decoded.to_preorder_string(), "pear apple fig plum");
+
+        // 值中的空白、'*'、反斜杠和空值都要转义, 否则会被拆开或者当成重数
+        let mut odd = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        for word in [
+            "a b",
+            "a*2",
+            "a*2",
+            "x\\y",
+            "tab\there",
+            "",
+            "\\u{2a}",
+            "行\u{3000}尾",
+        ] {
+            odd.insert(word.to_string());
+        }
+        let text = odd.to_preorder_string();
+        assert_eq!(
+            text,
+            r"a\u{20}b \e \\u{2a} a\u{2a}2*2 x\\y tab\u{9}here 行\u{3000}尾"
+        );
+        let from_text = BinarySearchTree::<String>::from_preorder_str(&text).unwrap();
+        assert_eq!(shape(&from_text.root), shape(&odd.root));
+        assert_eq!(from_text.to_preorder_string(), text);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            BinarySearchTree::<i32>::from_preorder_str("5 x 7").unwrap_err(),
+            DecodeError::InvalidToken("x".to_string())
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_preorder_str("5 7 6 3").unwrap_err(),
+            DecodeError::OutOfOrder
+        );
+        let bytes = BinarySearchTree::from_preorder([5, 3, 8])
+            .unwrap()
+            .to_bytes();
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            DecodeError::UnexpectedEnd
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[2, 0, 0, 0, 0]).unwrap_err(),
+            DecodeError::InvalidTag(2)
+        );
+
+        // 重数为 0 的节点不存在, 两种格式都拒绝
+        assert_eq!(
+            BinarySearchTree::<i32>::from_preorder_str("5 3*0").unwrap_err(),
+            DecodeError::ZeroCount
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[1 | COUNT_FLAG, 5, 0, 0, 0, 0]).unwrap_err(),
+            DecodeError::ZeroCount
+        );
+        // '*' 后面不是重数, 或者转义写错了
+        assert_eq!(
+            BinarySearchTree::<i32>::from_preorder_str("5 3*x").unwrap_err(),
+            DecodeError::InvalidToken("3*x".to_string())
+        );
+        assert_eq!(
+            BinarySearchTree::<String>::from_preorder_str("a\\q").unwrap_err(),
+            DecodeError::InvalidToken("a\\q".to_string())
+        );
+        assert_eq!(
+            BinarySearchTree::<String>::from_preorder_str("a\\u{d800}").unwrap_err(),
+            DecodeError::InvalidToken("a\\u{d800}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_preorder() {
+        let bst = BinarySearchTree::from_preorder([8, 5, 1, 7, 10, 12]).unwrap();
+        assert_eq!(
+            shape(&bst.root),
+            "(8x1 (5x1 (1x1 # #) (7x1 # #)) (10x1 # (12x1 # #)))"
+        );
+        assert_eq!(bst.len(), 6);
+        // 和按前序逐个插入得到的树相同
+        let mut seed: u64 = 9;
+        let mut inserted = BinarySearchTree::new();
+        for _ in 0..500 {
+            inserted.insert(lcg(&mut seed) >> 7);
+        }
+        let preorder: Vec<u64> = inserted.pre_order().copied().collect();
+        let rebuilt = BinarySearchTree::from_preorder(preorder).unwrap();
+        assert_eq!(shape(&rebuilt.root), shape(&inserted.root));
+
+        // 3 在 2 的右子树中, 之后的 1 不可能出现
+        assert_eq!(
+            BinarySearchTree::from_preorder([2, 3, 1]).unwrap_err(),
+            DecodeError::OutOfOrder
+        );
+        assert_eq!(
+            BinarySearchTree::from_preorder([2, 1, 2]).unwrap_err(),
+            DecodeError::OutOfOrder
+        );
+        // 有序的前序是一条一百万层的链, 重建和析构都不能递归
+        let chain = BinarySearchTree::from_preorder(0..1_000_000).unwrap();
+        assert_eq!(chain.len(), 1_000_000);
+        assert!(chain.search(&999_999));
+    }
+
+    #[test]
+    fn test_from_traversals() {
+        // 不是搜索树:     1
+        //               / \
+        //              2   3
+        //             /   / \
+        //            4   5   6
+        //             \
+        //              7
+        let preorder = vec![1, 2, 4, 7, 3, 5, 6];
+        let inorder = [4, 7, 2, 1, 5, 3, 6];
+        let postorder = vec![7, 4, 2, 5, 6, 3, 1];
+        let expected = "(1x1 (2x1 (4x1 # (7x1 # #)) #) (3x1 (5x1 # #) (6x1 # #)))";
+        let root = TreeNode::from_preorder_inorder(preorder, &inorder).unwrap();
+        assert_eq!(shape(&root), expected);
+        let root = TreeNode::from_postorder_inorder(postorder, &inorder).unwrap();
+        assert_eq!(shape(&root), expected);
+
+        // 对一批随机形状的树, 用遍历结果重建后形状不变
+        let mut seed: u64 = 3;
+        for n in 0..60 {
+            let mut bst = BinarySearchTree::new();
+            for _ in 0..n {
+                bst.insert(lcg(&mut seed));
+            }
+            let inorder: Vec<u64> = bst.in_order().copied().collect();
+            let preorder = bst.pre_order().copied().collect();
+            let postorder = bst.post_order().copied().collect();
+            let from_pre = TreeNode::from_preorder_inorder(preorder, &inorder).unwrap();
+            let from_post = TreeNode::from_postorder_inorder(postorder, &inorder).unwrap();
+            assert_eq!(shape(&from_pre), shape(&bst.root));
+            assert_eq!(shape(&from_post), shape(&bst.root));
+        }
+
+        assert_eq!(
+            TreeNode::from_preorder_inorder(vec![1, 2], &[1, 3]).unwrap_err(),
+            DecodeError::MismatchedTraversals
+        );
+        assert_eq!(
+            TreeNode::from_postorder_inorder(vec![1, 2, 3], &[1, 2]).unwrap_err(),
+            DecodeError::MismatchedTraversals
+        );
+        assert!(
+            TreeNode::<i32>::from_preorder_inorder(vec![], &[])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_hierarchy_queries() {
+        //        8
+        //      /   \
+        //     3     10
+        //    / \      \
+        //   1   6      14
+        //      / \    /
+        //     4   7  13
+        let bst: BinarySearchTree<i32> = [8, 3, 10, 1, 6, 14, 4, 7, 13].into_iter().collect();
+
+        assert_eq!(bst.path_to(&7), Some(vec![&8, &3, &6, &7]));
+        assert_eq!(bst.path_to(&8), Some(vec![&8]));
+        assert_eq!(bst.path_to(&5), None);
+
+        assert_eq!(bst.depth(&8), Some(0));
+        assert_eq!(bst.depth(&4), Some(3));
+        assert_eq!(bst.depth(&13), Some(3));
+        assert_eq!(bst.depth(&0), None);
+
+        assert_eq!(bst.kth_ancestor(&4, 0), Some(&4));
+        assert_eq!(bst.kth_ancestor(&4, 1), Some(&6));
+        assert_eq!(bst.kth_ancestor(&4, 3), Some(&8));
+        assert_eq!(bst.kth_ancestor(&4, 4), None);
+        assert_eq!(bst.kth_ancestor(&4, usize::MAX), None);
+        assert_eq!(bst.kth_ancestor(&5, 0), None);
+
+        assert_eq!(bst.lca(&4, &7), Some(&6));
+        assert_eq!(bst.lca(&1, &7), Some(&3));
+        assert_eq!(bst.lca(&4, &13), Some(&8));
+        assert_eq!(bst.lca(&6, &4), Some(&6));
+        assert_eq!(bst.lca(&10, &10), Some(&10));
+        assert_eq!(bst.lca(&4, &5), None);
+
+        assert_eq!(bst.distance(&4, &7), Some(2));
+        assert_eq!(bst.distance(&1, &7), Some(3));
+        assert_eq!(bst.distance(&4, &13), Some(6));
+        assert_eq!(bst.distance(&6, &4), Some(1));
+        assert_eq!(bst.distance(&14, &14), Some(0));
+        assert_eq!(bst.distance(&0, &14), None);
+
+        let empty = BinarySearchTree::<i32>::new();
+        assert_eq!(empty.path_to(&1), None);
+        assert_eq!(empty.lca(&1, &1), None);
+
+        // 退化成链表也不会栈溢出
+        let n = 1_000_000;
+        let chain = BinarySearchTree::from_preorder(0..n).unwrap();
+        assert_eq!(chain.depth(&(n - 1)), Some(n as usize - 1));
+        assert_eq!(chain.kth_ancestor(&(n - 1), n as usize - 1), Some(&0));
+        assert_eq!(chain.lca(&10, &(n - 1)), Some(&10));
+        assert_eq!(chain.distance(&10, &(n - 1)), Some(n as usize - 11));
+    }
+
+    #[test]
+    fn test_tree_metrics() {
+        let empty = BinarySearchTree::<i32>::new();
+        assert_eq!(empty.height(), 0);
+        assert_eq!(empty.diameter(), 0);
+        assert!(empty.is_balanced());
+        assert!(empty.is_valid_bst());
+
+        let single: BinarySearchTree<i32> = [1].into_iter().collect();
+        assert_eq!(single.height(), 1);
+        assert_eq!(single.diameter(), 0);
+        assert!(single.is_balanced());
+
+        // 与上一个测试同一棵树: 最长路径是 4 到 13, 不平衡的是节点 10
+        let mut bst: BinarySearchTree<i32> = [8, 3, 10, 1, 6, 14, 4, 7, 13].into_iter().collect();
+        assert_eq!(bst.height(), 4);
+        assert_eq!(bst.diameter(), 6);
+        assert!(!bst.is_balanced());
+        assert!(bst.is_valid_bst());
+
+        // 补上 10 的左孩子之后就平衡了
+        bst.insert(9);
+        assert!(bst.is_balanced());
+        assert_eq!(bst.height(), 4);
+
+        // 直径不一定经过根
+        //   1
+        //    \
+        //     10
+        //    /  \
+        //   5    15
+        //  /       \
+        // 3         20
+        let lopsided: BinarySearchTree<i32> = [1, 10, 5, 15, 3, 20].into_iter().collect();
+        assert_eq!(lopsided.height(), 4);
+        assert_eq!(lopsided.diameter(), 4);
+        assert!(!lopsided.is_balanced());
+
+        // 直接改动节点的值, 破坏搜索树的性质
+        let mut broken: BinarySearchTree<i32> = [8, 3, 10, 1, 6].into_iter().collect();
+        assert!(broken.is_valid_bst());
+        let left = broken.root.as_mut().unwrap().left.as_mut().unwrap();
+        left.right.as_mut().unwrap().value = 9;
+        assert!(!broken.is_valid_bst());
+        broken
+            .root
+            .as_mut()
+            .unwrap()
+            .left
+            .as_mut()
+            .unwrap()
+            .right
+            .as_mut()
+            .unwrap()
+            .value = 3;
+        assert!(!broken.is_valid_bst());
+
+        // 完全平衡的树
+        let mut seed = 42u64;
+        let mut balanced = BinarySearchTree::new();
+        let mut keys: Vec<u64> = Vec::new();
+        for _ in 0..1000 {
+            keys.push(lcg(&mut seed));
+        }
+        keys.sort_unstable();
+        keys.dedup();
+        let mut ranges = vec![(0, keys.len())];
+        while let Some((lo, hi)) = ranges.pop() {
+            if lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                balanced.insert(keys[mid]);
+                ranges.push((lo, mid));
+                ranges.push((mid + 1, hi));
+            }
+        }
+        assert!(balanced.is_balanced());
+        assert_eq!(
+            balanced.height(),
+            (usize::BITS - keys.len().leading_zeros()) as usize
+        );
+        assert!(balanced.is_valid_bst());
+
+        // 链表: 高度和直径都等于节点数 (减一)
+        let n = 1_000_000;
+        let chain = BinarySearchTree::from_preorder(0..n).unwrap();
+        assert_eq!(chain.height(), n as usize);
+        assert_eq!(chain.diameter(), n as usize - 1);
+        assert!(!chain.is_balanced());
+        assert!(chain.is_valid_bst());
+    }
 }
@@ -1,7 +1,18 @@
 // 完成基本的二叉搜索树接口
 
+mod common;
+
+use common::Edge;
+use common::codec::{
+    COUNT_FLAG, CountError, Encode, escape_into, preorder_parents, read_varint, split_count,
+    unescape, write_varint,
+};
+use common::render::{RenderNode, dot_escape, write_ascii, write_dot};
+use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::fmt::Debug;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Write};
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq)]
 enum Color {
@@ -9,15 +20,39 @@ enum Color {
     Black,
 }
 
+impl Color {
+    /// 红黑互换
+    fn flip(&mut self) {
+        *self = match self {
+            Color::Red => Color::Black,
+            Color::Black => Color::Red,
+        };
+    }
+}
+
+/// validate() 发现的红黑树性质破坏
+#[derive(Debug, PartialEq)]
+enum RbViolation {
+    RedRoot,             // 根节点为红
+    RightLeaningRed,     // 出现右倾红链接
+    DoubleRed,           // 连续两个红节点
+    SizeMismatch,        // 子树大小记录错误
+    BlackHeightMismatch, // 左右子树黑高不等
+    OutOfOrder,          // 违反二叉搜索树有序性
+}
+
 #[derive(Debug)]
-struct TreeNode<T>
+struct TreeNode<T, V = ()>
 where
     T: Ord,
 {
     value: T,
-    left: Option<Box<TreeNode<T>>>,
-    right: Option<Box<TreeNode<T>>>,
+    left: Option<Box<TreeNode<T, V>>>,
+    right: Option<Box<TreeNode<T, V>>>,
     color: Color, // 新增颜色标记
+    size: usize,  // 子树中的节点数 (含自身), 用于 rank/select
+    count: usize, // 重数, 只有 DuplicatePolicy::Count 会大于 1
+    data: V,      // 跟着值一起存放的数据, 集合中为 (), 有序映射中 value 是键, data 是键对应的值
 }
 
 #[derive(Debug)]
@@ -26,6 +61,31 @@ where
     T: Ord,
 {
     root: Option<Box<TreeNode<T>>>,
+    policy: DuplicatePolicy,
+}
+
+/// 插入重复值时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DuplicatePolicy {
+    Reject,  // 拒绝插入, 把新值还给调用方
+    Replace, // 用新值替换旧值
+    Count,   // 多重集合: 节点上的重数加一, 子树大小和遍历仍按不同的值计算
+}
+
+/// insert 的结果
+#[derive(Debug, PartialEq)]
+enum InsertOutcome<T> {
+    Inserted,       // 插入了新节点
+    Rejected(T),    // 值已存在, 原样退回
+    Replaced(T),    // 值已存在, 返回被替换掉的旧值
+    Counted(usize), // 值已存在, 返回新的重数
+}
+
+/// 删除的结果
+#[derive(Debug, PartialEq)]
+enum RemoveOutcome<T> {
+    Removed(T),         // 摘掉了节点, 返回其中的值
+    Decremented(usize), // 多重集合中重数大于 1, 只减一, 返回剩下的重数
 }
 
 impl<T> TreeNode<T>
@@ -33,11 +93,23 @@ where
     T: Ord,
 {
     fn new(value: T) -> Self {
+        Self::with_data(value, ())
+    }
+}
+
+impl<T, V> TreeNode<T, V>
+where
+    T: Ord,
+{
+    fn with_data(value: T, data: V) -> Self {
         TreeNode {
             value,
             left: None,
             right: None,
             color: Color::Red, // 新节点默认为红色
+            size: 1,
+            count: 1,
+            data,
         }
     }
 }
@@ -47,31 +119,39 @@ where
     T: Ord,
 {
     fn new() -> Self {
-        BinarySearchTree { root: None }
+        Self::with_policy(DuplicatePolicy::Reject)
+    }
+
+    /// 指定重复值的处理方式
+    fn with_policy(policy: DuplicatePolicy) -> Self {
+        BinarySearchTree { root: None, policy }
     }
 
     // Insert a value into the BST
-    fn insert(&mut self, value: T) {
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
         //TODO
         if let Some(ref mut root) = self.root {
-            root.insert(value);
+            root.insert(value, self.policy)
         } else {
             self.root = Some(Box::new(TreeNode::new(value)));
+            InsertOutcome::Inserted
         }
     }
 
-    /// 公开的插入接口
-    pub fn insert_rb(&mut self, value: T) {
+    /// 公开的插入接口, 值已存在时按 policy 处理, 返回实际发生了什么
+    pub fn insert_rb(&mut self, value: T) -> InsertOutcome<T> {
         match self.root.take() {
             Some(root) => {
-                let mut new_root = root.insert_rb(value);
+                let (mut new_root, outcome) = root.insert_rb(value, self.policy);
                 new_root.color = Color::Black; // 根节点始终为黑
                 self.root = Some(new_root);
+                outcome
             }
             None => {
                 let mut node = TreeNode::new(value);
                 node.color = Color::Black; // 根节点强制为黑
                 self.root = Some(Box::new(node));
+                InsertOutcome::Inserted
             }
         }
     }
@@ -81,6 +161,167 @@ where
         //TODO
         self.root.as_ref().map_or(false, |root| root.search(value))
     }
+
+    /// 按引用查找, 供删除前判断是否存在
+    fn contains(&self, value: &T) -> bool {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match value.cmp(&node.value) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    // 找到值所在的节点
+    fn find_mut(&mut self, value: &T) -> Option<&mut TreeNode<T>> {
+        let mut current = self.root.as_deref_mut();
+        while let Some(node) = current {
+            current = match value.cmp(&node.value) {
+                Ordering::Less => node.left.as_deref_mut(),
+                Ordering::Greater => node.right.as_deref_mut(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    /// 值的重数, 不存在时为 0
+    fn count(&self, value: &T) -> usize {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match value.cmp(&node.value) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return node.count,
+            };
+        }
+        0
+    }
+
+    /// 删除最小值, 树为空时返回 None
+    /// 和 delete 一样, 多重集合中重数大于 1 时只把重数减一
+    pub fn delete_min(&mut self) -> Option<RemoveOutcome<T>> {
+        let mut node = self.root.as_deref_mut()?;
+        while node.left.is_some() {
+            node = node.left.as_deref_mut().unwrap();
+        }
+        if node.count > 1 {
+            node.count -= 1;
+            return Some(RemoveOutcome::Decremented(node.count));
+        }
+        let mut root = self.root.take()?;
+        // 根的两个孩子都是黑色时先把根染红, 下行时才有红链接可借
+        if !TreeNode::is_red(&root.left) && !TreeNode::is_red(&root.right) {
+            root.color = Color::Red;
+        }
+        let (root, min) = root.delete_min();
+        self.root = root;
+        self.paint_root_black();
+        Some(RemoveOutcome::Removed(min.value))
+    }
+
+    /// 删除最大值, 重数的处理和 delete_min 相同
+    pub fn delete_max(&mut self) -> Option<RemoveOutcome<T>> {
+        let mut node = self.root.as_deref_mut()?;
+        while node.right.is_some() {
+            node = node.right.as_deref_mut().unwrap();
+        }
+        if node.count > 1 {
+            node.count -= 1;
+            return Some(RemoveOutcome::Decremented(node.count));
+        }
+        let mut root = self.root.take()?;
+        if !TreeNode::is_red(&root.left) && !TreeNode::is_red(&root.right) {
+            root.color = Color::Red;
+        }
+        let (root, max) = root.delete_max();
+        self.root = root;
+        self.paint_root_black();
+        Some(RemoveOutcome::Removed(max))
+    }
+
+    /// 删除指定值, 不存在时返回 None 且树保持不变
+    /// 多重集合中重数大于 1 时只把重数减一, 树的结构不变; 要一次删掉所有副本用 delete_all
+    pub fn delete(&mut self, value: &T) -> Option<RemoveOutcome<T>> {
+        let node = self.find_mut(value)?;
+        if node.count > 1 {
+            node.count -= 1;
+            return Some(RemoveOutcome::Decremented(node.count));
+        }
+        self.delete_all(value).map(RemoveOutcome::Removed)
+    }
+
+    /// 删除值所在的节点, 不管重数是多少, 返回节点中的值
+    pub fn delete_all(&mut self, value: &T) -> Option<T> {
+        // 下行过程会沿途调整结构, 必须先确认值存在
+        if !self.contains(value) {
+            return None;
+        }
+        let (removed, ()) = TreeNode::delete_from_root(&mut self.root, value);
+        Some(removed)
+    }
+
+    /// 删除后根节点重新染黑
+    fn paint_root_black(&mut self) {
+        if let Some(root) = self.root.as_mut() {
+            root.color = Color::Black;
+        }
+    }
+
+    /// 检查左倾红黑树的全部性质, 返回第一个被破坏的性质
+    pub fn validate(&self) -> Result<(), RbViolation> {
+        match &self.root {
+            None => Ok(()),
+            Some(root) if root.color == Color::Red => Err(RbViolation::RedRoot),
+            Some(root) => root.validate(None, None).map(|_| ()),
+        }
+    }
+
+    /// 比 value 小的元素个数, value 不在树中也可以查询
+    pub fn rank(&self, value: &T) -> usize {
+        let mut rank = 0;
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            match value.cmp(&node.value) {
+                Ordering::Less => current = node.left.as_deref(),
+                Ordering::Greater => {
+                    // 左子树和当前节点都比 value 小
+                    rank += TreeNode::size(&node.left) + 1;
+                    current = node.right.as_deref();
+                }
+                Ordering::Equal => return rank + TreeNode::size(&node.left),
+            }
+        }
+        rank
+    }
+
+    /// 第 k 小的元素 (从 0 开始), 越界返回 None
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            let left = TreeNode::size(&node.left);
+            match k.cmp(&left) {
+                Ordering::Less => current = node.left.as_deref(),
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Greater => {
+                    k -= left + 1;
+                    current = node.right.as_deref();
+                }
+            }
+        }
+        None
+    }
+
+    /// 闭区间 [a, b] 内的元素个数
+    pub fn count_range(&self, a: &T, b: &T) -> usize {
+        if a > b {
+            return 0;
+        }
+        self.rank(b) - self.rank(a) + usize::from(self.contains(b))
+    }
 }
 
 impl<T> TreeNode<T>
@@ -88,26 +329,41 @@ where
     T: Ord,
 {
     // Insert a node into the tree
-    fn insert(&mut self, value: T) {
+    fn insert(&mut self, value: T, policy: DuplicatePolicy) -> InsertOutcome<T> {
         //TODO
-        match value.cmp(&self.value) {
+        let outcome = match value.cmp(&self.value) {
             Ordering::Less => {
                 if let Some(ref mut left) = self.left {
-                    left.insert(value);
+                    left.insert(value, policy)
                 } else {
                     self.left = Some(Box::new(TreeNode::new(value)));
+                    InsertOutcome::Inserted
                 }
             }
             Ordering::Greater => {
                 if let Some(ref mut right) = self.right {
-                    right.insert(value);
+                    right.insert(value, policy)
                 } else {
                     self.right = Some(Box::new(TreeNode::new(value)));
+                    InsertOutcome::Inserted
                 }
             }
-            Ordering::Equal => {
-                // 重复值处理: 这里我们选择不插入重复值
-                // 也可以根据需求选择其他处理方式
+            Ordering::Equal => self.on_duplicate(value, policy),
+        };
+        self.update_size();
+        outcome
+    }
+
+    /// 重复值处理: 按策略拒绝, 替换或者累加重数
+    fn on_duplicate(&mut self, value: T, policy: DuplicatePolicy) -> InsertOutcome<T> {
+        match policy {
+            DuplicatePolicy::Reject => InsertOutcome::Rejected(value),
+            DuplicatePolicy::Replace => {
+                InsertOutcome::Replaced(std::mem::replace(&mut self.value, value))
+            }
+            DuplicatePolicy::Count => {
+                self.count += 1;
+                InsertOutcome::Counted(self.count)
             }
         }
     }
@@ -125,45 +381,89 @@ where
         }
     }
 
+    /// 红黑树插入入口
+    pub fn insert_rb(
+        mut self: Box<Self>,
+        value: T,
+        policy: DuplicatePolicy,
+    ) -> (Box<Self>, InsertOutcome<T>) {
+        let outcome = match value.cmp(&self.value) {
+            Ordering::Less => match self.left.take() {
+                Some(left) => {
+                    let (left, outcome) = left.insert_rb(value, policy);
+                    self.left = Some(left);
+                    outcome
+                }
+                None => {
+                    self.left = Some(Box::new(Self::new(value)));
+                    InsertOutcome::Inserted
+                }
+            },
+            Ordering::Greater => match self.right.take() {
+                Some(right) => {
+                    let (right, outcome) = right.insert_rb(value, policy);
+                    self.right = Some(right);
+                    outcome
+                }
+                None => {
+                    self.right = Some(Box::new(Self::new(value)));
+                    InsertOutcome::Inserted
+                }
+            },
+            // 重复值按策略处理, 结构不变
+            Ordering::Equal => {
+                let outcome = self.on_duplicate(value, policy);
+                return (self, outcome);
+            }
+        };
+        (self.fixup(None), outcome) // 插入后修复红黑树性质
+    }
+}
+
+// 下面的旋转, 修复, 删除和检查对集合和有序映射通用, data 跟着所在的节点一起移动
+
+impl<T, V> TreeNode<T, V>
+where
+    T: Ord,
+{
+    /// 子树大小
+    fn size(node: &Option<Box<Self>>) -> usize {
+        node.as_ref().map_or(0, |n| n.size)
+    }
+
+    /// 根据左右孩子重新计算子树大小
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
     /// 判断节点是否为红色 (空节点视为黑色)
     fn is_red(node: &Option<Box<Self>>) -> bool {
         node.as_ref().map_or(false, |n| n.color == Color::Red)
     }
 
-    /// 红黑树插入入口
-    pub fn insert_rb(mut self: Box<Self>, value: T) -> Box<Self> {
-        match value.cmp(&self.value) {
-            Ordering::Less => {
-                self.left = match self.left {
-                    Some(left) => Some(left.insert_rb(value)),
-                    None => Some(Box::new(Self::new(value))),
-                };
-            }
-            Ordering::Greater => {
-                self.right = match self.right {
-                    Some(right) => Some(right.insert_rb(value)),
-                    None => Some(Box::new(Self::new(value))),
-                };
-            }
-            Ordering::Equal => return self, // 重复值不插入
-        }
-        self.fixup() // 插入后修复红黑树性质
-    }
-
     /// 修复红黑树性质的三种情况
-    fn fixup(mut self: Box<Self>) -> Box<Self> {
+    /// path 是从这棵子树的根到某个节点的路径 (有序映射插入时用来找回新节点), 随旋转一起调整
+    fn fixup(mut self: Box<Self>, mut path: Option<&mut Path>) -> Box<Self> {
         // 右子红且左子黑
         if Self::is_red(&self.right) && !Self::is_red(&self.left) {
             self = self.rotate_left();
+            if let Some(path) = path.as_deref_mut() {
+                path.lift(Side::Right);
+            }
         }
         // 左子红且左子的左子红
         if Self::is_red(&self.left) && Self::is_red(&self.left.as_ref().unwrap().left) {
             self = self.rotate_right();
+            if let Some(path) = path {
+                path.lift(Side::Left);
+            }
         }
         // 左右子均红
         if Self::is_red(&self.left) && Self::is_red(&self.right) {
             self.flip_colors();
         }
+        // 插入和删除回溯时孩子可能变了, 都在这里重新计算大小
+        self.update_size();
         self
     }
 
@@ -173,7 +473,10 @@ where
         self.right = new_root.left.take();
         new_root.color = self.color;
         self.color = Color::Red;
+        // 先更新下沉的旧根, 再更新新根
+        self.update_size();
         new_root.left = Some(self);
+        new_root.update_size();
         new_root
     }
 
@@ -183,85 +486,1678 @@ where
         self.left = new_root.right.take();
         new_root.color = self.color;
         self.color = Color::Red;
+        self.update_size();
         new_root.right = Some(self);
+        new_root.update_size();
         new_root
     }
 
     /// 颜色翻转
+    /// 插入时把红色上推 (父黑子红 -> 父红子黑), 删除时反向把红色下借, 三个节点同时取反
     fn flip_colors(&mut self) {
-        self.color = match self.color {
-            Color::Red => Color::Black,
-            Color::Black => Color::Red,
+        self.color.flip();
+        self.left.as_mut().unwrap().color.flip();
+        self.right.as_mut().unwrap().color.flip();
+    }
+
+    /// 保证左孩子或左孩子的左孩子为红, 从右兄弟借节点
+    fn move_red_left(mut self: Box<Self>) -> Box<Self> {
+        self.flip_colors();
+        // 右孩子的左孩子为红, 可以借一个节点过来, 再把多余的红色推回
+        if Self::is_red(&self.right.as_ref().unwrap().left) {
+            self.right = Some(self.right.take().unwrap().rotate_right());
+            self = self.rotate_left();
+            self.flip_colors();
+        }
+        self
+    }
+
+    /// 保证右孩子或右孩子的左孩子为红, 从左兄弟借节点
+    fn move_red_right(mut self: Box<Self>) -> Box<Self> {
+        self.flip_colors();
+        if Self::is_red(&self.left.as_ref().unwrap().left) {
+            self = self.rotate_right();
+            self.flip_colors();
+        }
+        self
+    }
+
+    /// 摘下子树中的最小节点, 返回新的子树根和该节点
+    fn delete_min(mut self: Box<Self>) -> (Option<Box<Self>>, Box<Self>) {
+        // 左倾红黑树中没有左孩子的节点也不会有右孩子
+        if self.left.is_none() {
+            return (None, self);
+        }
+        // 左侧是 2-节点, 下行前先借一个红链接
+        if !Self::is_red(&self.left) && !Self::is_red(&self.left.as_ref().unwrap().left) {
+            self = self.move_red_left();
+        }
+        let (left, min) = self.left.take().unwrap().delete_min();
+        self.left = left;
+        (Some(self.fixup(None)), min)
+    }
+
+    /// 删除子树中的最大值, 返回新的子树根和被删除的值
+    fn delete_max(mut self: Box<Self>) -> (Option<Box<Self>>, T) {
+        // 左倾的红链接先转到右边, 最大值才能从右侧删除
+        if Self::is_red(&self.left) {
+            self = self.rotate_right();
+        }
+        if self.right.is_none() {
+            return (None, self.value);
+        }
+        if !Self::is_red(&self.right) && !Self::is_red(&self.right.as_ref().unwrap().left) {
+            self = self.move_red_right();
+        }
+        let (right, max) = self.right.take().unwrap().delete_max();
+        self.right = right;
+        (Some(self.fixup(None)), max)
+    }
+
+    /// 从整棵树中删除指定值 (调用方保证该值存在), 返回被删除的值和数据
+    /// 根的两个孩子都是黑色时先把根染红, 下行时才有红链接可借, 删除后再把根染黑
+    fn delete_from_root<Q>(root: &mut Option<Box<Self>>, value: &Q) -> (T, V)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = root.take().unwrap();
+        if !Self::is_red(&node.left) && !Self::is_red(&node.right) {
+            node.color = Color::Red;
+        }
+        let (node, removed) = node.delete(value);
+        *root = node;
+        if let Some(node) = root.as_mut() {
+            node.color = Color::Black;
+        }
+        removed
+    }
+
+    /// 删除子树中的指定值 (调用方保证该值存在)
+    /// 有序映射按借用的键查找, 所以值的类型是 Q
+    fn delete<Q>(mut self: Box<Self>, value: &Q) -> (Option<Box<Self>>, (T, V))
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed;
+        if value < self.value.borrow() {
+            if !Self::is_red(&self.left) && !Self::is_red(&self.left.as_ref().unwrap().left) {
+                self = self.move_red_left();
+            }
+            let (left, value) = self.left.take().unwrap().delete(value);
+            self.left = left;
+            removed = value;
+        } else {
+            if Self::is_red(&self.left) {
+                self = self.rotate_right();
+            }
+            // 命中且没有右孩子, 说明是叶子, 直接删除
+            if value == self.value.borrow() && self.right.is_none() {
+                return (None, (self.value, self.data));
+            }
+            if !Self::is_red(&self.right) && !Self::is_red(&self.right.as_ref().unwrap().left) {
+                self = self.move_red_right();
+            }
+            if value == self.value.borrow() {
+                // 用右子树的最小值 (后继) 替换当前节点, 重数和数据一起搬过来
+                let (right, successor) = self.right.take().unwrap().delete_min();
+                self.right = right;
+                self.count = successor.count;
+                removed = (
+                    std::mem::replace(&mut self.value, successor.value),
+                    std::mem::replace(&mut self.data, successor.data),
+                );
+            } else {
+                let (right, value) = self.right.take().unwrap().delete(value);
+                self.right = right;
+                removed = value;
+            }
+        }
+        (Some(self.fixup(None)), removed)
+    }
+
+    /// 递归检查子树, 返回黑高; lower/upper 为祖先给出的开区间边界
+    fn validate(&self, lower: Option<&T>, upper: Option<&T>) -> Result<usize, RbViolation> {
+        if lower.is_some_and(|l| self.value <= *l) || upper.is_some_and(|u| self.value >= *u) {
+            return Err(RbViolation::OutOfOrder);
+        }
+        if Self::is_red(&self.right) {
+            return Err(RbViolation::RightLeaningRed);
+        }
+        if self.color == Color::Red && Self::is_red(&self.left) {
+            return Err(RbViolation::DoubleRed);
+        }
+        let left = match &self.left {
+            Some(left) => left.validate(lower, Some(&self.value))?,
+            None => 0,
         };
-        self.left.as_mut().unwrap().color = Color::Black;
-        self.right.as_mut().unwrap().color = Color::Black;
+        let right = match &self.right {
+            Some(right) => right.validate(Some(&self.value), upper)?,
+            None => 0,
+        };
+        if left != right {
+            return Err(RbViolation::BlackHeightMismatch);
+        }
+        if self.size != 1 + Self::size(&self.left) + Self::size(&self.right) {
+            return Err(RbViolation::SizeMismatch);
+        }
+        Ok(left + usize::from(self.color == Color::Black))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// 遍历
+// 都用显式的栈或队列实现, 不会因为树太深而栈溢出
 
-    #[test]
-    fn test_insert_and_search() {
-        let mut bst = BinarySearchTree::new();
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 中序遍历, 也就是从小到大
+    fn iter(&self) -> InOrder<'_, T> {
+        self.in_order()
+    }
 
-        assert_eq!(bst.search(1), false);
+    /// 中序遍历: 左子树, 根, 右子树
+    fn in_order(&self) -> InOrder<'_, T> {
+        let mut iter = InOrder { stack: Vec::new() };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
 
-        bst.insert(5);
-        bst.insert(3);
-        bst.insert(7);
-        bst.insert(2);
-        bst.insert(4);
+    /// 前序遍历: 根, 左子树, 右子树
+    fn pre_order(&self) -> PreOrder<'_, T> {
+        PreOrder {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
 
-        assert_eq!(bst.search(5), true);
-        assert_eq!(bst.search(3), true);
-        assert_eq!(bst.search(7), true);
-        assert_eq!(bst.search(2), true);
-        assert_eq!(bst.search(4), true);
+    /// 后序遍历: 左子树, 右子树, 根
+    fn post_order(&self) -> PostOrder<'_, T> {
+        PostOrder {
+            stack: self
+                .root
+                .as_deref()
+                .map(|n| (n, false))
+                .into_iter()
+                .collect(),
+        }
+    }
 
-        assert_eq!(bst.search(1), false);
-        assert_eq!(bst.search(6), false);
+    /// 层序遍历: 按层从上到下, 每层从左到右
+    fn level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder {
+            queue: self.root.as_deref().into_iter().collect(),
+        }
     }
+}
 
-    #[test]
-    fn test_insert_duplicate() {
-        let mut bst = BinarySearchTree::new();
+/// 中序遍历的迭代器
+/// 栈里是还没有返回的节点, 它们的左子树都已经处理完或者正在处理
+struct InOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+}
 
-        bst.insert(1);
-        bst.insert(1);
+impl<'a, T> InOrder<'a, T>
+where
+    T: Ord,
+{
+    // 从 node 开始一路向左, 沿途的节点都压栈
+    fn push_left(&mut self, mut node: Option<&'a TreeNode<T>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
 
-        assert_eq!(bst.search(1), true);
+impl<'a, T> Iterator for InOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
 
-        match bst.root {
-            Some(ref node) => {
-                assert!(node.left.is_none());
-                assert!(node.right.is_none());
-            }
-            None => panic!("Root should not be None after insertion"),
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        // 左子树已经访问完, 接下来是右子树中最小的节点
+        self.push_left(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+/// 前序遍历的迭代器
+struct PreOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<&'a TreeNode<T>>,
+}
+
+impl<'a, T> Iterator for PreOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        // 先压右再压左, 这样左子树先出栈
+        if let Some(right) = node.right.as_deref() {
+            self.stack.push(right);
+        }
+        if let Some(left) = node.left.as_deref() {
+            self.stack.push(left);
         }
+        Some(&node.value)
     }
+}
 
-    #[test]
-    fn test_rb_insert() {
-        let mut tree = BinarySearchTree::new();
-        tree.insert_rb(3);
-        tree.insert_rb(1);
-        tree.insert_rb(5);
-        tree.insert_rb(2);
+/// 后序遍历的迭代器
+/// 节点第一次出栈时还不能返回, 要先把它标记为已展开再压回去, 然后压入两个子树
+/// 第二次出栈时, 它的两个子树都已经访问完了
+struct PostOrder<'a, T>
+where
+    T: Ord,
+{
+    stack: Vec<(&'a TreeNode<T>, bool)>,
+}
 
-        // 验证根节点为黑
-        assert_eq!(tree.root.as_ref().unwrap().color, Color::Black);
+impl<'a, T> Iterator for PostOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
 
-        // 验证无连续红节点
-        fn check_rb_properties<T: Ord>(node: &Option<Box<TreeNode<T>>>) -> bool {
-            node.as_ref().map_or(true, |n| {
-                let no_double_red = n.color != Color::Red ||
-                    (TreeNode::is_red(&n.left) == false &&
-                    TreeNode::is_red(&n.right) == false);
-                no_double_red && check_rb_properties(&n.left) && check_rb_properties(&n.right)
-            })
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(&node.value);
+            }
+            self.stack.push((node, true));
+            if let Some(right) = node.right.as_deref() {
+                self.stack.push((right, false));
+            }
+            if let Some(left) = node.left.as_deref() {
+                self.stack.push((left, false));
+            }
         }
-        assert!(check_rb_properties(&tree.root));
+    }
+}
+
+/// 层序遍历的迭代器, 就是树上的 BFS
+struct LevelOrder<'a, T>
+where
+    T: Ord,
+{
+    queue: VecDeque<&'a TreeNode<T>>,
+}
+
+impl<'a, T> Iterator for LevelOrder<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.left.as_deref());
+        self.queue.extend(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
+/// 按从小到大的顺序取出所有值的迭代器, 会消耗整棵树
+struct IntoIter<T>
+where
+    T: Ord,
+{
+    stack: Vec<Box<TreeNode<T>>>,
+}
+
+impl<T> IntoIter<T>
+where
+    T: Ord,
+{
+    // 和 InOrder::push_left 一样, 只是这里拿走了左子树的所有权
+    fn push_left(&mut self, mut node: Option<Box<TreeNode<T>>>) {
+        while let Some(mut n) = node {
+            node = n.left.take();
+            self.stack.push(n);
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T>
+where
+    T: Ord,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut node = self.stack.pop()?;
+        self.push_left(node.right.take());
+        Some(node.value)
+    }
+}
+
+impl<T> IntoIterator for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        let mut iter = IntoIter { stack: Vec::new() };
+        iter.push_left(self.root);
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BinarySearchTree<T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+    type IntoIter = InOrder<'a, T>;
+
+    fn into_iter(self) -> InOrder<'a, T> {
+        self.in_order()
+    }
+}
+
+impl<T> FromIterator<T> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = BinarySearchTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<T> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert_rb(value);
+        }
+    }
+}
+
+// 可视化
+// 画法在 common/render.rs 里, 这里只决定节点上显示什么
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord + Display,
+{
+    /// 横向打印: 根在最左边, 右子树画在上面, 左子树画在下面
+    pub fn to_ascii(&self) -> String {
+        let mut out = String::new();
+        if let Some(root) = self.root.as_deref() {
+            write_ascii(root, &mut out);
+        }
+        out
+    }
+
+    /// 导出 Graphviz DOT, 可以用 `dot -Tpng` 画出来
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        if let Some(root) = self.root.as_deref() {
+            write_dot(root, &mut out);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<T> RenderNode for TreeNode<T>
+where
+    T: Ord + Display,
+{
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+
+    /// 值以及颜色
+    fn ascii_label(&self) -> String {
+        let color = match self.color {
+            Color::Red => 'R',
+            Color::Black => 'B',
+        };
+        format!("{} ({color})", self.value)
+    }
+
+    /// 节点按颜色填充
+    fn dot_attrs(&self) -> String {
+        let color = match self.color {
+            Color::Red => "red",
+            Color::Black => "black",
+        };
+        format!(
+            "label=\"{}\", style=filled, fillcolor={color}, fontcolor=white",
+            dot_escape(&self.value.to_string())
+        )
+    }
+
+    /// 指向红节点的链接也画成红色
+    fn dot_edge_attrs(&self) -> &'static str {
+        match self.color {
+            Color::Red => " [color=red]",
+            Color::Black => "",
+        }
+    }
+}
+
+// 序列化
+// 搜索树的形状由前序序列唯一确定, 所以只按前序写出每个节点, 同时带上颜色
+// 文本格式: 节点之间用空格分隔, 每个节点写成 "值:R" 或 "值:B", 重数大于 1 时再加上 "*重数"
+// 值中的空白、'*' 和反斜杠会被转义
+// 字节格式: 每个节点一个标记字节, 1 表示黑, 2 表示红, 最高位表示值后面还跟着重数
+// 和其他树共用的编码, 转义和前序重建在 common/codec.rs 里
+
+/// 反序列化失败的原因
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    UnexpectedEnd,          // 字节流在一个节点的中间结束
+    InvalidTag(u8),         // 无法识别的标记字节
+    InvalidToken(String),   // 文本中无法解析的记号
+    OutOfOrder,             // 不是任何一棵搜索树的前序序列, 包括有重复值的情况
+    Violation(RbViolation), // 颜色不满足左倾红黑树的性质
+    ZeroCount,              // 记录的重数为 0
+}
+
+impl From<CountError> for DecodeError {
+    fn from(error: CountError) -> Self {
+        match error {
+            CountError::Zero => DecodeError::ZeroCount,
+            CountError::Invalid(token) => DecodeError::InvalidToken(token),
+        }
+    }
+}
+
+impl<T> BinarySearchTree<T>
+where
+    T: Ord,
+{
+    /// 按前序访问每个节点
+    fn walk_preorder(&self, mut visit: impl FnMut(&TreeNode<T>)) {
+        let mut stack: Vec<&TreeNode<T>> = self.root.as_deref().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            visit(node);
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+        }
+    }
+
+    /// 序列化成文本, 例如 "4:B 2:R 1:B 3:B 6:B 5:R*2"
+    fn to_preorder_string(&self) -> String
+    where
+        T: Display,
+    {
+        let mut out = String::new();
+        self.walk_preorder(|node| {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            let color = match node.color {
+                Color::Red => 'R',
+                Color::Black => 'B',
+            };
+            escape_into(&node.value.to_string(), &mut out);
+            write!(out, ":{color}").unwrap();
+            if node.count > 1 {
+                write!(out, "*{}", node.count).unwrap();
+            }
+        });
+        out
+    }
+
+    /// 从 to_preorder_string 的结果恢复, 重复值策略恢复成默认的 Reject
+    fn from_preorder_str(s: &str) -> Result<Self, DecodeError>
+    where
+        T: FromStr,
+    {
+        let nodes = s
+            .split_whitespace()
+            .map(|token| {
+                let invalid = || DecodeError::InvalidToken(token.to_string());
+                let (rest, count) = split_count(token)?;
+                let (value, color) = rest.rsplit_once(':').ok_or_else(invalid)?;
+                let value = unescape(value).and_then(|value| value.parse().ok());
+                let mut node = Box::new(TreeNode::new(value.ok_or_else(invalid)?));
+                node.color = match color {
+                    "R" => Color::Red,
+                    "B" => Color::Black,
+                    _ => return Err(invalid()),
+                };
+                node.count = count;
+                Ok(node)
+            })
+            .collect::<Result<_, DecodeError>>()?;
+        Self::from_preorder_nodes(nodes)
+    }
+
+    /// 序列化成字节
+    fn to_bytes(&self) -> Vec<u8>
+    where
+        T: Encode,
+    {
+        let mut out = Vec::new();
+        self.walk_preorder(|node| {
+            let tag = match node.color {
+                Color::Black => 1,
+                Color::Red => 2,
+            };
+            if node.count > 1 {
+                out.push(tag | COUNT_FLAG);
+                node.value.encode(&mut out);
+                write_varint(node.count as u64, &mut out);
+            } else {
+                out.push(tag);
+                node.value.encode(&mut out);
+            }
+        });
+        out
+    }
+
+    /// 从 to_bytes 的结果恢复, 重复值策略恢复成默认的 Reject
+    fn from_bytes(mut input: &[u8]) -> Result<Self, DecodeError>
+    where
+        T: Encode,
+    {
+        let mut nodes = Vec::new();
+        while let Some((&tag, rest)) = input.split_first() {
+            input = rest;
+            let color = match tag & !COUNT_FLAG {
+                1 => Color::Black,
+                2 => Color::Red,
+                _ => return Err(DecodeError::InvalidTag(tag)),
+            };
+            let value = T::decode(&mut input).ok_or(DecodeError::UnexpectedEnd)?;
+            let mut node = Box::new(TreeNode::new(value));
+            node.color = color;
+            if tag & COUNT_FLAG != 0 {
+                node.count = match read_varint(&mut input).ok_or(DecodeError::UnexpectedEnd)? {
+                    0 => return Err(DecodeError::ZeroCount),
+                    count => count as usize,
+                };
+            }
+            nodes.push(node);
+        }
+        Self::from_preorder_nodes(nodes)
+    }
+
+    fn from_preorder_nodes(nodes: Vec<Box<TreeNode<T>>>) -> Result<Self, DecodeError> {
+        let parents =
+            preorder_parents(&nodes, |node| &node.value).ok_or(DecodeError::OutOfOrder)?;
+        Ok(BinarySearchTree {
+            root: TreeNode::assemble(nodes, &parents).map_err(DecodeError::Violation)?,
+            policy: DuplicatePolicy::Reject,
+        })
+    }
+}
+
+impl<T> TreeNode<T>
+where
+    T: Ord,
+{
+    /// 按 parents 把节点挂起来, 返回根
+    /// 父节点总是排在孩子前面, 所以倒着挂时每个节点的子树都已经完整,
+    /// 可以在这时检查颜色并算出黑高, 不需要再递归地 validate, 恶意构造的长链也不会栈溢出
+    fn assemble(
+        nodes: Vec<Box<Self>>,
+        parents: &[(usize, Edge)],
+    ) -> Result<Option<Box<Self>>, RbViolation> {
+        // 每个节点左右子树的黑高, 空子树为 0
+        let mut black_heights = vec![(0, 0); nodes.len()];
+        let mut slots: Vec<Option<Box<Self>>> = nodes.into_iter().map(Some).collect();
+        for (i, &(parent, edge)) in parents.iter().enumerate().rev() {
+            let mut node = slots[i].take().unwrap();
+            let (left, right) = black_heights[i];
+            if left != right {
+                return Err(RbViolation::BlackHeightMismatch);
+            }
+            if Self::is_red(&node.right) {
+                return Err(RbViolation::RightLeaningRed);
+            }
+            if node.color == Color::Red && Self::is_red(&node.left) {
+                return Err(RbViolation::DoubleRed);
+            }
+            node.update_size();
+            let height = left + usize::from(node.color == Color::Black);
+            match edge {
+                Edge::Root if node.color == Color::Red => return Err(RbViolation::RedRoot),
+                Edge::Root => return Ok(Some(node)),
+                Edge::Left => {
+                    black_heights[parent].0 = height;
+                    slots[parent].as_mut().unwrap().left = Some(node);
+                }
+                Edge::Right => {
+                    black_heights[parent].1 = height;
+                    slots[parent].as_mut().unwrap().right = Some(node);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+// 有序映射
+// 直接用上面的 TreeNode 存放键值对: value 是键, data 是键对应的值
+// 旋转, 修复和删除都和集合共用同一份实现
+
+/// 有序映射的节点
+type MapNode<K, V> = TreeNode<K, V>;
+
+// 插入时要把新值的可变引用交给调用方, 但旋转会移动节点所在的 Box, 不能预先记下值的地址
+// 所以插入时同时维护从子树根到新节点的路径, 旋转时跟着调整, 插入完成后沿路径重新走到新节点
+// entry() 查找时记下的也是这样的路径, 之后读写值和插入新节点都沿路径走, 不必再比较键
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// 从子树根到某个节点 (或某个空位) 的路径, 反向存放: 最后一个元素是从根出发的第一步
+#[derive(Debug, Default)]
+struct Path(Vec<Side>);
+
+impl Path {
+    /// 由从根出发依次走过的方向构造
+    fn from_steps(mut steps: Vec<Side>) -> Self {
+        steps.reverse();
+        Path(steps)
+    }
+
+    /// 子树根 up 侧的孩子被旋转上来成为新的根
+    fn lift(&mut self, up: Side) {
+        let down = up.opposite();
+        match self.0.pop() {
+            // 目标是原来的根, 它成了新根 down 侧的孩子
+            None => self.0.push(down),
+            // 目标在原来的根的另一侧, 上面多了一层
+            Some(side) if side == down => {
+                self.0.push(down);
+                self.0.push(down);
+            }
+            Some(_) => match self.0.pop() {
+                // 目标就是被提上来的孩子
+                None => {}
+                // 目标在孩子的外侧子树, 跟着孩子上升一层
+                Some(side) if side == up => self.0.push(up),
+                // 目标在孩子的内侧子树, 这棵子树被挂到原来的根的 up 侧
+                Some(_) => {
+                    self.0.push(up);
+                    self.0.push(down);
+                }
+            },
+        }
+    }
+
+    /// 沿路径从子树根走到目标节点
+    fn get<'n, T: Ord, V>(&self, mut node: &'n TreeNode<T, V>) -> &'n TreeNode<T, V> {
+        for &side in self.0.iter().rev() {
+            node = node.child(side).as_deref().unwrap();
+        }
+        node
+    }
+
+    fn get_mut<'n, T: Ord, V>(&self, mut node: &'n mut TreeNode<T, V>) -> &'n mut TreeNode<T, V> {
+        for &side in self.0.iter().rev() {
+            node = node.child_mut(side).as_deref_mut().unwrap();
+        }
+        node
+    }
+}
+
+impl<T, V> TreeNode<T, V>
+where
+    T: Ord,
+{
+    fn child(&self, side: Side) -> &Option<Box<Self>> {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    fn child_mut(&mut self, side: Side) -> &mut Option<Box<Self>> {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+        }
+    }
+}
+
+impl<K, V> MapNode<K, V>
+where
+    K: Ord,
+{
+    /// 沿 path 走到它指向的空位, 挂上新节点, 返回修复后的子树根
+    /// 返回时 path 是从新的子树根到新节点的路径
+    fn attach(mut self: Box<Self>, node: Box<Self>, path: &mut Path) -> Box<Self> {
+        let side = path.0.pop().unwrap();
+        let child = self.child_mut(side);
+        *child = Some(match child.take() {
+            Some(child) => child.attach(node, path),
+            None => node,
+        });
+        path.0.push(side);
+        self.fixup(Some(path))
+    }
+}
+
+/// 基于左倾红黑树的有序映射
+#[derive(Debug)]
+struct RbMap<K, V>
+where
+    K: Ord,
+{
+    root: Option<Box<MapNode<K, V>>>,
+}
+
+impl<K, V> RbMap<K, V>
+where
+    K: Ord,
+{
+    pub fn new() -> Self {
+        RbMap { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        TreeNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// 插入键值对, 键已存在时替换值并返回旧值 (键本身保持不变)
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match key.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.data),
+            };
+        }
+        None
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_deref_mut();
+        while let Some(node) = current {
+            current = match key.cmp(node.value.borrow()) {
+                Ordering::Less => node.left.as_deref_mut(),
+                Ordering::Greater => node.right.as_deref_mut(),
+                Ordering::Equal => return Some(&mut node.data),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// 删除键, 返回对应的值
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // 下行过程会沿途调整结构, 必须先确认键存在
+        if !self.contains_key(key) {
+            return None;
+        }
+        let (_, value) = TreeNode::delete_from_root(&mut self.root, key);
+        Some(value)
+    }
+
+    /// 取得键对应的位置, 用法和标准库的 entry 相同
+    /// 只查找这一次, 记下从根到键所在节点 (或应该插入的空位) 的路径
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let mut steps = Vec::new();
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            let side = match key.cmp(&node.value) {
+                Ordering::Less => Side::Left,
+                Ordering::Greater => Side::Right,
+                Ordering::Equal => {
+                    let path = Path::from_steps(steps);
+                    return Entry::Occupied(OccupiedEntry {
+                        map: self,
+                        key,
+                        path,
+                    });
+                }
+            };
+            steps.push(side);
+            current = node.child(side).as_deref();
+        }
+        let path = Path::from_steps(steps);
+        Entry::Vacant(VacantEntry {
+            map: self,
+            key,
+            path,
+        })
+    }
+
+    /// 按键从小到大遍历 (&K, &V)
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// 检查左倾红黑树的全部性质
+    pub fn validate(&self) -> Result<(), RbViolation> {
+        match &self.root {
+            None => Ok(()),
+            Some(root) if root.color == Color::Red => Err(RbViolation::RedRoot),
+            Some(root) => root.validate(None, None).map(|_| ()),
+        }
+    }
+}
+
+/// entry() 返回的位置, 键可能已存在也可能不存在
+enum Entry<'a, K, V>
+where
+    K: Ord,
+{
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+struct OccupiedEntry<'a, K, V>
+where
+    K: Ord,
+{
+    map: &'a mut RbMap<K, V>,
+    key: K,
+    path: Path, // 从根到键所在节点的路径
+}
+
+struct VacantEntry<'a, K, V>
+where
+    K: Ord,
+{
+    map: &'a mut RbMap<K, V>,
+    key: K,
+    path: Path, // 从根到新节点应该挂上的空位的路径
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// 不存在时插入 default, 返回值的可变引用
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// 已存在时先修改一下值
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.path.get(self.map.root.as_deref().unwrap()).data
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self
+            .path
+            .get_mut(self.map.root.as_deref_mut().unwrap())
+            .data
+    }
+
+    /// 转成和 map 借用一样长的可变引用
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self
+            .path
+            .get_mut(self.map.root.as_deref_mut().unwrap())
+            .data
+    }
+
+    /// 替换值, 返回旧值
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// 从 map 中删除, 返回值
+    /// 左倾红黑树删除时一边下行一边调整结构, 记下的路径用不上, 只能从根按键下行
+    /// 键一定存在, 所以不用像 RbMap::remove 那样先查找一遍
+    pub fn remove(self) -> V {
+        let (_, value) = TreeNode::delete_from_root(&mut self.map.root, &self.key);
+        value
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// 插入值, 返回它的可变引用
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { map, key, mut path } = self;
+        let node = Box::new(TreeNode::with_data(key, value));
+        // 键已经移进节点, 插入后没法再按键找回来, 所以让 attach 顺便算出新节点的位置
+        let mut root = match map.root.take() {
+            Some(root) => root.attach(node, &mut path),
+            None => node,
+        };
+        root.color = Color::Black;
+        &mut path.get_mut(map.root.insert(root)).data
+    }
+}
+
+/// 按键中序遍历的迭代器
+struct Iter<'a, K, V>
+where
+    K: Ord,
+{
+    stack: Vec<&'a MapNode<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V>
+where
+    K: Ord,
+{
+    fn push_left(&mut self, mut node: Option<&'a MapNode<K, V>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V>
+where
+    K: Ord,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.as_deref());
+        Some((&node.value, &node.data))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a RbMap<K, V>
+where
+    K: Ord,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for RbMap<K, V>
+where
+    K: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = RbMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 简单的线性同余生成器
+    fn lcg(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
+    #[test]
+    fn test_insert_and_search() {
+        let mut bst = BinarySearchTree::new();
+
+        assert_eq!(bst.search(1), false);
+
+        bst.insert(5);
+        bst.insert(3);
+        bst.insert(7);
+        bst.insert(2);
+        bst.insert(4);
+
+        assert_eq!(bst.search(5), true);
+        assert_eq!(bst.search(3), true);
+        assert_eq!(bst.search(7), true);
+        assert_eq!(bst.search(2), true);
+        assert_eq!(bst.search(4), true);
+
+        assert_eq!(bst.search(1), false);
+        assert_eq!(bst.search(6), false);
+    }
+
+    #[test]
+    fn test_insert_duplicate() {
+        let mut bst = BinarySearchTree::new();
+
+        bst.insert(1);
+        bst.insert(1);
+
+        assert_eq!(bst.search(1), true);
+
+        match bst.root {
+            Some(ref node) => {
+                assert!(node.left.is_none());
+                assert!(node.right.is_none());
+            }
+            None => panic!("Root should not be None after insertion"),
+        }
+    }
+
+    #[test]
+    fn test_rb_insert() {
+        let mut tree = BinarySearchTree::new();
+        tree.insert_rb(3);
+        tree.insert_rb(1);
+        tree.insert_rb(5);
+        tree.insert_rb(2);
+
+        // 验证根节点为黑
+        assert_eq!(tree.root.as_ref().unwrap().color, Color::Black);
+
+        // 验证红黑树全部性质
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_rb_validate_reports_violation() {
+        let mut tree = BinarySearchTree::new();
+        for i in 1..=7 {
+            tree.insert_rb(i);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+
+        // 把根染红
+        tree.root.as_mut().unwrap().color = Color::Red;
+        assert_eq!(tree.validate(), Err(RbViolation::RedRoot));
+        tree.root.as_mut().unwrap().color = Color::Black;
+
+        // 把右孩子染红, 形成右倾红链接
+        tree.root.as_mut().unwrap().right.as_mut().unwrap().color = Color::Red;
+        assert_eq!(tree.validate(), Err(RbViolation::RightLeaningRed));
+        tree.root.as_mut().unwrap().right.as_mut().unwrap().color = Color::Black;
+
+        // 把左孩子染红, 左右黑高不等
+        tree.root.as_mut().unwrap().left.as_mut().unwrap().color = Color::Red;
+        assert_eq!(tree.validate(), Err(RbViolation::BlackHeightMismatch));
+        tree.root.as_mut().unwrap().left.as_mut().unwrap().color = Color::Black;
+
+        // 子树大小记录错误
+        tree.root.as_mut().unwrap().size += 1;
+        assert_eq!(tree.validate(), Err(RbViolation::SizeMismatch));
+        tree.root.as_mut().unwrap().size -= 1;
+
+        // 破坏有序性
+        tree.root.as_mut().unwrap().left.as_mut().unwrap().value = 100;
+        assert_eq!(tree.validate(), Err(RbViolation::OutOfOrder));
+    }
+
+    #[test]
+    fn test_rb_delete_min_max() {
+        let mut tree: BinarySearchTree<i32> = (1..=100).collect();
+        for i in 1..=25 {
+            assert_eq!(tree.delete_min(), Some(RemoveOutcome::Removed(i)));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        for i in (76..=100).rev() {
+            assert_eq!(tree.delete_max(), Some(RemoveOutcome::Removed(i)));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            (26..=75).collect::<Vec<_>>()
+        );
+
+        while tree.delete_min().is_some() {
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(tree.root.is_none());
+        assert_eq!(tree.delete_min(), None);
+        assert_eq!(tree.delete_max(), None);
+    }
+
+    #[test]
+    fn test_rb_delete() {
+        let mut tree: BinarySearchTree<i32> = (1..=7).collect();
+        assert_eq!(tree.delete(&8), None);
+        assert_eq!(tree.delete(&4), Some(RemoveOutcome::Removed(4)));
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.delete(&4), None);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn test_rb_random_insert_delete() {
+        use std::collections::BTreeSet;
+
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || lcg(&mut seed);
+
+        let mut tree = BinarySearchTree::new();
+        let mut model = BTreeSet::new();
+        for _ in 0..20_000 {
+            let value = next() % 500;
+            if next() % 3 == 0 {
+                assert_eq!(
+                    tree.delete(&value),
+                    model.take(&value).map(RemoveOutcome::Removed)
+                );
+            } else {
+                tree.insert_rb(value);
+                model.insert(value);
+            }
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(tree.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn test_traversals() {
+        // 左倾红黑树顺序插入 1..=7 的结果
+        //        4
+        //     2     6
+        //    1 3   5 7
+        let tree: BinarySearchTree<i32> = (1..=7).collect();
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            tree.pre_order().copied().collect::<Vec<_>>(),
+            vec![4, 2, 1, 3, 6, 5, 7]
+        );
+        assert_eq!(
+            tree.post_order().copied().collect::<Vec<_>>(),
+            vec![1, 3, 2, 5, 7, 6, 4]
+        );
+        assert_eq!(
+            tree.level_order().copied().collect::<Vec<_>>(),
+            vec![4, 2, 6, 1, 3, 5, 7]
+        );
+    }
+
+    #[test]
+    fn test_into_iter_and_extend() {
+        let mut tree: BinarySearchTree<i32> = (0..100).rev().collect();
+        tree.extend(50..150);
+        assert_eq!(tree.root.as_ref().unwrap().color, Color::Black);
+        assert_eq!((&tree).into_iter().count(), 150);
+        assert_eq!(
+            tree.into_iter().collect::<Vec<_>>(),
+            (0..150).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_rb_duplicate_policies() {
+        let mut tree = BinarySearchTree::new();
+        assert_eq!(tree.insert_rb(1), InsertOutcome::Inserted);
+        assert_eq!(tree.insert_rb(1), InsertOutcome::Rejected(1));
+        assert_eq!(tree.insert(1), InsertOutcome::Rejected(1));
+
+        let mut tree = BinarySearchTree::with_policy(DuplicatePolicy::Replace);
+        tree.insert_rb(2);
+        tree.insert_rb(1);
+        assert_eq!(tree.insert_rb(1), InsertOutcome::Replaced(1));
+        assert_eq!(tree.validate(), Ok(()));
+
+        // 多重集合, 大量重复值插入后仍然满足红黑树性质
+        let mut tree = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        for x in 0..300 {
+            tree.insert_rb(x % 100);
+        }
+        assert_eq!(tree.insert_rb(42), InsertOutcome::Counted(4));
+        assert_eq!(tree.insert(43), InsertOutcome::Counted(4));
+        assert_eq!(tree.count(&42), 4);
+        assert_eq!(tree.count(&100), 0);
+        assert_eq!(tree.validate(), Ok(()));
+
+        // 删除一次只去掉一个副本, 重数减到 1 之后再删除才摘掉节点
+        assert_eq!(tree.delete(&42), Some(RemoveOutcome::Decremented(3)));
+        assert_eq!(tree.count(&42), 3);
+        for expected in [2, 1] {
+            assert_eq!(tree.delete(&42), Some(RemoveOutcome::Decremented(expected)));
+        }
+        assert_eq!(tree.delete(&42), Some(RemoveOutcome::Removed(42)));
+        assert_eq!(tree.delete(&42), None);
+        assert_eq!(TreeNode::size(&tree.root), 99);
+        assert_eq!(tree.validate(), Ok(()));
+
+        // delete_all 去掉所有副本; 删除根时后继顶替, 重数跟着移动
+        let root = tree.root.as_ref().unwrap().value;
+        let successor = *tree.select(tree.rank(&root) + 1).unwrap();
+        tree.insert_rb(successor);
+        assert_eq!(tree.count(&root), 3);
+        assert_eq!(tree.delete_all(&root), Some(root));
+        assert_eq!(tree.delete_all(&root), None);
+        assert_eq!(tree.count(&root), 0);
+        assert_eq!(tree.count(&successor), 4);
+        assert_eq!(tree.validate(), Ok(()));
+
+        // delete_min 和 delete_max 同样一次只去掉一个副本
+        let min = *tree.select(0).unwrap();
+        assert_eq!(tree.count(&min), 3);
+        assert_eq!(tree.delete_min(), Some(RemoveOutcome::Decremented(2)));
+        assert_eq!(tree.delete_min(), Some(RemoveOutcome::Decremented(1)));
+        assert_eq!(tree.delete_min(), Some(RemoveOutcome::Removed(min)));
+        assert_eq!(tree.count(&min), 0);
+        let max = *tree.select(TreeNode::size(&tree.root) - 1).unwrap();
+        assert_eq!(tree.count(&max), 3);
+        assert_eq!(tree.delete_max(), Some(RemoveOutcome::Decremented(2)));
+        assert_eq!(tree.count(&max), 2);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_rb_rank_select() {
+        let tree: BinarySearchTree<i32> = [50, 20, 80, 10, 30, 70, 90].into_iter().collect();
+        assert_eq!(tree.rank(&10), 0);
+        assert_eq!(tree.rank(&50), 3);
+        assert_eq!(tree.rank(&55), 4); // 不在树中
+        assert_eq!(tree.rank(&100), 7);
+        assert_eq!(tree.select(0), Some(&10));
+        assert_eq!(tree.select(3), Some(&50));
+        assert_eq!(tree.select(6), Some(&90));
+        assert_eq!(tree.select(7), None);
+        assert_eq!(tree.count_range(&20, &70), 4);
+        assert_eq!(tree.count_range(&21, &69), 2);
+        assert_eq!(tree.count_range(&0, &100), 7);
+        assert_eq!(tree.count_range(&70, &20), 0);
+    }
+
+    #[test]
+    fn test_rb_rank_select_random() {
+        let mut seed: u64 = 38;
+        let mut next = || lcg(&mut seed) % 1000;
+        let mut tree = BinarySearchTree::new();
+        let mut model = std::collections::BTreeSet::new();
+        for round in 0..5000 {
+            let x = next();
+            if next() % 3 == 0 {
+                tree.delete(&x);
+                model.remove(&x);
+            } else {
+                tree.insert_rb(x);
+                model.insert(x);
+            }
+            if round % 50 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+                let sorted: Vec<u64> = model.iter().copied().collect();
+                for (k, v) in sorted.iter().enumerate() {
+                    assert_eq!(tree.select(k), Some(v));
+                    assert_eq!(tree.rank(v), k);
+                }
+                let (a, b) = (next(), next());
+                let expected = model.iter().filter(|&&v| a <= v && v <= b).count();
+                assert_eq!(tree.count_range(&a, &b), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rb_map_basic() {
+        let mut map = RbMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("c".to_string(), 3), None);
+        assert_eq!(map.insert("b".to_string(), 20), Some(2));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.validate(), Ok(()));
+
+        // 可以用 &str 查 String 键
+        assert_eq!(map.get("b"), Some(&20));
+        assert_eq!(map.get("d"), None);
+        *map.get_mut("a").unwrap() += 10;
+        assert_eq!(map.get("a"), Some(&11));
+
+        let pairs: Vec<(&str, i32)> = map.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(pairs, vec![("a", 11), ("b", 20), ("c", 3)]);
+
+        assert_eq!(map.remove("b"), Some(20));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.len(), 2);
+        assert!(map.keys().eq(["a", "c"].iter()));
+        assert!(map.values().eq([11, 3].iter()));
+    }
+
+    #[test]
+    fn test_rb_map_entry() {
+        // 单词计数
+        let mut counts: RbMap<&str, usize> = RbMap::new();
+        for word in "the cat and the dog and the bird".split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        let result: Vec<(&str, usize)> = counts.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(
+            result,
+            vec![("and", 2), ("bird", 1), ("cat", 1), ("dog", 1), ("the", 3)]
+        );
+
+        counts.entry("cat").and_modify(|v| *v += 10).or_default();
+        counts.entry("cow").and_modify(|v| *v += 10).or_default();
+        assert_eq!(counts.get("cat"), Some(&11));
+        assert_eq!(counts.get("cow"), Some(&0));
+
+        match counts.entry("dog") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &"dog");
+                assert_eq!(entry.get(), &1);
+                assert_eq!(entry.insert(5), 1);
+                assert_eq!(entry.remove(), 5);
+            }
+            Entry::Vacant(_) => panic!("dog should be present"),
+        }
+        match counts.entry("emu") {
+            Entry::Occupied(_) => panic!("emu should be absent"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &"emu");
+                assert_eq!(entry.into_key(), "emu");
+            }
+        }
+        assert_eq!(counts.entry("ant").key(), &"ant");
+        assert!(!counts.contains_key("dog"));
+        assert!(!counts.contains_key("emu"));
+        assert_eq!(counts.len(), 5);
+
+        // 插入时的旋转会移动节点, 返回的引用必须指向新插入的那个值
+        // 有序, 逆序和随机的键分别触发各种旋转
+        let mut seed = 3u64;
+        let shuffled: Vec<u32> = (0..200).map(|_| lcg(&mut seed) as u32 % 1000).collect();
+        for keys in [(0..200).collect(), (0..200).rev().collect(), shuffled] {
+            let mut map = RbMap::new();
+            for &key in &keys {
+                *map.entry(key).or_insert(0) += key + 1;
+            }
+            for &key in &keys {
+                let times = keys.iter().filter(|&&k| k == key).count() as u32;
+                assert_eq!(map.get(&key), Some(&((key + 1) * times)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rb_map_random() {
+        let mut seed: u64 = 7;
+        let mut next = || lcg(&mut seed);
+        let mut map = RbMap::new();
+        let mut model = std::collections::BTreeMap::new();
+        for round in 0..20_000 {
+            let key = next() % 1000;
+            match next() % 4 {
+                0 => assert_eq!(map.remove(&key), model.remove(&key)),
+                1 => {
+                    *map.entry(key).or_insert(0) += round;
+                    *model.entry(key).or_insert(0) += round;
+                }
+                _ => assert_eq!(map.insert(key, round), model.insert(key, round)),
+            }
+            if round % 100 == 0 {
+                assert_eq!(map.validate(), Ok(()));
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        assert!(map.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn test_rb_to_ascii_and_dot() {
+        let tree: BinarySearchTree<i32> = (1..=3).collect();
+        assert_eq!(tree.to_ascii(), "    /-- 3 (B)\n2 (B)\n    \\-- 1 (B)\n");
+
+        // 红节点和指向它的链接都画成红色
+        let tree: BinarySearchTree<i32> = (1..=2).collect();
+        assert_eq!(tree.to_ascii(), "2 (B)\n    \\-- 1 (R)\n");
+        let expected = concat!(
+            "digraph {\n",
+            "    n0 [label=\"2\", style=filled, fillcolor=black, fontcolor=white];\n",
+            "    n1 [label=\"1\", style=filled, fillcolor=red, fontcolor=white];\n",
+            "    n0 -> n1 [color=red];\n",
+            "    n2 [shape=point];\n",
+            "    n0 -> n2;\n",
+            "}\n",
+        );
+        assert_eq!(tree.to_dot(), expected);
+    }
+
+    // 按前序输出结构, 空孩子记为 #, 用来比较两棵树的形状和节点上记录的信息
+    fn shape<T: Ord + Display>(node: &Option<Box<TreeNode<T>>>) -> String {
+        match node {
+            None => "#".to_string(),
+            Some(n) => format!(
+                "({}:{:?}:{}x{} {} {})",
+                n.value,
+                n.color,
+                n.size,
+                n.count,
+                shape(&n.left),
+                shape(&n.right)
+            ),
+        }
+    }
+
+    #[test]
+    fn test_rb_serialize_round_trip() {
+        let tree: BinarySearchTree<i32> = (1..=5).collect();
+        assert_eq!(tree.to_preorder_string(), "4:B 2:R 1:B 3:B 5:B");
+
+        let mut tree = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        let mut seed: u64 = 23;
+        for _ in 0..500 {
+            tree.insert_rb(lcg(&mut seed) as i32 % 300);
+        }
+        let text = tree.to_preorder_string();
+        let from_text = BinarySearchTree::<i32>::from_preorder_str(&text).unwrap();
+        assert_eq!(from_text.validate(), Ok(()));
+        assert_eq!(shape(&from_text.root), shape(&tree.root));
+
+        let bytes = tree.to_bytes();
+        let from_bytes = BinarySearchTree::<i32>::from_bytes(&bytes).unwrap();
+        assert_eq!(shape(&from_bytes.root), shape(&tree.root));
+        assert_eq!(from_bytes.to_bytes(), bytes);
+
+        let words: BinarySearchTree<String> = ["kiwi", "fig", "lime", "date", "pear"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let decoded = BinarySearchTree::<String>::from_bytes(&words.to_bytes()).unwrap();
+        assert_eq!(shape(&decoded.root), shape(&words.root));
+
+        // 值中的空白、'*' 和反斜杠都要转义, ':' 不用, 颜色总是在最后一个 ':' 之后
+        let mut odd = BinarySearchTree::with_policy(DuplicatePolicy::Count);
+        for word in ["a b", "a*2", "a*2", "x\\y:B", "\n", ""] {
+            odd.insert_rb(word.to_string());
+        }
+        let text = odd.to_preorder_string();
+        assert_eq!(text, r"a\u{2a}2:B*2 \u{a}:R \e:B a\u{20}b:B x\\y:B:B");
+        let from_text = BinarySearchTree::<String>::from_preorder_str(&text).unwrap();
+        assert_eq!(from_text.validate(), Ok(()));
+        assert_eq!(shape(&from_text.root), shape(&odd.root));
+        assert_eq!(from_text.to_preorder_string(), text);
+
+        assert!(
+            BinarySearchTree::<i32>::from_bytes(&[])
+                .unwrap()
+                .root
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_rb_decode_errors() {
+        let parse = BinarySearchTree::<i32>::from_preorder_str;
+        assert_eq!(
+            parse("2:B 1:B 3").unwrap_err(),
+            DecodeError::InvalidToken("3".to_string())
+        );
+        assert_eq!(
+            parse("2:B 1:X").unwrap_err(),
+            DecodeError::InvalidToken("1:X".to_string())
+        );
+        assert_eq!(parse("2:B 3:B 1:B").unwrap_err(), DecodeError::OutOfOrder);
+        let violation = |s| parse(s).unwrap_err();
+        assert_eq!(
+            violation("2:R 1:B 3:B"),
+            DecodeError::Violation(RbViolation::RedRoot)
+        );
+        assert_eq!(
+            violation("2:B 1:R 3:R"),
+            DecodeError::Violation(RbViolation::RightLeaningRed)
+        );
+        assert_eq!(
+            violation("3:B 2:R 1:R"),
+            DecodeError::Violation(RbViolation::DoubleRed)
+        );
+        assert_eq!(
+            violation("2:B 1:B"),
+            DecodeError::Violation(RbViolation::BlackHeightMismatch)
+        );
+        // 一百万个黑节点的链在挂到第二层时就会发现黑高不等, 不会递归到底
+        let chain: String = (0..1_000_000).map(|i| format!("{i}:B ")).collect();
+        assert_eq!(
+            violation(&chain),
+            DecodeError::Violation(RbViolation::BlackHeightMismatch)
+        );
+
+        let bytes = parse("2:B 1:R").unwrap().to_bytes();
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            DecodeError::UnexpectedEnd
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[3, 1, 0, 0, 0]).unwrap_err(),
+            DecodeError::InvalidTag(3)
+        );
+
+        // 重数为 0 的节点不存在, 两种格式都拒绝
+        assert_eq!(parse("2:B 1:R*0").unwrap_err(), DecodeError::ZeroCount);
+        assert_eq!(
+            parse("2:B 1:R*x").unwrap_err(),
+            DecodeError::InvalidToken("1:R*x".to_string())
+        );
+        assert_eq!(
+            BinarySearchTree::<i32>::from_bytes(&[1 | COUNT_FLAG, 1, 0, 0, 0, 0]).unwrap_err(),
+            DecodeError::ZeroCount
+        );
+        assert_eq!(
+            BinarySearchTree::<String>::from_preorder_str("a\\:B").unwrap_err(),
+            DecodeError::InvalidToken("a\\:B".to_string())
+        );
     }
 }
@@ -0,0 +1,1073 @@
+// B 树和 B+ 树
+// 二叉树每层一次分配, 一次指针跳转; B 树每个节点放很多键, 层数少, 节点内部按数组连续访问
+// 最小度数 t 可以配置: 除根以外每个节点有 t - 1 到 2t - 1 个键
+// B+ 树只在叶子里放值, 内部节点只做路由, 叶子串成链表, 有序遍历不需要回到上层
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+// 两种树的范围迭代器共用的边界判断
+fn above_start<Q, R>(range: &R, key: &Q) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.start_bound() {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    }
+}
+
+fn below_end<Q, R>(range: &R, key: &Q) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.end_bound() {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true,
+    }
+}
+
+// B 树
+
+#[derive(Debug)]
+struct BTreeNode<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+    children: Vec<BTreeNode<K, V>>, // 叶子没有孩子, 内部节点的孩子比键多一个
+}
+
+#[derive(Debug)]
+struct BTree<K, V> {
+    root: BTreeNode<K, V>,
+    min_degree: usize,
+    len: usize,
+}
+
+impl<K, V> BTree<K, V>
+where
+    K: Ord,
+{
+    /// 最小度数至少为 2, 也就是 2-3-4 树
+    fn new(min_degree: usize) -> Self {
+        assert!(min_degree >= 2, "最小度数至少为 2");
+        BTree {
+            root: BTreeNode::new(),
+            min_degree,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = &self.root;
+        loop {
+            match node.search(key) {
+                Ok(i) => return Some(&node.values[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = &node.children[i],
+            }
+        }
+    }
+
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// 插入键值对, 键已存在时替换并返回旧值
+    /// 下行途中遇到满节点就先分裂, 这样插入到叶子时父节点一定还有空位
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let t = self.min_degree;
+        if self.root.is_full(t) {
+            // 根满了先长高一层, 这是 B 树唯一变高的方式
+            let old_root = std::mem::replace(&mut self.root, BTreeNode::new());
+            self.root.children.push(old_root);
+            self.root.split_child(0, t);
+        }
+        let old = self.root.insert_non_full(key, value, t);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// 删除键, 返回对应的值
+    /// 下行前保证要进入的孩子至少有 t 个键, 这样删除后不会少于 t - 1 个
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = self.root.remove(key, self.min_degree);
+        // 根的最后一个键被合并下去了, 树变矮一层
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            self.root = self.root.children.pop().unwrap();
+        }
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn iter(&self) -> Range<'_, K, V, K, (Bound<&K>, Bound<&K>)> {
+        self.range((Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// 按键的顺序遍历落在 range 中的键值对, 例如 tree.range(3..7)
+    fn range<Q, R>(&self, range: R) -> Range<'_, K, V, Q, R>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut iter = Range {
+            stack: Vec::new(),
+            range,
+            _query: PhantomData,
+        };
+        // 每层找到第一个满足下界的键, 它左边的键和孩子都太小, 直接跳过
+        let mut node = &self.root;
+        loop {
+            let i = node
+                .keys
+                .partition_point(|k| !above_start(&iter.range, k.borrow()));
+            iter.stack.push((node, i));
+            if node.is_leaf() {
+                break;
+            }
+            node = &node.children[i];
+        }
+        iter
+    }
+}
+
+impl<K, V> BTreeNode<K, V>
+where
+    K: Ord,
+{
+    fn new() -> Self {
+        BTreeNode {
+            keys: Vec::new(),
+            values: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn is_full(&self, t: usize) -> bool {
+        self.keys.len() == 2 * t - 1
+    }
+
+    /// 节点内二分查找, 找到时返回 Ok(位置), 否则返回 Err(应该进入的孩子)
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.keys.binary_search_by(|k| k.borrow().cmp(key))
+    }
+
+    /// 分裂满的第 i 个孩子: 中间的键上移到自己, 右半部分成为新的第 i + 1 个孩子
+    fn split_child(&mut self, i: usize, t: usize) {
+        let child = &mut self.children[i];
+        let mut right = BTreeNode::new();
+        right.keys = child.keys.split_off(t);
+        right.values = child.values.split_off(t);
+        if !child.is_leaf() {
+            right.children = child.children.split_off(t);
+        }
+        let key = child.keys.pop().unwrap();
+        let value = child.values.pop().unwrap();
+        self.keys.insert(i, key);
+        self.values.insert(i, value);
+        self.children.insert(i + 1, right);
+    }
+
+    /// 插入到一个不满的节点中
+    fn insert_non_full(&mut self, key: K, value: V, t: usize) -> Option<V> {
+        let mut i = match self.search(&key) {
+            Ok(i) => return Some(std::mem::replace(&mut self.values[i], value)),
+            Err(i) => i,
+        };
+        if self.is_leaf() {
+            self.keys.insert(i, key);
+            self.values.insert(i, value);
+            return None;
+        }
+        if self.children[i].is_full(t) {
+            self.split_child(i, t);
+            // 上移的中间键可能就是要插入的键, 也决定了该进入哪一半
+            match key.cmp(&self.keys[i]) {
+                Ordering::Equal => {
+                    return Some(std::mem::replace(&mut self.values[i], value));
+                }
+                Ordering::Greater => i += 1,
+                Ordering::Less => {}
+            }
+        }
+        self.children[i].insert_non_full(key, value, t)
+    }
+
+    fn remove<Q>(&mut self, key: &Q, t: usize) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.search(key) {
+            // 1. 叶子中直接删除
+            Ok(i) if self.is_leaf() => {
+                self.keys.remove(i);
+                Some(self.values.remove(i))
+            }
+            // 2. 内部节点中: 用前驱或后继顶替, 两边都只有 t - 1 个键时先合并再到孩子里删
+            Ok(i) => {
+                if self.children[i].keys.len() >= t {
+                    let (k, v) = self.children[i].remove_max(t);
+                    self.keys[i] = k;
+                    Some(std::mem::replace(&mut self.values[i], v))
+                } else if self.children[i + 1].keys.len() >= t {
+                    let (k, v) = self.children[i + 1].remove_min(t);
+                    self.keys[i] = k;
+                    Some(std::mem::replace(&mut self.values[i], v))
+                } else {
+                    self.merge(i);
+                    self.children[i].remove(key, t)
+                }
+            }
+            // 3. 不在当前节点: 保证孩子够 "胖" 之后再下去
+            Err(_) if self.is_leaf() => None,
+            Err(i) => {
+                let i = self.fill(i, t);
+                self.children[i].remove(key, t)
+            }
+        }
+    }
+
+    /// 删除子树中最大的键值对
+    fn remove_max(&mut self, t: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.pop().unwrap(), self.values.pop().unwrap());
+        }
+        let i = self.fill(self.children.len() - 1, t);
+        self.children[i].remove_max(t)
+    }
+
+    /// 删除子树中最小的键值对
+    fn remove_min(&mut self, t: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.remove(0), self.values.remove(0));
+        }
+        let i = self.fill(0, t);
+        self.children[i].remove_min(t)
+    }
+
+    /// 保证第 i 个孩子至少有 t 个键: 先向左右兄弟借, 借不到就和兄弟合并
+    /// 合并可能让孩子的位置左移一格, 返回它现在的位置
+    fn fill(&mut self, i: usize, t: usize) -> usize {
+        if self.children[i].keys.len() >= t {
+            return i;
+        }
+        if i > 0 && self.children[i - 1].keys.len() >= t {
+            self.borrow_from_left(i);
+            i
+        } else if i + 1 < self.children.len() && self.children[i + 1].keys.len() >= t {
+            self.borrow_from_right(i);
+            i
+        } else if i + 1 < self.children.len() {
+            self.merge(i);
+            i
+        } else {
+            self.merge(i - 1);
+            i - 1
+        }
+    }
+
+    /// 左兄弟的最大键上移, 原来的分隔键下移到第 i 个孩子的最前面
+    fn borrow_from_left(&mut self, i: usize) {
+        let (left, right) = self.children.split_at_mut(i);
+        let (left, child) = (&mut left[i - 1], &mut right[0]);
+        let key = std::mem::replace(&mut self.keys[i - 1], left.keys.pop().unwrap());
+        let value = std::mem::replace(&mut self.values[i - 1], left.values.pop().unwrap());
+        child.keys.insert(0, key);
+        child.values.insert(0, value);
+        if let Some(grandchild) = left.children.pop() {
+            child.children.insert(0, grandchild);
+        }
+    }
+
+    /// 右兄弟的最小键上移, 原来的分隔键下移到第 i 个孩子的最后面
+    fn borrow_from_right(&mut self, i: usize) {
+        let (left, right) = self.children.split_at_mut(i + 1);
+        let (child, right) = (&mut left[i], &mut right[0]);
+        let key = std::mem::replace(&mut self.keys[i], right.keys.remove(0));
+        let value = std::mem::replace(&mut self.values[i], right.values.remove(0));
+        child.keys.push(key);
+        child.values.push(value);
+        if !right.is_leaf() {
+            child.children.push(right.children.remove(0));
+        }
+    }
+
+    /// 把第 i + 1 个孩子和分隔键一起并入第 i 个孩子, 两个孩子都只有 t - 1 个键, 合并后正好 2t - 1 个
+    fn merge(&mut self, i: usize) {
+        let right = self.children.remove(i + 1);
+        let key = self.keys.remove(i);
+        let value = self.values.remove(i);
+        let child = &mut self.children[i];
+        child.keys.push(key);
+        child.values.push(value);
+        child.keys.extend(right.keys);
+        child.values.extend(right.values);
+        child.children.extend(right.children);
+    }
+}
+
+/// B 树的范围迭代器
+/// 栈里是 (节点, 下一个要返回的键的位置), 位置之前的键和孩子都已经访问过
+struct Range<'a, K, V, Q: ?Sized, R> {
+    stack: Vec<(&'a BTreeNode<K, V>, usize)>,
+    range: R,
+    _query: PhantomData<fn(&Q)>,
+}
+
+impl<'a, K, V, Q, R> Iterator for Range<'a, K, V, Q, R>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            let (node, index) = (*node, *i);
+            if index == node.keys.len() {
+                // 这个节点访问完了, 回到父节点
+                self.stack.pop();
+                continue;
+            }
+            if !below_end(&self.range, node.keys[index].borrow()) {
+                self.stack.clear();
+                return None;
+            }
+            *i += 1;
+            // 下一个是右边孩子子树中最小的键, 一路向左压栈
+            if !node.is_leaf() {
+                let mut child = &node.children[index + 1];
+                loop {
+                    self.stack.push((child, 0));
+                    if child.is_leaf() {
+                        break;
+                    }
+                    child = &child.children[0];
+                }
+            }
+            return Some((&node.keys[index], &node.values[index]));
+        }
+    }
+}
+
+// B+ 树
+// 叶子都放在树的 LeafArena 里, 节点和链表都用下标指向叶子, 不需要裸指针
+
+#[derive(Debug)]
+struct Leaf<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+    next: Option<usize>, // 右边相邻的叶子在 LeafArena 中的下标
+}
+
+impl<K, V> Leaf<K, V> {
+    fn empty() -> Self {
+        Leaf {
+            keys: Vec::new(),
+            values: Vec::new(),
+            next: None,
+        }
+    }
+}
+
+/// 存放所有叶子, 合并掉的叶子留下的空位记在 free 里, 之后分裂时复用
+#[derive(Debug)]
+struct LeafArena<K, V> {
+    slots: Vec<Leaf<K, V>>,
+    free: Vec<usize>,
+}
+
+impl<K, V> LeafArena<K, V> {
+    fn alloc(&mut self, leaf: Leaf<K, V>) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.slots[id] = leaf;
+                id
+            }
+            None => {
+                self.slots.push(leaf);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// 取出叶子的内容, 把位置还回去
+    fn release(&mut self, id: usize) -> Leaf<K, V> {
+        self.free.push(id);
+        std::mem::replace(&mut self.slots[id], Leaf::empty())
+    }
+}
+
+#[derive(Debug)]
+struct Internal<K> {
+    keys: Vec<K>, // keys[i] 把 children[i] 和 children[i + 1] 分开: 左边都小于它, 右边都不小于它
+    children: Vec<BPlusNode<K>>,
+}
+
+#[derive(Debug)]
+enum BPlusNode<K> {
+    Leaf(usize), // 叶子在 LeafArena 中的下标
+    Internal(Box<Internal<K>>),
+}
+
+#[derive(Debug)]
+struct BPlusTree<K, V> {
+    root: BPlusNode<K>,
+    leaves: LeafArena<K, V>,
+    min_degree: usize,
+    len: usize,
+}
+
+/// 孩子分裂后交给父节点的分隔键和右半部分
+type Split<K> = Option<(K, BPlusNode<K>)>;
+
+impl<K, V> BPlusTree<K, V>
+where
+    K: Ord + Clone,
+{
+    fn new(min_degree: usize) -> Self {
+        assert!(min_degree >= 2, "最小度数至少为 2");
+        BPlusTree {
+            root: BPlusNode::Leaf(0),
+            leaves: LeafArena {
+                slots: vec![Leaf::empty()],
+                free: Vec::new(),
+            },
+            min_degree,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let leaf = &self.leaves.slots[self.root.find_leaf(key)];
+        let i = leaf.keys.binary_search_by(|k| k.borrow().cmp(key)).ok()?;
+        Some(&leaf.values[i])
+    }
+
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// 插入键值对, 键已存在时替换并返回旧值
+    /// 先插到叶子, 回溯时溢出的节点一分为二, 根分裂时树长高一层
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (old, split) = self
+            .root
+            .insert(key, value, self.min_degree, &mut self.leaves);
+        if let Some((key, right)) = split {
+            let left = std::mem::replace(
+                &mut self.root,
+                BPlusNode::Internal(Box::new(Internal {
+                    keys: Vec::new(),
+                    children: Vec::new(),
+                })),
+            );
+            if let BPlusNode::Internal(root) = &mut self.root {
+                root.keys.push(key);
+                root.children.extend([left, right]);
+            }
+        }
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// 删除键, 返回对应的值
+    /// 回溯时不足 t - 1 个键的孩子向兄弟借, 借不到就合并, 根只剩一个孩子时树变矮一层
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = self.root.remove(key, self.min_degree, &mut self.leaves);
+        if let BPlusNode::Internal(root) = &mut self.root
+            && root.keys.is_empty()
+        {
+            self.root = root.children.pop().unwrap();
+        }
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn iter(&self) -> LeafRange<'_, K, V, K, (Bound<&K>, Bound<&K>)> {
+        self.range((Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// 找到下界所在的叶子, 之后只沿着叶子链表往右走
+    fn range<Q, R>(&self, range: R) -> LeafRange<'_, K, V, Q, R>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let leaf = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => self.root.find_leaf(start),
+            Bound::Unbounded => self.root.first_leaf(),
+        };
+        let index = self.leaves.slots[leaf]
+            .keys
+            .partition_point(|k| !above_start(&range, k.borrow()));
+        LeafRange {
+            leaves: &self.leaves.slots,
+            leaf: Some(leaf),
+            index,
+            range,
+            _query: PhantomData,
+        }
+    }
+}
+
+impl<K> BPlusNode<K>
+where
+    K: Ord + Clone,
+{
+    fn len<V>(&self, leaves: &LeafArena<K, V>) -> usize {
+        match self {
+            BPlusNode::Leaf(leaf) => leaves.slots[*leaf].keys.len(),
+            BPlusNode::Internal(node) => node.keys.len(),
+        }
+    }
+
+    /// key 所在 (或应该在) 的叶子
+    fn find_leaf<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self;
+        loop {
+            match node {
+                BPlusNode::Leaf(leaf) => return *leaf,
+                BPlusNode::Internal(internal) => node = &internal.children[internal.route(key)],
+            }
+        }
+    }
+
+    fn first_leaf(&self) -> usize {
+        let mut node = self;
+        loop {
+            match node {
+                BPlusNode::Leaf(leaf) => return *leaf,
+                BPlusNode::Internal(internal) => node = &internal.children[0],
+            }
+        }
+    }
+
+    /// 插入后节点超过 2t - 1 个键就分裂, 把分隔键和右半部分交给父节点
+    fn insert<V>(
+        &mut self,
+        key: K,
+        value: V,
+        t: usize,
+        leaves: &mut LeafArena<K, V>,
+    ) -> (Option<V>, Split<K>) {
+        match self {
+            BPlusNode::Leaf(id) => {
+                let leaf = &mut leaves.slots[*id];
+                let i = match leaf.keys.binary_search(&key) {
+                    Ok(i) => return (Some(std::mem::replace(&mut leaf.values[i], value)), None),
+                    Err(i) => i,
+                };
+                leaf.keys.insert(i, key);
+                leaf.values.insert(i, value);
+                if leaf.keys.len() < 2 * t {
+                    return (None, None);
+                }
+                // 叶子对半分, 右半部分的第一个键复制一份作为分隔键, 新叶子接进链表
+                let right = Leaf {
+                    keys: leaf.keys.split_off(t),
+                    values: leaf.values.split_off(t),
+                    next: leaf.next,
+                };
+                let separator = right.keys[0].clone();
+                let right = leaves.alloc(right);
+                leaves.slots[*id].next = Some(right);
+                (None, Some((separator, BPlusNode::Leaf(right))))
+            }
+            BPlusNode::Internal(node) => {
+                let i = node.route(&key);
+                let (old, split) = node.children[i].insert(key, value, t, leaves);
+                if let Some((separator, right)) = split {
+                    node.keys.insert(i, separator);
+                    node.children.insert(i + 1, right);
+                }
+                if node.keys.len() < 2 * t {
+                    return (old, None);
+                }
+                // 内部节点分裂时中间的键上移, 不在两半中保留
+                let right = Box::new(Internal {
+                    keys: node.keys.split_off(t + 1),
+                    children: node.children.split_off(t + 1),
+                });
+                let separator = node.keys.pop().unwrap();
+                (old, Some((separator, BPlusNode::Internal(right))))
+            }
+        }
+    }
+
+    fn remove<Q, V>(&mut self, key: &Q, t: usize, leaves: &mut LeafArena<K, V>) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self {
+            BPlusNode::Leaf(id) => {
+                let leaf = &mut leaves.slots[*id];
+                let i = leaf.keys.binary_search_by(|k| k.borrow().cmp(key)).ok()?;
+                leaf.keys.remove(i);
+                Some(leaf.values.remove(i))
+            }
+            BPlusNode::Internal(node) => {
+                // 分隔键可能已经不在叶子里了, 但仍然能正确地分开左右, 所以不需要更新
+                let i = node.route(key);
+                let removed = node.children[i].remove(key, t, leaves)?;
+                if node.children[i].len(leaves) < t - 1 {
+                    node.fix_underflow(i, t, leaves);
+                }
+                Some(removed)
+            }
+        }
+    }
+}
+
+impl<K> Internal<K>
+where
+    K: Ord + Clone,
+{
+    /// key 应该进入的孩子: 不大于 key 的分隔键个数
+    fn route<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.keys.partition_point(|k| k.borrow() <= key)
+    }
+
+    /// 第 i 个孩子只剩 t - 2 个键: 先向左右兄弟借, 借不到就和兄弟合并
+    fn fix_underflow<V>(&mut self, i: usize, t: usize, leaves: &mut LeafArena<K, V>) {
+        if i > 0 && self.children[i - 1].len(leaves) >= t {
+            self.borrow_from_left(i, leaves);
+        } else if i + 1 < self.children.len() && self.children[i + 1].len(leaves) >= t {
+            self.borrow_from_right(i, leaves);
+        } else if i + 1 < self.children.len() {
+            self.merge(i, leaves);
+        } else {
+            self.merge(i - 1, leaves);
+        }
+    }
+
+    fn borrow_from_left<V>(&mut self, i: usize, leaves: &mut LeafArena<K, V>) {
+        let (left, right) = self.children.split_at_mut(i);
+        match (&mut left[i - 1], &mut right[0]) {
+            // 叶子: 左兄弟的最后一项直接移过来, 它成为新的分隔键
+            (BPlusNode::Leaf(left), BPlusNode::Leaf(child)) => {
+                let left = &mut leaves.slots[*left];
+                let (key, value) = (left.keys.pop().unwrap(), left.values.pop().unwrap());
+                let child = &mut leaves.slots[*child];
+                child.keys.insert(0, key);
+                child.values.insert(0, value);
+                self.keys[i - 1] = child.keys[0].clone();
+            }
+            // 内部节点: 和 B 树一样经过父节点旋转
+            (BPlusNode::Internal(left), BPlusNode::Internal(child)) => {
+                let key = std::mem::replace(&mut self.keys[i - 1], left.keys.pop().unwrap());
+                child.keys.insert(0, key);
+                child.children.insert(0, left.children.pop().unwrap());
+            }
+            _ => unreachable!("兄弟节点在同一层"),
+        }
+    }
+
+    fn borrow_from_right<V>(&mut self, i: usize, leaves: &mut LeafArena<K, V>) {
+        let (left, right) = self.children.split_at_mut(i + 1);
+        match (&mut left[i], &mut right[0]) {
+            (BPlusNode::Leaf(child), BPlusNode::Leaf(right)) => {
+                let right = &mut leaves.slots[*right];
+                let (key, value) = (right.keys.remove(0), right.values.remove(0));
+                self.keys[i] = right.keys[0].clone();
+                let child = &mut leaves.slots[*child];
+                child.keys.push(key);
+                child.values.push(value);
+            }
+            (BPlusNode::Internal(child), BPlusNode::Internal(right)) => {
+                let key = std::mem::replace(&mut self.keys[i], right.keys.remove(0));
+                child.keys.push(key);
+                child.children.push(right.children.remove(0));
+            }
+            _ => unreachable!("兄弟节点在同一层"),
+        }
+    }
+
+    /// 把第 i + 1 个孩子并入第 i 个孩子
+    /// 叶子合并时直接丢掉分隔键, 并把链表跳过被合并的叶子, 它的位置还给 LeafArena
+    fn merge<V>(&mut self, i: usize, leaves: &mut LeafArena<K, V>) {
+        let right = self.children.remove(i + 1);
+        let separator = self.keys.remove(i);
+        match (&mut self.children[i], right) {
+            (BPlusNode::Leaf(child), BPlusNode::Leaf(right)) => {
+                let right = leaves.release(right);
+                let child = &mut leaves.slots[*child];
+                child.keys.extend(right.keys);
+                child.values.extend(right.values);
+                child.next = right.next;
+            }
+            (BPlusNode::Internal(child), BPlusNode::Internal(right)) => {
+                child.keys.push(separator);
+                child.keys.extend(right.keys);
+                child.children.extend(right.children);
+            }
+            _ => unreachable!("兄弟节点在同一层"),
+        }
+    }
+}
+
+/// B+ 树的范围迭代器, 只在叶子链表上移动
+struct LeafRange<'a, K, V, Q: ?Sized, R> {
+    leaves: &'a [Leaf<K, V>],
+    leaf: Option<usize>,
+    index: usize,
+    range: R,
+    _query: PhantomData<fn(&Q)>,
+}
+
+impl<'a, K, V, Q, R> Iterator for LeafRange<'a, K, V, Q, R>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let leaves = self.leaves;
+        let mut leaf = &leaves[self.leaf?];
+        while self.index == leaf.keys.len() {
+            let next = leaf.next?;
+            leaf = &leaves[next];
+            self.leaf = Some(next);
+            self.index = 0;
+        }
+        let key = &leaf.keys[self.index];
+        if !below_end(&self.range, key.borrow()) {
+            self.leaf = None;
+            return None;
+        }
+        let value = &leaf.values[self.index];
+        self.index += 1;
+        Some((key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // 简单的线性同余生成器
+    fn lcg(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
+    // 检查 B 树性质, 返回子树的高度
+    // lower/upper 是祖先给出的开区间边界
+    fn check_btree<K: Ord, V>(
+        node: &BTreeNode<K, V>,
+        t: usize,
+        is_root: bool,
+        lower: Option<&K>,
+        upper: Option<&K>,
+    ) -> usize {
+        assert!(node.keys.len() < 2 * t);
+        assert!(is_root || node.keys.len() >= t - 1);
+        assert_eq!(node.keys.len(), node.values.len());
+        assert!(node.keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(lower.is_none_or(|l| node.keys[0] > *l));
+        assert!(upper.is_none_or(|u| *node.keys.last().unwrap() < *u));
+        if node.is_leaf() {
+            return 1;
+        }
+        assert_eq!(node.children.len(), node.keys.len() + 1);
+        let heights: Vec<usize> = (0..node.children.len())
+            .map(|i| {
+                let lower = if i == 0 {
+                    lower
+                } else {
+                    Some(&node.keys[i - 1])
+                };
+                let upper = node.keys.get(i).or(upper);
+                check_btree(&node.children[i], t, false, lower, upper)
+            })
+            .collect();
+        // 所有叶子在同一层
+        assert!(heights.windows(2).all(|w| w[0] == w[1]));
+        heights[0] + 1
+    }
+
+    // 检查 B+ 树性质, 返回子树的高度, 同时按从左到右的顺序收集叶子
+    fn check_bplus<K: Ord + Clone, V>(
+        tree: &BPlusTree<K, V>,
+        node: &BPlusNode<K>,
+        is_root: bool,
+        lower: Option<&K>,
+        upper: Option<&K>,
+        leaves: &mut Vec<usize>,
+    ) -> usize {
+        let t = tree.min_degree;
+        assert!(node.len(&tree.leaves) < 2 * t);
+        assert!(is_root || node.len(&tree.leaves) >= t - 1);
+        match node {
+            BPlusNode::Leaf(id) => {
+                let leaf = &tree.leaves.slots[*id];
+                assert_eq!(leaf.keys.len(), leaf.values.len());
+                assert!(leaf.keys.windows(2).all(|w| w[0] < w[1]));
+                // 左边界是闭区间: 分隔键等于右边孩子中的最小键
+                assert!(leaf.keys.iter().all(|k| lower.is_none_or(|l| k >= l)));
+                assert!(leaf.keys.iter().all(|k| upper.is_none_or(|u| k < u)));
+                leaves.push(*id);
+                1
+            }
+            BPlusNode::Internal(internal) => {
+                assert_eq!(internal.children.len(), internal.keys.len() + 1);
+                assert!(internal.keys.windows(2).all(|w| w[0] < w[1]));
+                let heights: Vec<usize> = (0..internal.children.len())
+                    .map(|i| {
+                        let lower = if i == 0 {
+                            lower
+                        } else {
+                            Some(&internal.keys[i - 1])
+                        };
+                        let upper = internal.keys.get(i).or(upper);
+                        check_bplus(tree, &internal.children[i], false, lower, upper, leaves)
+                    })
+                    .collect();
+                assert!(heights.windows(2).all(|w| w[0] == w[1]));
+                heights[0] + 1
+            }
+        }
+    }
+
+    fn check_bplus_tree<K: Ord + Clone, V>(tree: &BPlusTree<K, V>) {
+        let mut leaves = Vec::new();
+        check_bplus(tree, &tree.root, true, None, None, &mut leaves);
+        // 叶子链表和从上往下找到的叶子顺序一致
+        for w in leaves.windows(2) {
+            assert_eq!(tree.leaves.slots[w[0]].next, Some(w[1]));
+        }
+        assert!(tree.leaves.slots[*leaves.last().unwrap()].next.is_none());
+        // 每个位置要么挂在树上, 要么在空闲列表里
+        let mut used = leaves.clone();
+        used.extend(&tree.leaves.free);
+        used.sort_unstable();
+        assert!(used.iter().copied().eq(0..tree.leaves.slots.len()));
+    }
+
+    #[test]
+    fn test_btree_basic() {
+        let mut tree = BTree::new(2);
+        assert!(tree.is_empty());
+        for (i, key) in [10, 20, 5, 6, 12, 30, 7, 17].into_iter().enumerate() {
+            assert_eq!(tree.insert(key, i), None);
+        }
+        assert_eq!(tree.insert(6, 100), Some(3));
+        assert_eq!(tree.len(), 8);
+        assert_eq!(tree.get(&6), Some(&100));
+        assert!(tree.contains_key(&17));
+        assert!(!tree.contains_key(&18));
+        check_btree(&tree.root, 2, true, None, None);
+
+        let keys: Vec<i32> = tree.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [5, 6, 7, 10, 12, 17, 20, 30]);
+        assert_eq!(tree.remove(&10), Some(0));
+        assert_eq!(tree.remove(&10), None);
+        check_btree(&tree.root, 2, true, None, None);
+        assert_eq!(tree.len(), 7);
+    }
+
+    #[test]
+    fn test_btree_random() {
+        for t in [2, 3, 5] {
+            let mut tree = BTree::new(t);
+            let mut model = BTreeMap::new();
+            let mut seed = t as u64;
+            for _ in 0..5000 {
+                let key = lcg(&mut seed) % 1000;
+                if lcg(&mut seed).is_multiple_of(3) {
+                    assert_eq!(tree.remove(&key), model.remove(&key));
+                } else {
+                    assert_eq!(tree.insert(key, key * 2), model.insert(key, key * 2));
+                }
+                assert_eq!(tree.len(), model.len());
+            }
+            check_btree(&tree.root, t, true, None, None);
+            assert!(tree.iter().eq(model.iter()));
+            for key in 0..1000 {
+                assert_eq!(tree.get(&key), model.get(&key));
+            }
+            // 全部删掉, 树应该缩回一个空的叶子
+            let keys: Vec<u64> = model.keys().copied().collect();
+            for key in keys {
+                assert_eq!(tree.remove(&key), model.remove(&key));
+                check_btree(&tree.root, t, true, None, None);
+            }
+            assert!(tree.is_empty());
+            assert!(tree.root.is_leaf() && tree.root.keys.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_btree_range() {
+        let mut tree = BTree::new(3);
+        let mut model = BTreeMap::new();
+        let mut seed = 11;
+        for _ in 0..500 {
+            let key = lcg(&mut seed) % 300;
+            tree.insert(key, ());
+            model.insert(key, ());
+        }
+        for _ in 0..100 {
+            let a = lcg(&mut seed) % 320;
+            let b = a + lcg(&mut seed) % 50;
+            assert!(tree.range(a..b).eq(model.range(a..b)));
+            assert!(tree.range(a..=b).eq(model.range(a..=b)));
+            assert!(tree.range(..b).eq(model.range(..b)));
+            assert!(tree.range(a..).eq(model.range(a..)));
+            let bounds = (Bound::Excluded(a), Bound::Included(b));
+            assert!(tree.range(bounds).eq(model.range(bounds)));
+        }
+        // 借助 Borrow 用 &str 查 String 键
+        let mut names = BTree::new(2);
+        for name in ["carol", "alice", "dave", "bob", "erin"] {
+            names.insert(name.to_string(), name.len());
+        }
+        assert_eq!(names.get("dave"), Some(&4));
+        let keys: Vec<&String> = names
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, ["bob", "carol"]);
+        assert_eq!(names.remove("alice"), Some(5));
+    }
+
+    #[test]
+    fn test_bplus_tree_random() {
+        for t in [2, 3, 5] {
+            let mut tree = BPlusTree::new(t);
+            let mut model = BTreeMap::new();
+            let mut seed = 100 + t as u64;
+            for step in 0..5000 {
+                let key = lcg(&mut seed) % 1000;
+                if lcg(&mut seed).is_multiple_of(3) {
+                    assert_eq!(tree.remove(&key), model.remove(&key));
+                } else {
+                    assert_eq!(tree.insert(key, step), model.insert(key, step));
+                }
+                assert_eq!(tree.len(), model.len());
+            }
+            check_bplus_tree(&tree);
+            assert!(tree.iter().eq(model.iter()));
+            for key in 0..1000 {
+                assert_eq!(tree.get(&key), model.get(&key));
+                assert_eq!(tree.contains_key(&key), model.contains_key(&key));
+            }
+            let keys: Vec<u64> = model.keys().copied().collect();
+            for key in keys {
+                assert_eq!(tree.remove(&key), model.remove(&key));
+                check_bplus_tree(&tree);
+            }
+            assert!(tree.is_empty());
+            assert!(tree.iter().next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_bplus_tree_range() {
+        let mut tree = BPlusTree::new(2);
+        let mut model = BTreeMap::new();
+        let mut seed = 12;
+        for _ in 0..500 {
+            let key = lcg(&mut seed) % 300;
+            tree.insert(key, key);
+            model.insert(key, key);
+        }
+        for _ in 0..100 {
+            let a = lcg(&mut seed) % 320;
+            let b = a + lcg(&mut seed) % 50;
+            assert!(tree.range(a..b).eq(model.range(a..b)));
+            assert!(tree.range(a..=b).eq(model.range(a..=b)));
+            assert!(tree.range(..b).eq(model.range(..b)));
+            assert!(tree.range(a..).eq(model.range(a..)));
+            let bounds = (Bound::Excluded(a), Bound::Included(b));
+            assert!(tree.range(bounds).eq(model.range(bounds)));
+        }
+        // 有序输入也能保持所有叶子同层
+        let mut sorted = BPlusTree::new(4);
+        for key in 0..10_000 {
+            sorted.insert(key, ());
+        }
+        check_bplus_tree(&sorted);
+        assert_eq!(sorted.range(9990..).count(), 10);
+    }
+}
@@ -1,26 +1,12 @@
 // 基本的广度优先算法
 
-use std::collections::VecDeque;
+mod common;
 
-// Define a graph
-struct Graph {
-    adj: Vec<Vec<usize>>, // 邻接表表示图
-}
+use common::graph::Graph;
+use std::collections::VecDeque;
 
+// 图的结构和 add_edge, 度数, 转置等基本操作在 common/graph.rs 里, 和 DFS 共用
 impl Graph {
-    // Create a new graph with n vertices
-    fn new(n: usize) -> Self {
-        Graph {
-            adj: vec![vec![]; n],
-        }
-    }
-
-    // Add an edge to the graph
-    fn add_edge(&mut self, src: usize, dest: usize) {
-        self.adj[src].push(dest);
-        self.adj[dest].push(src); // 对于无向图, 我们需要双向边
-    }
-
     // Perform a breadth-first search on the graph, return the order of visited nodes
     fn bfs_with_return(&self, start: usize) -> Vec<usize> {
         //TODO
@@ -57,12 +43,178 @@ impl Graph {
         // 返回访问顺序
         visit_order
     }
+
+    // 从单个起点出发的距离 (边数), 不可达的节点为 None
+    fn bfs_distances(&self, start: usize) -> Vec<Option<usize>> {
+        self.multi_source_bfs(&[start])
+            .into_iter()
+            .map(|reached| reached.map(|r| r.distance))
+            .collect()
+    }
+
+    // BFS 树中每个节点的父节点, 起点和不可达的节点为 None, 配合 build_path 还原最短路径
+    fn bfs_parents(&self, start: usize) -> Vec<Option<usize>> {
+        self.multi_source_bfs(&[start])
+            .into_iter()
+            .map(|reached| reached.and_then(|r| r.parent))
+            .collect()
+    }
+
+    // 边数最少的路径, 包括两端, 不可达时为 None
+    fn shortest_path(&self, start: usize, target: usize) -> Option<Vec<usize>> {
+        build_path(&self.bfs_parents(start), start, target)
+    }
+
+    // 多源 BFS: 所有起点的距离都是 0, 一开始就全部入队
+    // 之后和普通 BFS 一样按层扩展, 每个节点第一次被访问时就确定了离它最近的起点
+    // 相当于加一个虚拟的超级起点连向所有起点, 再做一次单源 BFS
+    fn multi_source_bfs(&self, sources: &[usize]) -> Vec<Option<Reached>> {
+        let mut reached = vec![None; self.adj.len()];
+        let mut queue = VecDeque::new();
+        for &source in sources {
+            // 重复的起点只算一次
+            if reached[source].is_none() {
+                reached[source] = Some(Reached {
+                    distance: 0,
+                    parent: None,
+                    source,
+                });
+                queue.push_back(source);
+            }
+        }
+        while let Some(current) = queue.pop_front() {
+            let Reached {
+                distance, source, ..
+            } = reached[current].unwrap();
+            for &neighbor in &self.adj[current] {
+                if reached[neighbor].is_none() {
+                    reached[neighbor] = Some(Reached {
+                        distance: distance + 1,
+                        parent: Some(current),
+                        source,
+                    });
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        reached
+    }
+}
+
+// 多源 BFS 中一个节点被访问到时的信息
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Reached {
+    distance: usize,       // 到最近的起点的边数
+    parent: Option<usize>, // BFS 树中的父节点, 起点自己为 None
+    source: usize,         // 最近的起点, 距离相同时取先入队的那个
+}
+
+// 根据父节点数组还原从 start 到 target 的路径
+// 起点和不可达的节点父节点都是 None, 所以需要知道起点才能区分这两种情况
+fn build_path(parents: &[Option<usize>], start: usize, target: usize) -> Option<Vec<usize>> {
+    let mut path = vec![target];
+    let mut current = target;
+    while current != start {
+        current = parents[current]?;
+        path.push(current);
+    }
+    path.reverse();
+    Some(path)
+}
+
+// 0-1 BFS
+// 边权只有 0 和 1 时不需要 Dijkstra 的堆, 用双端队列就够了:
+// 沿权为 0 的边到达的节点和当前节点在同一层, 放到队头; 权为 1 的放到队尾
+// 这样队列中的距离始终单调不减, 且最多只有两种取值, 整体是 O(V + E)
+
+// 带 0/1 权重的边
+#[derive(Clone, Debug)]
+struct Edge {
+    node: usize,
+    weight: usize,
+}
+
+struct ZeroOneGraph {
+    adj: Vec<Vec<Edge>>,
+    directed: bool, // 是否是有向图
+}
+
+impl ZeroOneGraph {
+    fn new(n: usize) -> Self {
+        ZeroOneGraph {
+            adj: vec![vec![]; n],
+            directed: false,
+        }
+    }
+
+    // 有向图: add_edge 只添加 src -> dest 一个方向, 和 Graph::new_directed 一样
+    fn new_directed(n: usize) -> Self {
+        ZeroOneGraph {
+            adj: vec![vec![]; n],
+            directed: true,
+        }
+    }
+
+    // 权重只能是 0 或 1
+    fn add_edge(&mut self, src: usize, dest: usize, weight: usize) {
+        assert!(weight <= 1, "0-1 BFS 的边权只能是 0 或 1, 实际为 {weight}");
+        self.adj[src].push(Edge { node: dest, weight });
+        if !self.directed {
+            self.adj[dest].push(Edge { node: src, weight }); // 无向图需要双向添加
+        }
+    }
+
+    // 加一个没有边的新节点, 返回它的编号, 和 Graph::add_node 一样
+    fn add_node(&mut self) -> usize {
+        self.adj.push(vec![]);
+        self.adj.len() - 1
+    }
+
+    // 返回每个节点的最短距离和最短路径上的前驱, 不可达的节点都为 None
+    fn zero_one_bfs(&self, start: usize) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+        let mut distances: Vec<Option<usize>> = vec![None; self.adj.len()];
+        let mut previous = vec![None; self.adj.len()];
+        let mut deque = VecDeque::new();
+
+        distances[start] = Some(0);
+        deque.push_back((start, 0));
+
+        while let Some((current, distance)) = deque.pop_front() {
+            // 一个节点可能先被权为 1 的边放进队尾, 之后又通过权为 0 的边得到更短的距离
+            // 队尾那份已经过时, 跳过
+            if distances[current] < Some(distance) {
+                continue;
+            }
+            for edge in &self.adj[current] {
+                let next_distance = distance + edge.weight;
+                if distances[edge.node].is_none_or(|d| next_distance < d) {
+                    distances[edge.node] = Some(next_distance);
+                    previous[edge.node] = Some(current);
+                    if edge.weight == 0 {
+                        deque.push_front((edge.node, next_distance));
+                    } else {
+                        deque.push_back((edge.node, next_distance));
+                    }
+                }
+            }
+        }
+
+        (distances, previous)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // 简单的线性同余生成器
+    fn lcg(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
     #[test]
     fn test_bfs_all_nodes_visited() {
         let mut graph = Graph::new(5);
@@ -106,4 +258,369 @@ mod tests {
         let visited_order = graph.bfs_with_return(0);
         assert_eq!(visited_order, vec![0]);
     }
+
+    #[test]
+    fn test_bfs_distances_and_paths() {
+        // 0 - 1 - 2 - 3
+        // |       |
+        // 4 ----- 5     6 (孤立)
+        let mut graph = Graph::new(7);
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        graph.add_edge(2, 3);
+        graph.add_edge(0, 4);
+        graph.add_edge(4, 5);
+        graph.add_edge(5, 2);
+
+        assert_eq!(
+            graph.bfs_distances(0),
+            vec![Some(0), Some(1), Some(2), Some(3), Some(1), Some(2), None]
+        );
+
+        let parents = graph.bfs_parents(0);
+        assert_eq!(parents[0], None);
+        assert_eq!(parents[6], None);
+        assert_eq!(parents[2], Some(1));
+        assert_eq!(parents[5], Some(4));
+
+        assert_eq!(build_path(&parents, 0, 3), Some(vec![0, 1, 2, 3]));
+        assert_eq!(build_path(&parents, 0, 0), Some(vec![0]));
+        assert_eq!(build_path(&parents, 0, 6), None);
+        assert_eq!(graph.shortest_path(3, 4), Some(vec![3, 2, 5, 4]));
+        assert_eq!(graph.shortest_path(6, 0), None);
+
+        // 路径长度总是等于距离
+        for start in 0..7 {
+            let distances = graph.bfs_distances(start);
+            for (target, &distance) in distances.iter().enumerate() {
+                let path = graph.shortest_path(start, target);
+                assert_eq!(path.map(|p| p.len() - 1), distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_source_bfs() {
+        // 一条街上的路口 0..=9, 便利店在 2 和 7, 路口 10 不通
+        let mut graph = Graph::new(11);
+        for i in 0..9 {
+            graph.add_edge(i, i + 1);
+        }
+        let reached = graph.multi_source_bfs(&[2, 7, 7]);
+        let distances: Vec<_> = reached.iter().map(|r| r.map(|r| r.distance)).collect();
+        let nearest: Vec<_> = reached.iter().map(|r| r.map(|r| r.source)).collect();
+        assert_eq!(
+            distances,
+            vec![
+                Some(2),
+                Some(1),
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(2),
+                Some(1),
+                Some(0),
+                Some(1),
+                Some(2),
+                None
+            ]
+        );
+        // 每个路口都归到离它更近的那家店
+        assert_eq!(
+            nearest,
+            vec![
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(7),
+                Some(7),
+                Some(7),
+                Some(7),
+                Some(7),
+                None
+            ]
+        );
+        let parents: Vec<_> = reached.iter().map(|r| r.and_then(|r| r.parent)).collect();
+        assert_eq!(build_path(&parents, 7, 9), Some(vec![7, 8, 9]));
+        assert_eq!(build_path(&parents, 2, 0), Some(vec![2, 1, 0]));
+
+        // 与逐个起点做单源 BFS 取最小值的结果一致
+        let sources = [0, 9];
+        let expected: Vec<_> = (0..11)
+            .map(|v| {
+                sources
+                    .iter()
+                    .filter_map(|&s| graph.bfs_distances(s)[v])
+                    .min()
+            })
+            .collect();
+        let actual: Vec<_> = graph
+            .multi_source_bfs(&sources)
+            .iter()
+            .map(|r| r.map(|r| r.distance))
+            .collect();
+        assert_eq!(actual, expected);
+
+        // 离两个起点一样远时, 归到先入队的那个
+        let mut line = Graph::new(3);
+        line.add_edge(0, 1);
+        line.add_edge(1, 2);
+        assert_eq!(line.multi_source_bfs(&[0, 2])[1].unwrap().source, 0);
+        assert_eq!(line.multi_source_bfs(&[2, 0])[1].unwrap().source, 2);
+
+        // 没有起点时所有节点都不可达
+        assert!(graph.multi_source_bfs(&[]).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_zero_one_bfs() {
+        let mut graph = ZeroOneGraph::new(6);
+        graph.add_edge(0, 1, 1);
+        graph.add_edge(0, 2, 0);
+        graph.add_edge(2, 3, 1);
+        graph.add_edge(3, 1, 0);
+        graph.add_edge(1, 4, 1);
+
+        let (distances, previous) = graph.zero_one_bfs(0);
+        assert_eq!(
+            distances,
+            vec![Some(0), Some(1), Some(0), Some(1), Some(2), None]
+        );
+        assert_eq!(build_path(&previous, 0, 4).unwrap().len(), 3);
+        assert_eq!(build_path(&previous, 0, 3), Some(vec![0, 2, 3]));
+        assert_eq!(build_path(&previous, 0, 5), None);
+
+        // 先通过权为 1 的边到达, 之后又找到权为 0 的更短路线
+        let mut graph = ZeroOneGraph::new(4);
+        graph.add_edge(0, 3, 1);
+        graph.add_edge(0, 1, 0);
+        graph.add_edge(1, 2, 0);
+        graph.add_edge(2, 3, 0);
+        let (distances, previous) = graph.zero_one_bfs(0);
+        assert_eq!(distances[3], Some(0));
+        assert_eq!(build_path(&previous, 0, 3), Some(vec![0, 1, 2, 3]));
+
+        // 新加的节点一开始不可达, 连上边之后才有距离
+        let v = graph.add_node();
+        assert_eq!(v, 4);
+        assert_eq!(graph.zero_one_bfs(0).0[v], None);
+        graph.add_edge(3, v, 1);
+        assert_eq!(graph.zero_one_bfs(0).0[v], Some(1));
+    }
+
+    #[test]
+    fn test_directed_zero_one_bfs() {
+        // 0 -> 1 免费, 回头只能走 1 -> 2 -> 0 这条付费的路
+        let mut graph = ZeroOneGraph::new_directed(4);
+        graph.add_edge(0, 1, 0);
+        graph.add_edge(1, 2, 1);
+        graph.add_edge(2, 0, 1);
+        graph.add_edge(3, 0, 0);
+
+        let (distances, _) = graph.zero_one_bfs(0);
+        assert_eq!(distances, vec![Some(0), Some(0), Some(1), None]);
+        let (distances, previous) = graph.zero_one_bfs(1);
+        assert_eq!(distances, vec![Some(2), Some(0), Some(1), None]);
+        assert_eq!(build_path(&previous, 1, 0), Some(vec![1, 2, 0]));
+        let (distances, _) = graph.zero_one_bfs(3);
+        assert_eq!(distances, vec![Some(0), Some(0), Some(1), Some(0)]);
+
+        // 同样的边在无向图里可以原路返回
+        let mut undirected = ZeroOneGraph::new(4);
+        undirected.add_edge(0, 1, 0);
+        undirected.add_edge(1, 2, 1);
+        undirected.add_edge(2, 0, 1);
+        undirected.add_edge(3, 0, 0);
+        let (distances, _) = undirected.zero_one_bfs(1);
+        assert_eq!(distances, vec![Some(0), Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_one_bfs_rejects_other_weights() {
+        let mut graph = ZeroOneGraph::new(2);
+        graph.add_edge(0, 1, 2);
+    }
+
+    #[test]
+    fn test_grid_flooding() {
+        // 从所有 S 同时向外蔓延, 每格标上到最近的 S 的步数, 墙挡住蔓延
+        let grid = ["S...#", ".##.#", "...#S", "#...."];
+        let (rows, cols) = (grid.len(), grid[0].len());
+        let tile = |r: usize, c: usize| grid[r].as_bytes()[c];
+        let open = |r: usize, c: usize| tile(r, c) != b'#';
+        let mut graph = Graph::new(rows * cols);
+        let mut sources = vec![];
+        for r in 0..rows {
+            for c in 0..cols {
+                if !open(r, c) {
+                    continue;
+                }
+                if tile(r, c) == b'S' {
+                    sources.push(r * cols + c);
+                }
+                if r + 1 < rows && open(r + 1, c) {
+                    graph.add_edge(r * cols + c, (r + 1) * cols + c);
+                }
+                if c + 1 < cols && open(r, c + 1) {
+                    graph.add_edge(r * cols + c, r * cols + c + 1);
+                }
+            }
+        }
+        let reached = graph.multi_source_bfs(&sources);
+        let flooded: Vec<String> = (0..rows)
+            .map(|r| {
+                (0..cols)
+                    .map(|c| match reached[r * cols + c] {
+                        Some(reached) => char::from_digit(reached.distance as u32, 10).unwrap(),
+                        None => '#',
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(flooded, ["0123#", "1##4#", "234#0", "#4321"]);
+    }
+
+    #[test]
+    fn test_zero_one_bfs_on_terrain() {
+        // 在同一种地形里移动不花钱, 跨到另一种地形花 1, 求最少跨越几次
+        // 随机地形, 用反复松弛所有边直到不变的方法作对照
+        let (rows, cols) = (12, 15);
+        let mut seed = 7u64;
+        let mut terrain = vec![0; rows * cols];
+        for t in terrain.iter_mut() {
+            *t = lcg(&mut seed) % 3;
+        }
+        let mut graph = ZeroOneGraph::new(rows * cols);
+        let mut edges = vec![];
+        for r in 0..rows {
+            for c in 0..cols {
+                let v = r * cols + c;
+                for u in [
+                    (r + 1 < rows).then(|| v + cols),
+                    (c + 1 < cols).then(|| v + 1),
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    let weight = (terrain[v] != terrain[u]) as usize;
+                    graph.add_edge(v, u, weight);
+                    edges.push((v, u, weight));
+                }
+            }
+        }
+
+        let (distances, previous) = graph.zero_one_bfs(0);
+
+        let mut expected = vec![usize::MAX; rows * cols];
+        expected[0] = 0;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(a, b, weight) in &edges {
+                for (from, to) in [(a, b), (b, a)] {
+                    if expected[from] != usize::MAX && expected[from] + weight < expected[to] {
+                        expected[to] = expected[from] + weight;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            distances,
+            expected.into_iter().map(Some).collect::<Vec<_>>()
+        );
+
+        // 沿前驱还原的路径代价等于最短距离
+        let target = rows * cols - 1;
+        let path = build_path(&previous, 0, target).unwrap();
+        let cost: usize = path
+            .windows(2)
+            .map(|w| (terrain[w[0]] != terrain[w[1]]) as usize)
+            .sum();
+        assert_eq!(Some(cost), distances[target]);
+    }
+
+    #[test]
+    fn test_directed_bfs() {
+        // 0 -> 1 -> 2, 3 -> 1
+        let mut graph = Graph::new_directed(4);
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        graph.add_edge(3, 1);
+
+        // 只能沿着边的方向走
+        assert_eq!(graph.bfs_with_return(0), vec![0, 1, 2]);
+        assert_eq!(graph.bfs_with_return(2), vec![2]);
+        assert_eq!(
+            graph.bfs_distances(3),
+            vec![None, Some(1), Some(2), Some(0)]
+        );
+        assert_eq!(graph.shortest_path(2, 0), None);
+
+        // 转置之后方向全部反过来
+        let reversed = graph.reverse();
+        assert_eq!(reversed.bfs_with_return(2), vec![2, 1, 0, 3]);
+        assert_eq!(reversed.shortest_path(2, 3), Some(vec![2, 1, 3]));
+        assert_eq!(reversed.reverse().adj, graph.adj);
+
+        // 同样的边在无向图中可以双向走
+        let mut undirected = Graph::new(4);
+        undirected.add_edge(0, 1);
+        undirected.add_edge(1, 2);
+        undirected.add_edge(3, 1);
+        assert_eq!(undirected.bfs_with_return(2), vec![2, 1, 0, 3]);
+        assert_eq!(undirected.reverse().adj, undirected.adj);
+
+        // 邻居不是按编号顺序加入的, 转置后遍历顺序也不变
+        let mut undirected = Graph::new(3);
+        undirected.add_edge(0, 2);
+        undirected.add_edge(0, 1);
+        assert_eq!(undirected.bfs_with_return(0), vec![0, 2, 1]);
+        assert_eq!(undirected.reverse().adj, undirected.adj);
+        assert_eq!(undirected.reverse().bfs_with_return(0), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn test_degrees_and_add_node() {
+        let mut graph = Graph::new_directed(3);
+        graph.add_edge(0, 1);
+        graph.add_edge(0, 2);
+        graph.add_edge(2, 1);
+        graph.add_edge(2, 2);
+
+        assert_eq!(
+            (0..3).map(|v| graph.out_degree(v)).collect::<Vec<_>>(),
+            vec![2, 0, 2]
+        );
+        assert_eq!(
+            (0..3).map(|v| graph.in_degree(v)).collect::<Vec<_>>(),
+            vec![0, 2, 2]
+        );
+        // 转置交换入度和出度
+        let reversed = graph.reverse();
+        for v in 0..3 {
+            assert_eq!(reversed.in_degree(v), graph.out_degree(v));
+            assert_eq!(reversed.out_degree(v), graph.in_degree(v));
+        }
+
+        // 新节点一开始没有边, 之后可以和原有节点连边
+        let v = graph.add_node();
+        assert_eq!(v, 3);
+        assert_eq!((graph.in_degree(v), graph.out_degree(v)), (0, 0));
+        graph.add_edge(1, v);
+        assert_eq!(graph.bfs_with_return(0), vec![0, 1, 2, 3]);
+        assert_eq!(graph.in_degree(v), 1);
+
+        // 无向图中入度等于出度, 自环算两次
+        let mut undirected = Graph::new(0);
+        let a = undirected.add_node();
+        let b = undirected.add_node();
+        undirected.add_edge(a, b);
+        undirected.add_edge(b, b);
+        assert_eq!((undirected.in_degree(a), undirected.out_degree(a)), (1, 1));
+        assert_eq!((undirected.in_degree(b), undirected.out_degree(b)), (3, 3));
+    }
 }
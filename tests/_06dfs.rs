@@ -1,54 +1,172 @@
 // 实现基本的深度优先搜索遍历
 
-use std::collections::HashSet;
+mod common;
 
-struct Graph {
-    adj: Vec<Vec<usize>>,
-}
+use common::graph::Graph;
 
+// 图的结构和 add_edge, 度数, 转置等基本操作在 common/graph.rs 里, 和 BFS 共用
 impl Graph {
-    fn new(n: usize) -> Self {
-        Graph {
-            adj: vec![vec![]; n],
-        }
-    }
-
-    fn add_edge(&mut self, src: usize, dest: usize) {
-        self.adj[src].push(dest);
-        self.adj[dest].push(src);
-    }
-
-    fn dfs_util(&self, v: usize, visited: &mut HashSet<usize>, visit_order: &mut Vec<usize>) {
-        //TODO
-        // 标记当前节点为已访问
-        visited.insert(v);
-        // 将当前节点加入访问顺序
-        visit_order.push(v);
-        // 遍历当前节点的所有邻接节点
-        for &neighbor in &self.adj[v] {
-            // 如果邻居节点没有被访问, 那么不管其他邻居节点了, 我们立刻深入这个邻居节点继续递归调用
-            // 这里选择递归实现, 更直观体现DFS的自然回溯特性
-            // 但是如果图很深可能会调用栈溢出
-            // 这同样可以避免无限循环
-            if !visited.contains(&neighbor) {
-                self.dfs_util(neighbor, visited, visit_order);
+    // Perform a depth-first search on the graph, return the order of visited nodes
+    fn dfs(&self, start: usize) -> Vec<usize> {
+        let mut preorder = Preorder(Vec::new());
+        self.dfs_visit(start, &mut preorder);
+        preorder.0
+    }
+
+    // 从 start 出发做一次 DFS, 只访问能到达的节点
+    fn dfs_visit<V: DfsVisitor>(&self, start: usize, visitor: &mut V) -> DfsTimes {
+        self.dfs_from_roots([start], visitor)
+    }
+
+    // 按编号从小到大, 从每个还没被发现的节点开始一棵新的 DFS 树, 直到访问完所有节点
+    fn dfs_forest<V: DfsVisitor>(&self, visitor: &mut V) -> DfsTimes {
+        self.dfs_from_roots(0..self.adj.len(), visitor)
+    }
+
+    // 用显式的栈代替递归, 图再深也不会栈溢出
+    // 栈帧里记下节点和下一个要看的邻居下标, 回到这一帧时从断开的地方继续, 访问顺序和递归版本完全一样
+    // 节点的三种状态由时间戳表示: 没有发现时间的是未访问, 有发现时间没有完成时间的还在栈里
+    fn dfs_from_roots<V: DfsVisitor>(
+        &self,
+        roots: impl IntoIterator<Item = usize>,
+        visitor: &mut V,
+    ) -> DfsTimes {
+        let n = self.adj.len();
+        let mut times = DfsTimes {
+            discovery: vec![None; n],
+            finish: vec![None; n],
+        };
+        let mut time = 0;
+        // 栈帧: (节点, 下一个邻居的下标, 无向图中还没跳过的父节点)
+        let mut stack: Vec<(usize, usize, Option<usize>)> = Vec::new();
+
+        for root in roots {
+            if times.discovery[root].is_some() {
+                continue;
+            }
+            times.discovery[root] = Some(time);
+            visitor.on_discover(root, time);
+            time += 1;
+            stack.push((root, 0, None));
+
+            while let Some(frame) = stack.last_mut() {
+                let (u, next, parent) = *frame;
+                let Some(&v) = self.adj[u].get(next) else {
+                    // 所有邻居都看完了, 回溯
+                    stack.pop();
+                    times.finish[u] = Some(time);
+                    visitor.on_finish(u, time);
+                    time += 1;
+                    continue;
+                };
+                frame.1 += 1;
+                // 无向图中每条边在两端各存一份, 孩子回看父节点的那一份就是树边本身
+                // 只跳过一次, 父子之间的平行边仍然算回边
+                if !self.directed && parent == Some(v) {
+                    frame.2 = None;
+                    continue;
+                }
+                match (times.discovery[v], times.finish[v]) {
+                    (None, _) => {
+                        visitor.on_tree_edge(u, v);
+                        times.discovery[v] = Some(time);
+                        visitor.on_discover(v, time);
+                        time += 1;
+                        stack.push((v, 0, Some(u)));
+                    }
+                    (Some(_), None) => visitor.on_back_edge(u, v),
+                    // 无向图中 v 已经完成, 说明这条边已经在 v 那一端作为回边报告过了
+                    (Some(_), Some(_)) if !self.directed => {}
+                    (Some(discovered), Some(_)) => {
+                        if times.discovery[u] < Some(discovered) {
+                            visitor.on_forward_edge(u, v);
+                        } else {
+                            visitor.on_cross_edge(u, v);
+                        }
+                    }
+                }
             }
         }
+        times
     }
 
-    // Perform a depth-first search on the graph, return the order of visited nodes
-    fn dfs(&self, start: usize) -> Vec<usize> {
-        let mut visited = HashSet::new();
-        let mut visit_order = Vec::new();
-        self.dfs_util(start, &mut visited, &mut visit_order);
-        visit_order
+    // 拓扑排序: 按完成时间从晚到早排列, 有环时返回 None
+    // 只对有向图有意义
+    fn topological_sort(&self) -> Option<Vec<usize>> {
+        struct Topo {
+            finished: Vec<usize>,
+            has_cycle: bool,
+        }
+        impl DfsVisitor for Topo {
+            fn on_finish(&mut self, v: usize, _time: usize) {
+                self.finished.push(v);
+            }
+            fn on_back_edge(&mut self, _u: usize, _v: usize) {
+                self.has_cycle = true;
+            }
+        }
+
+        let mut topo = Topo {
+            finished: Vec::new(),
+            has_cycle: false,
+        };
+        self.dfs_forest(&mut topo);
+        if topo.has_cycle {
+            return None;
+        }
+        topo.finished.reverse();
+        Some(topo.finished)
     }
 }
 
+// DFS 的访问者, 遍历到各个时刻时回调, 默认什么都不做, 只需要实现关心的方法
+// 时间戳每发现或完成一个节点加一, 所以 discovery[v] < finish[v], 子孙的区间嵌套在祖先的区间里
+// 边 u -> v 按 v 当时的状态分类, 无向图中只有树边和回边, 每条边只报告一次
+// 无向图的自环在邻接表中存了两份, 会报告两次回边
+trait DfsVisitor {
+    fn on_discover(&mut self, _v: usize, _time: usize) {}
+    fn on_finish(&mut self, _v: usize, _time: usize) {}
+    // v 第一次被发现, 这条边属于 DFS 树
+    fn on_tree_edge(&mut self, _u: usize, _v: usize) {}
+    // v 还在栈里, 是 u 的祖先 (或者 u 自己), 有向图中说明有环
+    fn on_back_edge(&mut self, _u: usize, _v: usize) {}
+    // v 已经完成, 是 u 的子孙, 但不是通过这条边发现的
+    fn on_forward_edge(&mut self, _u: usize, _v: usize) {}
+    // v 已经完成, 并且和 u 没有祖先关系
+    fn on_cross_edge(&mut self, _u: usize, _v: usize) {}
+}
+
+// 只关心时间戳时用 () 作访问者
+impl DfsVisitor for () {}
+
+// 按发现顺序记录节点
+struct Preorder(Vec<usize>);
+
+impl DfsVisitor for Preorder {
+    fn on_discover(&mut self, v: usize, _time: usize) {
+        self.0.push(v);
+    }
+}
+
+// 每个节点的发现和完成时间, 没有访问到的节点为 None
+#[derive(Debug)]
+struct DfsTimes {
+    discovery: Vec<Option<usize>>,
+    finish: Vec<Option<usize>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // 简单的线性同余生成器
+    fn lcg(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
     #[test]
     fn test_dfs_simple() {
         let mut graph = Graph::new(3);
@@ -84,4 +202,247 @@ mod tests {
         let visit_order_disconnected = graph.dfs(3);
         assert_eq!(visit_order_disconnected, vec![3, 4]);
     }
+
+    #[test]
+    fn test_dfs_directed() {
+        // 0 -> 1 -> 2 -> 0, 2 -> 3, 4 -> 3
+        let mut graph = Graph::new_directed(5);
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        graph.add_edge(2, 0);
+        graph.add_edge(2, 3);
+        graph.add_edge(4, 3);
+
+        assert_eq!(graph.dfs(0), vec![0, 1, 2, 3]);
+        assert_eq!(graph.dfs(3), vec![3]);
+        assert_eq!(graph.dfs(4), vec![4, 3]);
+
+        let reversed = graph.reverse();
+        assert_eq!(reversed.dfs(3), vec![3, 2, 1, 0, 4]);
+        assert_eq!(reversed.dfs(0), vec![0, 2, 1]);
+
+        assert_eq!(graph.out_degree(2), 2);
+        assert_eq!(graph.in_degree(3), 2);
+        assert_eq!(graph.in_degree(4), 0);
+
+        let v = graph.add_node();
+        graph.add_edge(3, v);
+        assert_eq!(graph.dfs(4), vec![4, 3, 5]);
+        assert_eq!(graph.in_degree(v), 1);
+        assert_eq!(graph.reverse().dfs(v), vec![5, 3, 2, 1, 0, 4]);
+
+        // 无向图转置后邻居的顺序不变, 即使边不是按编号顺序加入的
+        let mut undirected = Graph::new(3);
+        undirected.add_edge(0, 2);
+        undirected.add_edge(0, 1);
+        assert_eq!(undirected.dfs(0), vec![0, 2, 1]);
+        assert_eq!(undirected.reverse().adj, undirected.adj);
+        assert_eq!(undirected.reverse().dfs(0), vec![0, 2, 1]);
+    }
+
+    // 把所有回调按顺序记下来
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Discover(usize, usize),
+        Finish(usize, usize),
+        Tree(usize, usize),
+        Back(usize, usize),
+        Forward(usize, usize),
+        Cross(usize, usize),
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<Event>);
+
+    impl DfsVisitor for Recorder {
+        fn on_discover(&mut self, v: usize, time: usize) {
+            self.0.push(Event::Discover(v, time));
+        }
+        fn on_finish(&mut self, v: usize, time: usize) {
+            self.0.push(Event::Finish(v, time));
+        }
+        fn on_tree_edge(&mut self, u: usize, v: usize) {
+            self.0.push(Event::Tree(u, v));
+        }
+        fn on_back_edge(&mut self, u: usize, v: usize) {
+            self.0.push(Event::Back(u, v));
+        }
+        fn on_forward_edge(&mut self, u: usize, v: usize) {
+            self.0.push(Event::Forward(u, v));
+        }
+        fn on_cross_edge(&mut self, u: usize, v: usize) {
+            self.0.push(Event::Cross(u, v));
+        }
+    }
+
+    #[test]
+    fn test_dfs_events_directed() {
+        // 0 -> 1 -> 2 -> 0 (回边), 0 -> 2 (前向边), 3 -> 1 (横跨边), 3 -> 3 (自环)
+        let mut graph = Graph::new_directed(4);
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        graph.add_edge(2, 0);
+        graph.add_edge(0, 2);
+        graph.add_edge(3, 1);
+        graph.add_edge(3, 3);
+
+        let mut recorder = Recorder::default();
+        let times = graph.dfs_forest(&mut recorder);
+        use Event::*;
+        assert_eq!(
+            recorder.0,
+            vec![
+                Discover(0, 0),
+                Tree(0, 1),
+                Discover(1, 1),
+                Tree(1, 2),
+                Discover(2, 2),
+                Back(2, 0),
+                Finish(2, 3),
+                Finish(1, 4),
+                Forward(0, 2),
+                Finish(0, 5),
+                Discover(3, 6),
+                Cross(3, 1),
+                Back(3, 3),
+                Finish(3, 7),
+            ]
+        );
+        assert_eq!(times.discovery, vec![Some(0), Some(1), Some(2), Some(6)]);
+        assert_eq!(times.finish, vec![Some(5), Some(4), Some(3), Some(7)]);
+
+        // 只从一个起点出发时, 到不了的节点没有时间戳
+        let times = graph.dfs_visit(1, &mut ());
+        assert_eq!(times.discovery, vec![Some(2), Some(0), Some(1), None]);
+        assert_eq!(times.finish, vec![Some(3), Some(5), Some(4), None]);
+    }
+
+    #[test]
+    fn test_dfs_events_undirected() {
+        // 0 - 1 - 2 - 0 构成环, 2 - 3, 0 和 1 之间还有一条平行边
+        let mut graph = Graph::new(5);
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        graph.add_edge(2, 0);
+        graph.add_edge(2, 3);
+        graph.add_edge(1, 0);
+
+        let mut recorder = Recorder::default();
+        graph.dfs_forest(&mut recorder);
+        let edges: Vec<_> = recorder
+            .0
+            .iter()
+            .filter(|e| !matches!(e, Event::Discover(..) | Event::Finish(..)))
+            .collect();
+        use Event::*;
+        // 每条边只报告一次, 没有前向边和横跨边
+        assert_eq!(
+            edges,
+            vec![
+                &Tree(0, 1),
+                &Tree(1, 2),
+                &Back(2, 0),
+                &Tree(2, 3),
+                &Back(1, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dfs_times_random() {
+        // 随机有向图, 用括号定理检查时间戳和边的分类是否一致
+        let mut seed = 2024u64;
+        let mut next = || lcg(&mut seed) as usize;
+        for round in 0..50 {
+            let n = 1 + next() % 30;
+            let mut graph = Graph::new_directed(n);
+            for _ in 0..next() % (3 * n) {
+                graph.add_edge(next() % n, next() % n);
+            }
+
+            let mut recorder = Recorder::default();
+            let times = graph.dfs_forest(&mut recorder);
+            let d = |v: usize| times.discovery[v].unwrap();
+            let f = |v: usize| times.finish[v].unwrap();
+
+            // 时间戳是 0..2n 的一个排列
+            let mut all: Vec<_> = (0..n).flat_map(|v| [d(v), f(v)]).collect();
+            all.sort_unstable();
+            assert_eq!(all, (0..2 * n).collect::<Vec<_>>(), "round {round}");
+
+            let mut edges = 0;
+            let mut tree = 0;
+            for event in &recorder.0 {
+                match *event {
+                    Event::Tree(u, v) => {
+                        assert!(d(u) < d(v) && f(v) < f(u));
+                        tree += 1;
+                    }
+                    Event::Back(u, v) => assert!(d(v) <= d(u) && f(u) <= f(v)),
+                    Event::Forward(u, v) => assert!(d(u) < d(v) && f(v) < f(u)),
+                    Event::Cross(u, v) => assert!(f(v) < d(u)),
+                    Event::Discover(..) | Event::Finish(..) => continue,
+                }
+                edges += 1;
+            }
+            // 有向图中每条边恰好被分类一次, 树边把 n 个节点连成森林
+            assert_eq!(edges, graph.adj.iter().map(Vec::len).sum::<usize>());
+            let roots = (0..n)
+                .filter(|&v| {
+                    !recorder
+                        .0
+                        .iter()
+                        .any(|e| matches!(*e, Event::Tree(_, w) if w == v))
+                })
+                .count();
+            assert_eq!(tree + roots, n);
+        }
+    }
+
+    #[test]
+    fn test_topological_sort() {
+        // 穿衣服的顺序
+        // 0 内裤 -> 1 裤子 -> 2 鞋, 0 -> 2, 3 袜子 -> 2, 1 -> 4 腰带, 5 衬衫 -> 4, 5 -> 6 领带
+        let mut graph = Graph::new_directed(7);
+        for (src, dest) in [(0, 1), (1, 2), (0, 2), (3, 2), (1, 4), (5, 4), (5, 6)] {
+            graph.add_edge(src, dest);
+        }
+        let order = graph.topological_sort().unwrap();
+        assert_eq!(order.len(), 7);
+        let mut position = [0; 7];
+        for (i, &v) in order.iter().enumerate() {
+            position[v] = i;
+        }
+        for (src, neighbors) in graph.adj.iter().enumerate() {
+            for &dest in neighbors {
+                assert!(position[src] < position[dest]);
+            }
+        }
+
+        graph.add_edge(2, 0);
+        assert_eq!(graph.topological_sort(), None);
+    }
+
+    #[test]
+    fn test_dfs_deep_graph() {
+        // 一百万个节点的链, 递归版本会栈溢出
+        let n = 1_000_000;
+        let mut graph = Graph::new(n);
+        for v in 1..n {
+            graph.add_edge(v - 1, v);
+        }
+        let order = graph.dfs(0);
+        assert_eq!(order.len(), n);
+        assert!(order.iter().enumerate().all(|(i, &v)| i == v));
+        let times = graph.dfs_visit(n - 1, &mut ());
+        assert_eq!(times.discovery[0], Some(n - 1));
+        assert_eq!(times.finish[0], Some(n));
+        assert_eq!(times.finish[n - 1], Some(2 * n - 1));
+
+        let mut dag = Graph::new_directed(n);
+        for v in 1..n {
+            dag.add_edge(v, v - 1);
+        }
+        assert_eq!(dag.topological_sort(), Some((0..n).rev().collect()));
+    }
 }
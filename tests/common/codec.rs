@@ -0,0 +1,167 @@
+// 搜索树序列化共用的部分
+// 搜索树的形状由前序序列唯一确定, 所以各个树都只按前序写出每个节点, 不需要标记空孩子
+// 文本格式: 节点之间用空格分隔, 重数大于 1 时在节点后面加上 "*重数", 值中的空白、'*' 和反斜杠会被转义
+// 字节格式: 每个节点一个标记字节, 标记的最高位 (COUNT_FLAG) 表示值后面还跟着重数
+// 各个树只有节点上多记的信息 (高度, 颜色) 和 DecodeError 的变体不同, 那部分留在各自的文件里
+
+use super::Edge;
+use std::fmt::Write;
+
+/// 值的二进制编码
+pub trait Encode: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// 从 input 的开头解码一个值, 并让 input 跳过读掉的字节
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+impl Encode for i32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let (bytes, rest) = input.split_first_chunk()?;
+        *input = rest;
+        Some(i32::from_le_bytes(*bytes))
+    }
+}
+
+// 字符串: 变长整数表示的字节数, 后面是 UTF-8 内容
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_varint(self.len() as u64, out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::try_from(read_varint(input)?).ok()?;
+        let (bytes, rest) = input.split_at_checked(len)?;
+        *input = rest;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+// LEB128 变长整数: 每个字节的低 7 位是数据, 最高位表示后面还有字节
+pub fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+pub fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// 标记字节的最高位: 值后面跟着变长整数表示的重数
+pub const COUNT_FLAG: u8 = 0x80;
+
+/// split_count 失败的原因, 各个文件的 DecodeError 都实现了 From<CountError>
+#[derive(Debug, PartialEq)]
+pub enum CountError {
+    Zero,            // 记录的重数为 0
+    Invalid(String), // 重数不是数字, 带上整个记号
+}
+
+// "值*重数" 拆成值和重数, 没有重数时为 1
+// 值里的 '*' 都转义过了, 所以出现 '*' 时后面一定是重数
+pub fn split_count(token: &str) -> Result<(&str, usize), CountError> {
+    let Some((value, count)) = token.rsplit_once('*') else {
+        return Ok((token, 1));
+    };
+    match count.parse() {
+        Ok(0) => Err(CountError::Zero),
+        Ok(count) => Ok((value, count)),
+        Err(_) => Err(CountError::Invalid(token.to_string())),
+    }
+}
+
+// 值的 Display 里的空白和 '*' 会和分隔符混淆, 写成 "\u{十六进制码点}", 反斜杠本身写成 "\\"
+// 空值写成 "\e", 否则前序文本里会少一个记号
+pub fn escape_into(text: &str, out: &mut String) {
+    if text.is_empty() {
+        out.push_str("\\e");
+    }
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            c if c == '*' || c.is_whitespace() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+}
+
+pub fn unescape(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('\\') {
+            out.push('\\');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('e') {
+            rest = after;
+        } else {
+            let (hex, after) = rest.strip_prefix("u{")?.split_once('}')?;
+            out.push(char::from_u32(u32::from_str_radix(hex, 16).ok()?)?);
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// 按前序排列的节点中, 每个节点的父节点以及自己是哪个孩子, value 取出节点的值
+/// 不是任何一棵搜索树的前序序列 (包括有重复值) 时返回 None
+/// 栈里是还可能接上右孩子的祖先: 比栈顶小的值是栈顶的左孩子,
+/// 否则弹出所有比它小的祖先, 它是最后弹出的那个的右孩子, 之后的值都必须比那个祖先大
+pub fn preorder_parents<N, T: Ord>(
+    nodes: &[N],
+    value: impl Fn(&N) -> &T,
+) -> Option<Vec<(usize, Edge)>> {
+    let mut parents = Vec::with_capacity(nodes.len());
+    let mut stack: Vec<usize> = Vec::new();
+    let mut lower: Option<&T> = None;
+    for (i, node) in nodes.iter().enumerate() {
+        let current = value(node);
+        if lower.is_some_and(|lower| current <= lower) {
+            return None;
+        }
+        match stack.last() {
+            None => parents.push((0, Edge::Root)),
+            Some(&top) if current < value(&nodes[top]) => parents.push((top, Edge::Left)),
+            Some(_) => {
+                let mut parent = 0;
+                while let Some(&top) = stack.last()
+                    && value(&nodes[top]) < current
+                {
+                    parent = top;
+                    stack.pop();
+                }
+                // 和某个祖先相等
+                if stack
+                    .last()
+                    .is_some_and(|&top| value(&nodes[top]) == current)
+                {
+                    return None;
+                }
+                parents.push((parent, Edge::Right));
+                lower = Some(value(&nodes[parent]));
+            }
+        }
+        stack.push(i);
+    }
+    Some(parents)
+}
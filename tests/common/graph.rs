@@ -0,0 +1,76 @@
+// BFS 和 DFS 共用的无权图
+// 这里只有图的构造和基本的度量, 遍历算法在各自的文件里给 Graph 加上 impl
+
+/// 邻接表表示的图
+pub struct Graph {
+    pub adj: Vec<Vec<usize>>, // 有向图中只记录出边
+    pub directed: bool,       // 是否是有向图
+}
+
+impl Graph {
+    /// n 个节点的无向图
+    pub fn new(n: usize) -> Self {
+        Graph {
+            adj: vec![vec![]; n],
+            directed: false,
+        }
+    }
+
+    // 有向图: add_edge 只添加 src -> dest 一个方向
+    // 遍历只沿着邻接表往外走, 所以在有向图上都只沿出边前进
+    pub fn new_directed(n: usize) -> Self {
+        Graph {
+            adj: vec![vec![]; n],
+            directed: true,
+        }
+    }
+
+    pub fn add_edge(&mut self, src: usize, dest: usize) {
+        self.adj[src].push(dest);
+        if !self.directed {
+            self.adj[dest].push(src); // 对于无向图, 我们需要双向边
+        }
+    }
+
+    // 加一个没有边的新节点, 返回它的编号
+    pub fn add_node(&mut self) -> usize {
+        self.adj.push(vec![]);
+        self.adj.len() - 1
+    }
+
+    // 从 v 出发的边数, 无向图中就是 v 的度 (自环算两次)
+    pub fn out_degree(&self, v: usize) -> usize {
+        self.adj[v].len()
+    }
+
+    // 指向 v 的边数
+    // 邻接表只记录出边, 有向图需要扫描所有边, 是 O(V + E) 的
+    pub fn in_degree(&self, v: usize) -> usize {
+        if !self.directed {
+            return self.out_degree(v);
+        }
+        self.adj.iter().flatten().filter(|&&dest| dest == v).count()
+    }
+
+    // 转置: 所有边反向
+    // 无向图的边没有方向, 直接复制邻接表, 这样每个节点的邻居顺序 (也就是遍历顺序) 不变
+    // 有向图按起点从小到大把边加入新图, 所以每个节点的邻居仍然有确定的顺序
+    pub fn reverse(&self) -> Self {
+        if !self.directed {
+            return Graph {
+                adj: self.adj.clone(),
+                directed: false,
+            };
+        }
+        let mut reversed = vec![vec![]; self.adj.len()];
+        for (src, neighbors) in self.adj.iter().enumerate() {
+            for &dest in neighbors {
+                reversed[dest].push(src);
+            }
+        }
+        Graph {
+            adj: reversed,
+            directed: self.directed,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// 测试文件之间共用的代码
+// tests 目录下每个 .rs 文件都是单独的 crate, 需要的文件用 `mod common;` 引入这个目录
+// Cargo 不会把子目录里的 mod.rs 当成单独的测试, 所以这里不放 #[test]
+// 每个文件只用到其中一部分 (树的文件不用图, 图的文件不用序列化), 没用到的部分在那个 crate 里就是死代码
+#![allow(dead_code)]
+
+pub mod codec;
+pub mod graph;
+pub mod render;
+
+/// 节点是父节点的哪个孩子
+#[derive(Clone, Copy, PartialEq)]
+pub enum Edge {
+    Root,
+    Left,
+    Right,
+}
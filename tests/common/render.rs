@@ -0,0 +1,101 @@
+// 二叉树的可视化
+// 调试旋转时比派生的 Debug 好读得多
+// 节点上显示什么由各个树自己决定, 这里只负责画出形状; 都用显式的栈, 树退化成链表也不会栈溢出
+
+use super::Edge;
+use std::fmt::Write;
+
+/// 能画出来的二叉树节点
+pub trait RenderNode {
+    fn left(&self) -> Option<&Self>;
+
+    fn right(&self) -> Option<&Self>;
+
+    /// 横向打印时节点上的文字
+    fn ascii_label(&self) -> String;
+
+    /// DOT 中节点的属性, 至少要有 label
+    fn dot_attrs(&self) -> String;
+
+    /// DOT 中指向这个节点的边附加的属性, 比如 " [color=red]", 默认没有
+    fn dot_edge_attrs(&self) -> &'static str {
+        ""
+    }
+}
+
+/// DOT 字符串里的反斜杠和引号需要转义
+pub fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// 横向打印: 根在最左边, 右子树画在上面, 左子树画在下面
+/// 所以按 右子树, 自己, 左子树 的顺序输出; 栈里的 bool 表示孩子是否已经入栈, 入栈之后再弹出时输出自己
+pub fn write_ascii<N: RenderNode>(root: &N, out: &mut String) {
+    let mut stack = vec![(root, String::new(), Edge::Root, false)];
+    while let Some((node, prefix, edge, expanded)) = stack.pop() {
+        if expanded {
+            let connector = match edge {
+                Edge::Root => "",
+                Edge::Right => "/-- ",
+                Edge::Left => "\\-- ",
+            };
+            writeln!(out, "{prefix}{connector}{}", node.ascii_label()).unwrap();
+            continue;
+        }
+        // 如果自己是左孩子, 右子树和父节点之间要画竖线; 右孩子的左子树同理
+        if let Some(left) = node.left() {
+            let pad = if edge == Edge::Right { "|   " } else { "    " };
+            stack.push((left, format!("{prefix}{pad}"), Edge::Left, false));
+        }
+        let right = node.right().map(|right| {
+            let pad = if edge == Edge::Left { "|   " } else { "    " };
+            (right, format!("{prefix}{pad}"), Edge::Right, false)
+        });
+        stack.push((node, prefix, edge, true));
+        stack.extend(right);
+    }
+}
+
+/// 导出 Graphviz DOT 的节点和边, 可以用 `dot -Tpng` 画出来
+/// 按前序给节点编号, 孩子的整棵子树输出完之后才输出指向它的边
+/// 栈里的 Node 是待输出的节点和父节点的编号, Nil 是空孩子占位的点, Link 是一条边
+pub fn write_dot<N: RenderNode>(root: &N, out: &mut String) {
+    enum Step<'a, N> {
+        Node(&'a N, Option<usize>),
+        Nil(usize),
+        Link(usize, usize, &'static str),
+    }
+    let mut next_id = 0;
+    let mut stack = vec![Step::Node(root, None)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Node(node, parent) => {
+                let id = next_id;
+                next_id += 1;
+                writeln!(out, "    n{id} [{}];", node.dot_attrs()).unwrap();
+                if let Some(parent) = parent {
+                    stack.push(Step::Link(parent, id, node.dot_edge_attrs()));
+                }
+                // 只有一个孩子时给空的一边画一个点, 这样能分清左右; 先压右孩子, 左孩子先输出
+                let children = [node.right(), node.left()];
+                if children.iter().any(Option::is_some) {
+                    for child in children {
+                        stack.push(match child {
+                            Some(child) => Step::Node(child, Some(id)),
+                            None => Step::Nil(id),
+                        });
+                    }
+                }
+            }
+            Step::Nil(parent) => {
+                let nil = next_id;
+                next_id += 1;
+                writeln!(out, "    n{nil} [shape=point];").unwrap();
+                writeln!(out, "    n{parent} -> n{nil};").unwrap();
+            }
+            Step::Link(parent, child, attrs) => {
+                writeln!(out, "    n{parent} -> n{child}{attrs};").unwrap();
+            }
+        }
+    }
+}